@@ -0,0 +1,18 @@
+DROP TRIGGER IF EXISTS decrement_prod_count_trigger_on_delete ON products;
+DROP FUNCTION IF EXISTS update_store_product_count_on_delete();
+
+CREATE OR REPLACE FUNCTION update_store_product_count()
+RETURNS TRIGGER
+AS $$
+BEGIN
+  IF OLD.store_id IS NULL AND NEW.store_id IS NOT NULL THEN
+    UPDATE stores SET prod_count = prod_count + 1 WHERE id = NEW.store_id;
+  ELSEIF OLD.store_id IS NOT NULL AND NEW.store_id IS NOT NULL THEN
+    UPDATE stores SET prod_count = prod_count + 1 WHERE id = NEW.store_id;
+    UPDATE stores SET prod_count = prod_count - 1 WHERE id = OLD.store_id AND prod_count > 0;
+  ELSEIF OLD.store_id IS NOT NULL AND NEW.store_id IS NULL THEN
+    UPDATE stores SET prod_count = prod_count - 1 WHERE id = OLD.store_id AND prod_count > 0;
+  END IF;
+  RETURN NEW;
+END;
+$$ LANGUAGE PLPGSQL;
@@ -0,0 +1,33 @@
+-- Only moves between stores change the counts, a product keeping its store is a no-op
+CREATE OR REPLACE FUNCTION update_store_product_count()
+RETURNS TRIGGER
+AS $$
+BEGIN
+  IF OLD.store_id IS NOT DISTINCT FROM NEW.store_id THEN
+    RETURN NEW;
+  END IF;
+  IF OLD.store_id IS NOT NULL THEN
+    UPDATE stores SET prod_count = prod_count - 1 WHERE id = OLD.store_id AND prod_count > 0;
+  END IF;
+  IF NEW.store_id IS NOT NULL THEN
+    UPDATE stores SET prod_count = prod_count + 1 WHERE id = NEW.store_id;
+  END IF;
+  RETURN NEW;
+END;
+$$ LANGUAGE PLPGSQL;
+
+CREATE OR REPLACE FUNCTION update_store_product_count_on_delete()
+RETURNS TRIGGER
+AS $$
+BEGIN
+  IF OLD.store_id IS NOT NULL THEN
+    UPDATE stores SET prod_count = prod_count - 1 WHERE id = OLD.store_id AND prod_count > 0;
+  END IF;
+  RETURN OLD;
+END;
+$$ LANGUAGE PLPGSQL;
+
+CREATE OR REPLACE TRIGGER decrement_prod_count_trigger_on_delete AFTER DELETE ON products FOR ROW EXECUTE PROCEDURE update_store_product_count_on_delete();
+
+-- Counts drifted while deletes were not tracked
+UPDATE stores SET prod_count = (SELECT COUNT(*) FROM products WHERE products.store_id = stores.id);
@@ -0,0 +1,5 @@
+ALTER TABLE worktimes
+  ALTER COLUMN am_open TYPE VARCHAR(5) USING to_char(am_open, 'HH12:MI'),
+  ALTER COLUMN am_close TYPE VARCHAR(5) USING to_char(am_close, 'HH12:MI'),
+  ALTER COLUMN pm_open TYPE VARCHAR(5) USING to_char(pm_open, 'HH12:MI'),
+  ALTER COLUMN pm_close TYPE VARCHAR(5) USING to_char(pm_close, 'HH12:MI');
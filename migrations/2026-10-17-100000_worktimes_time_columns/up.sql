@@ -0,0 +1,8 @@
+-- Times were 12-hour without a marker, afternoon slots get their 12 hours back
+ALTER TABLE worktimes
+  ALTER COLUMN am_open TYPE TIME USING am_open::TIME,
+  ALTER COLUMN am_close TYPE TIME USING am_close::TIME,
+  ALTER COLUMN pm_open TYPE TIME USING
+    CASE WHEN pm_open < '12:00' THEN pm_open::TIME + INTERVAL '12 hours' ELSE pm_open::TIME END,
+  ALTER COLUMN pm_close TYPE TIME USING
+    CASE WHEN pm_close < '12:00' THEN pm_close::TIME + INTERVAL '12 hours' ELSE pm_close::TIME END;
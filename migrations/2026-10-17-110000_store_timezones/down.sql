@@ -0,0 +1,2 @@
+ALTER TABLE stores ALTER COLUMN created_at TYPE TIMESTAMP USING created_at AT TIME ZONE 'UTC';
+ALTER TABLE stores DROP COLUMN timezone;
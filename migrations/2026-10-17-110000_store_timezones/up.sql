@@ -0,0 +1,3 @@
+-- Worktimes are read in the store zone, naive timestamps were written in UTC
+ALTER TABLE stores ADD COLUMN timezone VARCHAR(64) NOT NULL DEFAULT 'UTC';
+ALTER TABLE stores ALTER COLUMN created_at TYPE TIMESTAMPTZ USING created_at AT TIME ZONE 'UTC';
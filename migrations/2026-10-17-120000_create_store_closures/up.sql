@@ -0,0 +1,19 @@
+-- Dated exceptions to the weekly worktimes, the days from starts_on to ends_on
+-- use these hours instead. No hours at all means closed
+CREATE TABLE store_closures (
+  id SERIAL NOT NULL,
+  store_id INT NOT NULL,
+  starts_on DATE NOT NULL,
+  ends_on DATE NOT NULL,
+  am_open TIME,
+  am_close TIME,
+  pm_open TIME,
+  pm_close TIME,
+  reason VARCHAR(256),
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  CONSTRAINT closure_range CHECK (starts_on <= ends_on),
+  FOREIGN KEY (store_id) REFERENCES stores(id) ON DELETE CASCADE,
+  PRIMARY KEY (id)
+);
+
+CREATE INDEX store_closures_store_id_starts_on ON store_closures (store_id, starts_on);
@@ -0,0 +1,9 @@
+DROP TRIGGER categories_bump_version ON categories;
+DROP TRIGGER stores_bump_version ON stores;
+DROP TRIGGER products_bump_version ON products;
+
+ALTER TABLE categories DROP COLUMN version, DROP COLUMN updated_at;
+ALTER TABLE stores DROP COLUMN version, DROP COLUMN updated_at;
+ALTER TABLE products DROP COLUMN version, DROP COLUMN updated_at;
+
+DROP FUNCTION bump_version();
@@ -0,0 +1,25 @@
+-- Row version for optimistic concurrency, every update of a row bumps it
+CREATE FUNCTION bump_version() RETURNS TRIGGER AS $$
+BEGIN
+  NEW.version := OLD.version + 1;
+  NEW.updated_at := NOW();
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+ALTER TABLE products
+  ADD COLUMN version INT NOT NULL DEFAULT 1,
+  ADD COLUMN updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+ALTER TABLE stores
+  ADD COLUMN version INT NOT NULL DEFAULT 1,
+  ADD COLUMN updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+ALTER TABLE categories
+  ADD COLUMN version INT NOT NULL DEFAULT 1,
+  ADD COLUMN updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+
+CREATE TRIGGER products_bump_version BEFORE UPDATE ON products
+  FOR EACH ROW EXECUTE PROCEDURE bump_version();
+CREATE TRIGGER stores_bump_version BEFORE UPDATE ON stores
+  FOR EACH ROW EXECUTE PROCEDURE bump_version();
+CREATE TRIGGER categories_bump_version BEFORE UPDATE ON categories
+  FOR EACH ROW EXECUTE PROCEDURE bump_version();
@@ -0,0 +1,41 @@
+CREATE OR REPLACE FUNCTION update_store_product_count()
+RETURNS TRIGGER
+AS $$
+BEGIN
+  IF OLD.store_id IS NOT DISTINCT FROM NEW.store_id THEN
+    RETURN NEW;
+  END IF;
+  IF OLD.store_id IS NOT NULL THEN
+    UPDATE stores SET prod_count = prod_count - 1 WHERE id = OLD.store_id AND prod_count > 0;
+  END IF;
+  IF NEW.store_id IS NOT NULL THEN
+    UPDATE stores SET prod_count = prod_count + 1 WHERE id = NEW.store_id;
+  END IF;
+  RETURN NEW;
+END;
+$$ LANGUAGE PLPGSQL;
+
+CREATE OR REPLACE FUNCTION update_store_product_count_on_delete()
+RETURNS TRIGGER
+AS $$
+BEGIN
+  IF OLD.store_id IS NOT NULL THEN
+    UPDATE stores SET prod_count = prod_count - 1 WHERE id = OLD.store_id AND prod_count > 0;
+  END IF;
+  RETURN OLD;
+END;
+$$ LANGUAGE PLPGSQL;
+
+-- Soft deleted rows would come back as live ones
+DELETE FROM products WHERE deleted_at IS NOT NULL;
+UPDATE products SET store_id = NULL
+  WHERE store_id IN (SELECT id FROM stores WHERE deleted_at IS NOT NULL);
+DELETE FROM stores WHERE deleted_at IS NOT NULL;
+DELETE FROM categories WHERE deleted_at IS NOT NULL;
+
+DROP INDEX categories_name_key;
+ALTER TABLE categories ADD CONSTRAINT categories_name_key UNIQUE (name);
+
+ALTER TABLE categories DROP COLUMN deleted_at;
+ALTER TABLE stores DROP COLUMN deleted_at;
+ALTER TABLE products DROP COLUMN deleted_at;
@@ -0,0 +1,40 @@
+-- Deleted rows are kept around until purged, NULL means live
+ALTER TABLE products ADD COLUMN deleted_at TIMESTAMPTZ;
+ALTER TABLE stores ADD COLUMN deleted_at TIMESTAMPTZ;
+ALTER TABLE categories ADD COLUMN deleted_at TIMESTAMPTZ;
+
+-- A deleted category no longer holds its name
+ALTER TABLE categories DROP CONSTRAINT categories_name_key;
+CREATE UNIQUE INDEX categories_name_key ON categories (name) WHERE deleted_at IS NULL;
+
+-- Only live products count, deleting or restoring one moves it out of or back into its store
+CREATE OR REPLACE FUNCTION update_store_product_count()
+RETURNS TRIGGER
+AS $$
+DECLARE
+  old_store INT := CASE WHEN OLD.deleted_at IS NULL THEN OLD.store_id END;
+  new_store INT := CASE WHEN NEW.deleted_at IS NULL THEN NEW.store_id END;
+BEGIN
+  IF old_store IS NOT DISTINCT FROM new_store THEN
+    RETURN NEW;
+  END IF;
+  IF old_store IS NOT NULL THEN
+    UPDATE stores SET prod_count = prod_count - 1 WHERE id = old_store AND prod_count > 0;
+  END IF;
+  IF new_store IS NOT NULL THEN
+    UPDATE stores SET prod_count = prod_count + 1 WHERE id = new_store;
+  END IF;
+  RETURN NEW;
+END;
+$$ LANGUAGE PLPGSQL;
+
+CREATE OR REPLACE FUNCTION update_store_product_count_on_delete()
+RETURNS TRIGGER
+AS $$
+BEGIN
+  IF OLD.store_id IS NOT NULL AND OLD.deleted_at IS NULL THEN
+    UPDATE stores SET prod_count = prod_count - 1 WHERE id = OLD.store_id AND prod_count > 0;
+  END IF;
+  RETURN OLD;
+END;
+$$ LANGUAGE PLPGSQL;
@@ -0,0 +1,14 @@
+-- One row per write, `before`/`after` only hold the fields that changed
+CREATE TABLE audit_events (
+  id SERIAL PRIMARY KEY,
+  entity_type VARCHAR(32) NOT NULL,
+  entity_id INT NOT NULL,
+  action VARCHAR(32) NOT NULL,
+  actor VARCHAR(256) NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  before JSONB,
+  after JSONB
+);
+
+CREATE INDEX audit_events_entity ON audit_events (entity_type, entity_id, created_at);
+CREATE INDEX audit_events_created_at ON audit_events (created_at);
@@ -0,0 +1,3 @@
+DROP TRIGGER products_record_price ON products;
+DROP FUNCTION record_product_price();
+DROP TABLE product_prices;
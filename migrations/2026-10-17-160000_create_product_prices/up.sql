@@ -0,0 +1,30 @@
+-- Every price a product had, each one holds from effective_from until the next
+CREATE TABLE product_prices (
+  id SERIAL NOT NULL,
+  product_id INT NOT NULL,
+  price NUMERIC NOT NULL,
+  effective_from TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE CASCADE,
+  PRIMARY KEY (id)
+);
+
+CREATE INDEX product_prices_product_id_effective_from
+  ON product_prices (product_id, effective_from);
+
+-- The current prices are all that is known of the past. A product never
+-- updated has had it since its creation, the others since their last update
+INSERT INTO product_prices (product_id, price, effective_from)
+  SELECT id, price, CASE WHEN version = 1 THEN created_at AT TIME ZONE 'UTC' ELSE updated_at END
+  FROM products;
+
+CREATE FUNCTION record_product_price() RETURNS TRIGGER AS $$
+BEGIN
+  IF TG_OP = 'INSERT' OR NEW.price IS DISTINCT FROM OLD.price THEN
+    INSERT INTO product_prices (product_id, price) VALUES (NEW.id, NEW.price);
+  END IF;
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER products_record_price AFTER INSERT OR UPDATE OF price ON products
+  FOR EACH ROW EXECUTE PROCEDURE record_product_price();
@@ -0,0 +1,5 @@
+DROP TRIGGER products_record_price ON products;
+ALTER TABLE product_prices ALTER COLUMN price TYPE NUMERIC;
+ALTER TABLE products ALTER COLUMN price TYPE NUMERIC(9, 2);
+CREATE TRIGGER products_record_price AFTER INSERT OR UPDATE OF price ON products
+  FOR EACH ROW EXECUTE PROCEDURE record_product_price();
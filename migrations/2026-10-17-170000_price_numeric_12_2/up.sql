@@ -0,0 +1,7 @@
+-- Prices are exact amounts of at most two decimals, the history keeps the same
+-- precision as the products it records. The price trigger pins the column type
+DROP TRIGGER products_record_price ON products;
+ALTER TABLE products ALTER COLUMN price TYPE NUMERIC(12, 2);
+ALTER TABLE product_prices ALTER COLUMN price TYPE NUMERIC(12, 2);
+CREATE TRIGGER products_record_price AFTER INSERT OR UPDATE OF price ON products
+  FOR EACH ROW EXECUTE PROCEDURE record_product_price();
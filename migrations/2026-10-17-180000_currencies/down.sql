@@ -0,0 +1,3 @@
+DROP TABLE exchange_rates;
+DROP TABLE product_currency_prices;
+ALTER TABLE stores DROP COLUMN currency;
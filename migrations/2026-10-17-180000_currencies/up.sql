@@ -0,0 +1,28 @@
+-- Product prices are in the currency of their store, EUR for the products
+-- without one
+ALTER TABLE stores
+  ADD COLUMN currency VARCHAR(3) NOT NULL DEFAULT 'EUR'
+  CONSTRAINT stores_currency_code CHECK (currency ~ '^[A-Z]{3}$');
+
+-- Prices set by hand in other currencies, they win over converted prices
+CREATE TABLE product_currency_prices (
+  product_id INT NOT NULL,
+  currency VARCHAR(3) NOT NULL CONSTRAINT product_currency_prices_code CHECK (currency ~ '^[A-Z]{3}$'),
+  price NUMERIC(12, 2) NOT NULL,
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE CASCADE,
+  PRIMARY KEY (product_id, currency)
+);
+
+-- One unit of from_currency is worth rate units of to_currency, the reverse
+-- conversion uses the inverse when it has no row of its own
+CREATE TABLE exchange_rates (
+  id SERIAL NOT NULL,
+  from_currency VARCHAR(3) NOT NULL CONSTRAINT exchange_rates_from_code CHECK (from_currency ~ '^[A-Z]{3}$'),
+  to_currency VARCHAR(3) NOT NULL CONSTRAINT exchange_rates_to_code CHECK (to_currency ~ '^[A-Z]{3}$'),
+  rate NUMERIC(18, 8) NOT NULL CONSTRAINT exchange_rates_positive CHECK (rate > 0),
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  CONSTRAINT exchange_rates_pair UNIQUE (from_currency, to_currency),
+  CONSTRAINT exchange_rates_distinct CHECK (from_currency <> to_currency),
+  PRIMARY KEY (id)
+);
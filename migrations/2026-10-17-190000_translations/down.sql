@@ -0,0 +1,10 @@
+ALTER TABLE products ADD COLUMN i18n_name VARCHAR(256), ADD COLUMN i18n_description TEXT;
+
+UPDATE products SET i18n_name = LEFT(t.value, 256)
+FROM translations t
+WHERE t.entity_type = 'product' AND t.entity_id = products.id AND t.field = 'name' AND t.locale = 'fr';
+UPDATE products SET i18n_description = t.value
+FROM translations t
+WHERE t.entity_type = 'product' AND t.entity_id = products.id AND t.field = 'description' AND t.locale = 'fr';
+
+DROP TABLE translations;
@@ -0,0 +1,29 @@
+-- Localized text of the translatable fields, the entity columns hold the
+-- default locale
+CREATE TABLE translations (
+  id SERIAL NOT NULL,
+  entity_type VARCHAR(32) NOT NULL,
+  entity_id INT NOT NULL,
+  field VARCHAR(32) NOT NULL,
+  locale VARCHAR(35) NOT NULL,
+  value TEXT NOT NULL,
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  CONSTRAINT translations_field CHECK (
+    (entity_type, field) IN (
+      ('product', 'name'),
+      ('product', 'description'),
+      ('category', 'name'),
+      ('store', 'name')
+    )
+  ),
+  CONSTRAINT translations_unique UNIQUE (entity_type, entity_id, field, locale),
+  PRIMARY KEY (id)
+);
+
+-- The alternate language of the i18n columns was French
+INSERT INTO translations (entity_type, entity_id, field, locale, value)
+SELECT 'product', id, 'name', 'fr', i18n_name FROM products WHERE i18n_name IS NOT NULL;
+INSERT INTO translations (entity_type, entity_id, field, locale, value)
+SELECT 'product', id, 'description', 'fr', i18n_description FROM products WHERE i18n_description IS NOT NULL;
+
+ALTER TABLE products DROP COLUMN i18n_name, DROP COLUMN i18n_description;
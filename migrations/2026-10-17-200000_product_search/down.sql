@@ -0,0 +1,10 @@
+DROP INDEX products_search_vector_idx;
+DROP TRIGGER products_search_vector ON products;
+DROP FUNCTION refresh_product_search();
+ALTER TABLE products DROP COLUMN search_vector;
+DROP FUNCTION product_search_query(TEXT, TEXT[]);
+DROP AGGREGATE tsquery_or_agg(tsquery);
+DROP FUNCTION product_search_text(INT, TEXT, TEXT);
+DROP FUNCTION product_search_vector(INT, TEXT, TEXT);
+DROP AGGREGATE tsvector_agg(tsvector);
+DROP FUNCTION locale_search_config(TEXT);
@@ -0,0 +1,98 @@
+-- Text search configuration of a BCP 47 locale, by its language subtag
+CREATE FUNCTION locale_search_config(locale TEXT) RETURNS regconfig AS $$
+  SELECT (CASE split_part(lower(locale), '-', 1)
+    WHEN 'da' THEN 'danish'
+    WHEN 'de' THEN 'german'
+    WHEN 'en' THEN 'english'
+    WHEN 'es' THEN 'spanish'
+    WHEN 'fi' THEN 'finnish'
+    WHEN 'fr' THEN 'french'
+    WHEN 'hu' THEN 'hungarian'
+    WHEN 'it' THEN 'italian'
+    WHEN 'nb' THEN 'norwegian'
+    WHEN 'nl' THEN 'dutch'
+    WHEN 'no' THEN 'norwegian'
+    WHEN 'pt' THEN 'portuguese'
+    WHEN 'ro' THEN 'romanian'
+    WHEN 'ru' THEN 'russian'
+    WHEN 'sv' THEN 'swedish'
+    WHEN 'tr' THEN 'turkish'
+    ELSE 'simple'
+  END)::regconfig
+$$ LANGUAGE SQL IMMUTABLE;
+
+CREATE AGGREGATE tsvector_agg (tsvector) (
+  SFUNC = tsvector_concat,
+  STYPE = tsvector,
+  INITCOND = ''
+);
+
+-- Name (weight A) and description (weight B) of a product in the default
+-- locale and in every translation, each stemmed in its own language. The
+-- simple configuration keeps the words as written
+CREATE FUNCTION product_search_vector(product_id INT, name TEXT, description TEXT) RETURNS tsvector AS $$
+  SELECT
+    setweight(to_tsvector('simple', name) || to_tsvector('english', name), 'A')
+    || setweight(
+      to_tsvector('simple', coalesce(description, ''))
+        || to_tsvector('english', coalesce(description, '')),
+      'B'
+    )
+    || coalesce((
+      SELECT tsvector_agg(setweight(
+        to_tsvector('simple', t.value) || to_tsvector(locale_search_config(t.locale), t.value),
+        (CASE t.field WHEN 'name' THEN 'A' ELSE 'B' END)::"char"
+      ))
+      FROM translations t
+      WHERE t.entity_type = 'product' AND t.entity_id = product_id
+    ), '')
+$$ LANGUAGE SQL STABLE;
+
+-- Text the snippets are cut from
+CREATE FUNCTION product_search_text(product_id INT, name TEXT, description TEXT) RETURNS TEXT AS $$
+  SELECT concat_ws(' … ', name, description, (
+    SELECT string_agg(t.value, ' … ' ORDER BY t.field DESC, t.locale)
+    FROM translations t
+    WHERE t.entity_type = 'product' AND t.entity_id = product_id
+  ))
+$$ LANGUAGE SQL STABLE;
+
+CREATE AGGREGATE tsquery_or_agg (tsquery) (
+  SFUNC = tsquery_or,
+  STYPE = tsquery
+);
+
+-- Search text in the default locale and in the languages of `locales`, words
+-- in no language in particular still match as written. Configurations are not
+-- mixed any further, `-word` would not exclude the words stemmed differently
+CREATE FUNCTION product_search_query(q TEXT, locales TEXT[]) RETURNS tsquery AS $$
+  SELECT tsquery_or_agg(websearch_to_tsquery(config, q))
+  FROM (
+    SELECT DISTINCT config
+    FROM unnest(
+      ARRAY['simple', 'english']::regconfig[]
+        || ARRAY(SELECT locale_search_config(locale) FROM unnest(locales) locale)
+    ) config
+  ) configs
+$$ LANGUAGE SQL IMMUTABLE;
+
+ALTER TABLE products ADD COLUMN search_vector tsvector NOT NULL DEFAULT ''::tsvector;
+
+-- Refreshed on every write of the product. Translation writes touch the
+-- product they belong to, which picks them up
+CREATE FUNCTION refresh_product_search() RETURNS TRIGGER AS $$
+BEGIN
+  NEW.search_vector := product_search_vector(NEW.id, NEW.name, NEW.description);
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER products_search_vector BEFORE INSERT OR UPDATE ON products
+  FOR EACH ROW EXECUTE PROCEDURE refresh_product_search();
+
+-- The backfill is no change of the products, their versions are left alone
+ALTER TABLE products DISABLE TRIGGER products_bump_version;
+UPDATE products SET search_vector = product_search_vector(id, name, description);
+ALTER TABLE products ENABLE TRIGGER products_bump_version;
+
+CREATE INDEX products_search_vector_idx ON products USING GIN (search_vector);
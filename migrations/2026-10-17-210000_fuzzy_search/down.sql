@@ -0,0 +1,8 @@
+DROP INDEX translations_name_trgm_idx;
+DROP INDEX categories_name_trgm_idx;
+DROP INDEX stores_name_trgm_idx;
+DROP INDEX products_name_trgm_idx;
+DROP FUNCTION fuzzy_similarity(TEXT, TEXT, INT, TEXT);
+DROP FUNCTION fuzzy_text(TEXT);
+-- pg_trgm and unaccent may have been installed before this migration or be
+-- used by other objects, they stay
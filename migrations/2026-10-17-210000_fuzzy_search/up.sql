@@ -0,0 +1,27 @@
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+CREATE EXTENSION IF NOT EXISTS unaccent;
+
+-- unaccent() is only STABLE as it looks its dictionary up by name, given the
+-- dictionary it can back an index
+CREATE FUNCTION fuzzy_text(value TEXT) RETURNS TEXT
+    LANGUAGE sql IMMUTABLE PARALLEL SAFE STRICT
+    AS $$ SELECT lower(public.unaccent('public.unaccent'::regdictionary, value)) $$;
+
+-- Best word similarity of the query to the name of an entity or to one of its
+-- translations, accents and case aside
+CREATE FUNCTION fuzzy_similarity(query TEXT, entity TEXT, id INT, name TEXT) RETURNS REAL
+    LANGUAGE sql STABLE PARALLEL SAFE
+    AS $$
+    SELECT GREATEST(
+        word_similarity(fuzzy_text(query), fuzzy_text(name)),
+        (SELECT max(word_similarity(fuzzy_text(query), fuzzy_text(t.value)))
+           FROM translations t
+          WHERE t.entity_type = entity AND t.entity_id = id AND t.field = 'name')
+    )
+    $$;
+
+CREATE INDEX products_name_trgm_idx ON products USING GIN (fuzzy_text(name) gin_trgm_ops);
+CREATE INDEX stores_name_trgm_idx ON stores USING GIN (fuzzy_text(name) gin_trgm_ops);
+CREATE INDEX categories_name_trgm_idx ON categories USING GIN (fuzzy_text(name) gin_trgm_ops);
+CREATE INDEX translations_name_trgm_idx ON translations USING GIN (fuzzy_text(value) gin_trgm_ops)
+    WHERE field = 'name';
@@ -0,0 +1,3 @@
+DROP INDEX stores_name_prefix_idx;
+DROP INDEX categories_name_prefix_idx;
+DROP INDEX products_name_prefix_idx;
@@ -0,0 +1,8 @@
+-- Prefix lookups of the suggestions, `LIKE 'abc%'` on the normalized names of
+-- the live rows
+CREATE INDEX products_name_prefix_idx ON products (fuzzy_text(name) text_pattern_ops)
+    WHERE deleted_at IS NULL;
+CREATE INDEX categories_name_prefix_idx ON categories (fuzzy_text(name) text_pattern_ops)
+    WHERE deleted_at IS NULL;
+CREATE INDEX stores_name_prefix_idx ON stores (fuzzy_text(name) text_pattern_ops)
+    WHERE deleted_at IS NULL;
@@ -6,6 +6,7 @@ pub struct Config {
     server_addr: String,
     db_url: String,
     pool_size: u32,
+    max_per_page: i64,
 }
 
 impl Config {
@@ -22,6 +23,10 @@ impl Config {
                 .unwrap_or("10".to_owned())
                 .parse()
                 .expect("wrong POOL_SIZE provided in .env"),
+            max_per_page: std::env::var("MAX_PER_PAGE")
+                .unwrap_or("100".to_owned())
+                .parse()
+                .expect("wrong MAX_PER_PAGE provided in .env"),
         }
     }
 
@@ -40,4 +45,8 @@ impl Config {
     pub fn get_pool_size(&self) -> &u32 {
         &self.pool_size
     }
+
+    pub fn get_max_per_page(&self) -> &i64 {
+        &self.max_per_page
+    }
 }
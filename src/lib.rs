@@ -0,0 +1,6 @@
+pub mod config;
+pub mod models;
+pub mod repos;
+pub mod routes;
+pub mod schema;
+pub mod utils;
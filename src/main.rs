@@ -1,27 +1,25 @@
-use crate::{
+use fs_store::{
     config::Config,
     models::{
-        Category, CategoryDto, CategoryId, PaginatedResult, Product, ProductDto, QResult, Store,
-        StoreId, UpdateCategoryDto, UpdateProductDto, CreateStoreDto, UpdateStoreDto
+        AuditEvent, Category, CategoryDto, CategoryResult, CategoryId, CursorPaginatedResult, PaginatedResult, PriceRange, Product, ProductDetails, ProductDto, ProductExpand, ProductsResult, QResult, Store,
+        StoreId, UpdateCategoryDto, UpdateProductDto, CreateStoreDto, UpdateStoreDto, StoreResult,
+        StoreStatus, Worktimes, StoreClosure, ClosureDto, PatchProductDto, PatchStoreDto, PatchCategoryDto,
+        ProductPrice, PriceAt, InCurrency, ProductCurrencyPrice, CurrencyPriceDto, ExchangeRate,
+        ExchangeRateDto, UpdateExchangeRateDto, TextSearch, Translation, TranslationDto, UpdateTranslationDto,
+        Suggestion,
     },
-    repos::pagination::PaginationDto,
+    repos::{pagination::PaginationDto, purge_repo::Purged},
     routes::{
-        init_category_routes, init_product_routes, init_store_routes, ManyIdsDto, OrderBy, SearchBy, StoresOrderBy, DateFilter
+        self, init_routes, ManyIdsDto, OrderBy, SearchBy, StoresOrderBy, DateFilter, OpenAt, StatusAt, PurgeBefore, AuditFilter,
+        TranslationFilter, SuggestQuery
     },
-    utils::{create_conn_pool, server_running, AppData, ValidationErrorJsonPayload},
+    utils::{create_conn_pool, server_running, AppData, ErrorJsonPayload},
 };
 use actix_cors::Cors;
 use actix_web::{self, main, web, App, HttpServer};
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
-mod config;
-mod models;
-mod repos;
-mod routes;
-mod schema;
-mod utils;
-
 #[main]
 async fn main() -> std::io::Result<()> {
     let config: Config = Config::load_config();
@@ -32,46 +30,113 @@ async fn main() -> std::io::Result<()> {
     #[openapi(
         paths(
             routes::product_routes::get,
+            routes::product_routes::get_prices,
+            routes::product_routes::get_currency_prices,
+            routes::product_routes::set_currency_price,
+            routes::product_routes::delete_currency_price,
             routes::product_routes::get_many,
             routes::product_routes::post,
             routes::product_routes::delete,
+            routes::product_routes::restore,
             routes::product_routes::update,
+            routes::product_routes::patch,
             routes::product_routes::attach_category,
             routes::product_routes::attach_store,
             routes::product_routes::dettach_category,
             routes::category_routes::get,
             routes::category_routes::get_many,
             routes::category_routes::post,
+            routes::category_routes::patch,
             routes::category_routes::delete,
+            routes::category_routes::restore,
             routes::category_routes::delete_many,
             routes::store_routes::get,
             routes::store_routes::get_many,
             routes::store_routes::post,
             routes::store_routes::update,
+            routes::store_routes::patch,
             routes::store_routes::delete,
+            routes::store_routes::restore,
             routes::store_routes::product_count,
+            routes::store_routes::recount,
+            routes::store_routes::status,
+            routes::store_routes::get_closures,
+            routes::store_routes::get_closure,
+            routes::store_routes::post_closure,
+            routes::store_routes::update_closure,
+            routes::store_routes::delete_closure,
+            routes::admin_routes::purge,
+            routes::audit_routes::get_many,
+            routes::exchange_rate_routes::get_many,
+            routes::exchange_rate_routes::get,
+            routes::exchange_rate_routes::post,
+            routes::exchange_rate_routes::update,
+            routes::exchange_rate_routes::delete,
+            routes::translation_routes::get_many,
+            routes::translation_routes::get,
+            routes::translation_routes::post,
+            routes::translation_routes::update,
+            routes::translation_routes::delete,
+            routes::suggest_routes::get,
         ),
         components(
             schemas(
                 PaginatedResult<Product>,
+                CursorPaginatedResult<Product>,
                 QResult<Product>,
+                ProductsResult,
+                ProductDetails,
+                ProductExpand,
                 ProductDto,
                 UpdateProductDto,
+                PatchProductDto,
                 Category,
+                CategoryResult,
                 Store,
+                StoreResult,
+                Worktimes,
                 CreateStoreDto,
                 UpdateStoreDto,
+                PatchStoreDto,
                 CategoryDto,
                 UpdateCategoryDto,
+                PatchCategoryDto,
                 ManyIdsDto,
-                ValidationErrorJsonPayload,
+                ErrorJsonPayload,
                 PaginationDto,
                 OrderBy,
                 StoresOrderBy,
                 SearchBy,
                 CategoryId,
                 StoreId,
+                PriceRange,
                 DateFilter,
+                OpenAt,
+                StatusAt,
+                StoreStatus,
+                StoreClosure,
+                ClosureDto,
+                PurgeBefore,
+                Purged,
+                PaginatedResult<AuditEvent>,
+                AuditEvent,
+                AuditFilter,
+                ProductPrice,
+                PriceAt,
+                InCurrency,
+                TextSearch,
+                ProductCurrencyPrice,
+                CurrencyPriceDto,
+                ExchangeRate,
+                ExchangeRateDto,
+                UpdateExchangeRateDto,
+                PaginatedResult<Translation>,
+                Translation,
+                TranslationDto,
+                UpdateTranslationDto,
+                TranslationFilter,
+                Suggestion,
+                SuggestQuery,
                 
             )
         ),
@@ -88,9 +153,7 @@ async fn main() -> std::io::Result<()> {
         App::new()
             .wrap(cors)
             .app_data(web::Data::new(app_data.clone()))
-            .service(web::scope("/category").configure(init_category_routes))
-            .service(web::scope("/product").configure(init_product_routes))
-            .service(web::scope("/store").configure(init_store_routes))
+            .configure(init_routes)
             .service(
                 SwaggerUi::new("/swagger-ui/{_:.*}").url("/api-doc/openapi.json", openapi.clone()),
             )
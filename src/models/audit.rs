@@ -0,0 +1,100 @@
+use crate::{schema::audit_events, utils::Actor};
+use chrono::{DateTime, Utc};
+use diesel::{Insertable, Queryable};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use utoipa::ToSchema;
+
+/// Entity types found in `audit_events.entity_type`
+pub const AUDITED_ENTITIES: &[&str] = &[
+    "product",
+    "store",
+    "category",
+    "store_closure",
+    "exchange_rate",
+    "translation",
+];
+
+/// A write performed through the repos
+#[derive(Queryable, Serialize, Debug, ToSchema)]
+pub struct AuditEvent {
+    pub id: i32,
+    #[schema(example = "product")]
+    pub entity_type: String,
+    pub entity_id: i32,
+    #[schema(example = "update")]
+    pub action: String,
+    #[schema(example = "anonymous")]
+    pub actor: String,
+    pub created_at: DateTime<Utc>,
+    /// Changed fields as they were, the whole entity when it was removed
+    #[schema(value_type = Option<Object>, example = json!({"price": "3.50", "version": 1}))]
+    pub before: Option<Value>,
+    /// Changed fields as they are now, the whole entity when it was created
+    #[schema(value_type = Option<Object>, example = json!({"price": "4.25", "version": 2}))]
+    pub after: Option<Value>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = audit_events)]
+pub struct NewAuditEvent {
+    pub entity_type: &'static str,
+    pub entity_id: i32,
+    pub action: &'static str,
+    pub actor: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+impl NewAuditEvent {
+    pub fn new(
+        actor: &Actor,
+        entity_type: &'static str,
+        entity_id: i32,
+        action: &'static str,
+    ) -> Self {
+        NewAuditEvent {
+            entity_type,
+            entity_id,
+            action,
+            actor: actor.0.clone(),
+            before: None,
+            after: None,
+        }
+    }
+
+    /// Snapshots of the entity around the write, only the top level fields that
+    /// differ are kept when both sides are given
+    pub fn change<B: Serialize, A: Serialize>(self, before: Option<&B>, after: Option<&A>) -> Self {
+        let before = before.and_then(|before| serde_json::to_value(before).ok());
+        let after = after.and_then(|after| serde_json::to_value(after).ok());
+        let (before, after) = match (before, after) {
+            (Some(Value::Object(before)), Some(Value::Object(after))) => {
+                let (before, after) = changed_fields(before, after);
+                (Some(Value::Object(before)), Some(Value::Object(after)))
+            }
+            sides => sides,
+        };
+        NewAuditEvent {
+            before,
+            after,
+            ..self
+        }
+    }
+}
+
+fn changed_fields(
+    mut before: Map<String, Value>,
+    mut after: Map<String, Value>,
+) -> (Map<String, Value>, Map<String, Value>) {
+    let unchanged: Vec<String> = before
+        .iter()
+        .filter(|(key, value)| after.get(key.as_str()) == Some(value))
+        .map(|(key, _)| key.clone())
+        .collect();
+    for key in unchanged {
+        before.remove(&key);
+        after.remove(&key);
+    }
+    (before, after)
+}
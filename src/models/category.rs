@@ -2,10 +2,12 @@ use diesel::{prelude::*, Queryable};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 use validator::Validate;
-use chrono::NaiveDateTime;
+use chrono::{DateTime, NaiveDateTime, Utc};
 
 use crate::schema::categories;
 
+use super::patch::present;
+
 #[derive(Queryable, Validate, Serialize, Deserialize, Debug, ToSchema)]
 #[diesel(table_name = categories)]
 pub struct Category {
@@ -13,6 +15,22 @@ pub struct Category {
     #[validate(length(min = 3, max = 50))]
     pub name: String,
     pub created_at: NaiveDateTime,
+    /// Bumped on every change, sent as the `ETag`
+    pub version: i32,
+    pub updated_at: DateTime<Utc>,
+    /// Set while the row sits in the trash, see `restore`
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+/// Category of a listing, with its similarity to a `fuzzy` search
+#[derive(Serialize, Debug, ToSchema)]
+pub struct CategoryResult {
+    #[serde(flatten)]
+    pub category: Category,
+    /// Similarity of the name to a `fuzzy` search, from 0 to 1
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 0.6666667)]
+    pub similarity: Option<f32>,
 }
 
 #[derive(Validate, Deserialize, Debug, ToSchema)]
@@ -22,6 +40,23 @@ pub struct UpdateCategoryDto {
     pub name: String,
 }
 
+/// Merge patch of a category, absent fields are left untouched
+#[derive(Validate, Deserialize, Debug, ToSchema)]
+pub struct PatchCategoryDto {
+    #[serde(default, deserialize_with = "present")]
+    #[validate(length(min = 3, max = 256))]
+    #[schema(example = "New name")]
+    pub name: Option<String>,
+}
+
+impl From<UpdateCategoryDto> for PatchCategoryDto {
+    fn from(cat: UpdateCategoryDto) -> Self {
+        PatchCategoryDto {
+            name: Some(cat.name),
+        }
+    }
+}
+
 #[derive(Deserialize, Validate, Debug, Insertable, ToSchema)]
 #[diesel(table_name = categories)]
 pub struct CategoryDto {
@@ -0,0 +1,91 @@
+use crate::schema::store_closures;
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use diesel::{AsChangeset, Associations, Identifiable, Insertable, Queryable};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
+
+use super::{
+    schedule::{hhmm, DateOverride, DayHours},
+    Store,
+};
+
+/// Exception to the weekly worktimes from `starts_on` to `ends_on` included.
+/// The store is closed on those days unless special hours are given
+#[derive(Identifiable, Associations, Queryable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = store_closures, belongs_to(Store, foreign_key = store_id))]
+pub struct StoreClosure {
+    pub id: i32,
+    pub store_id: i32,
+    #[schema(value_type = String, example = "2023-12-24")]
+    pub starts_on: NaiveDate,
+    #[schema(value_type = String, example = "2023-12-24")]
+    pub ends_on: NaiveDate,
+    #[serde(default, with = "hhmm")]
+    #[schema(value_type = Option<String>, example = "09:00")]
+    pub am_open: Option<NaiveTime>,
+    #[serde(default, with = "hhmm")]
+    #[schema(value_type = Option<String>, example = "13:00")]
+    pub am_close: Option<NaiveTime>,
+    #[serde(default, with = "hhmm")]
+    #[schema(value_type = Option<String>)]
+    pub pm_open: Option<NaiveTime>,
+    #[serde(default, with = "hhmm")]
+    #[schema(value_type = Option<String>)]
+    pub pm_close: Option<NaiveTime>,
+    #[schema(example = "Christmas eve")]
+    pub reason: Option<String>,
+    #[schema(value_type = String)]
+    pub created_at: DateTime<Utc>,
+}
+
+/// Creates or replaces a closure, omitted hours mean closed all day
+#[derive(Deserialize, Validate, Insertable, AsChangeset, ToSchema)]
+#[diesel(table_name = store_closures, treat_none_as_null = true)]
+#[validate(schema(function = "validate_closure"))]
+pub struct ClosureDto {
+    #[schema(value_type = String, example = "2023-12-24")]
+    pub starts_on: NaiveDate,
+    #[schema(value_type = String, example = "2023-12-24")]
+    pub ends_on: NaiveDate,
+    #[serde(default, with = "hhmm")]
+    #[schema(value_type = Option<String>, example = "09:00")]
+    pub am_open: Option<NaiveTime>,
+    #[serde(default, with = "hhmm")]
+    #[schema(value_type = Option<String>, example = "13:00")]
+    pub am_close: Option<NaiveTime>,
+    #[serde(default, with = "hhmm")]
+    #[schema(value_type = Option<String>)]
+    pub pm_open: Option<NaiveTime>,
+    #[serde(default, with = "hhmm")]
+    #[schema(value_type = Option<String>)]
+    pub pm_close: Option<NaiveTime>,
+    #[validate(length(max = 256))]
+    #[schema(example = "Christmas eve")]
+    pub reason: Option<String>,
+}
+
+impl DayHours for StoreClosure {
+    fn times(&self) -> [Option<NaiveTime>; 4] {
+        [self.am_open, self.am_close, self.pm_open, self.pm_close]
+    }
+}
+
+impl DateOverride for StoreClosure {
+    fn covers(&self, date: NaiveDate) -> bool {
+        self.starts_on <= date && date <= self.ends_on
+    }
+}
+
+impl DayHours for ClosureDto {
+    fn times(&self) -> [Option<NaiveTime>; 4] {
+        [self.am_open, self.am_close, self.pm_open, self.pm_close]
+    }
+}
+
+fn validate_closure(closure: &ClosureDto) -> Result<(), ValidationError> {
+    if closure.ends_on < closure.starts_on {
+        return Err(ValidationError::new("closure ends before it starts"));
+    }
+    closure.slots().map(|_| ())
+}
@@ -0,0 +1,150 @@
+use crate::schema::exchange_rates;
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+use diesel::{AsChangeset, Identifiable, Insertable, Queryable};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use validator::{Validate, ValidationError};
+
+use super::price::{self, validate_amount, Amount};
+
+/// Currency of the stores created without one, and of the products without a store
+pub const DEFAULT_CURRENCY: &str = "EUR";
+
+/// Highest exchange rate a `NUMERIC(18, 8)` column holds
+const MAX_RATE: i64 = 1_000_000_000;
+
+/// Active ISO 4217 codes, sorted
+const ISO_4217: &[&str] = &[
+    "AED", "AFN", "ALL", "AMD", "ANG", "AOA", "ARS", "AUD", "AWG", "AZN", "BAM", "BBD", "BDT",
+    "BGN", "BHD", "BIF", "BMD", "BND", "BOB", "BRL", "BSD", "BTN", "BWP", "BYN", "BZD", "CAD",
+    "CDF", "CHF", "CLP", "CNY", "COP", "CRC", "CUP", "CVE", "CZK", "DJF", "DKK", "DOP", "DZD",
+    "EGP", "ERN", "ETB", "EUR", "FJD", "FKP", "GBP", "GEL", "GHS", "GIP", "GMD", "GNF", "GTQ",
+    "GYD", "HKD", "HNL", "HTG", "HUF", "IDR", "ILS", "INR", "IQD", "IRR", "ISK", "JMD", "JOD",
+    "JPY", "KES", "KGS", "KHR", "KMF", "KPW", "KRW", "KWD", "KYD", "KZT", "LAK", "LBP", "LKR",
+    "LRD", "LSL", "LYD", "MAD", "MDL", "MGA", "MKD", "MMK", "MNT", "MOP", "MRU", "MUR", "MVR",
+    "MWK", "MXN", "MYR", "MZN", "NAD", "NGN", "NIO", "NOK", "NPR", "NZD", "OMR", "PAB", "PEN",
+    "PGK", "PHP", "PKR", "PLN", "PYG", "QAR", "RON", "RSD", "RUB", "RWF", "SAR", "SBD", "SCR",
+    "SDG", "SEK", "SGD", "SHP", "SLE", "SOS", "SRD", "SSP", "STN", "SVC", "SYP", "SZL", "THB",
+    "TJS", "TMT", "TND", "TOP", "TRY", "TTD", "TWD", "TZS", "UAH", "UGX", "USD", "UYU", "UZS",
+    "VES", "VND", "VUV", "WST", "XAF", "XCD", "XCG", "XOF", "XPF", "YER", "ZAR", "ZMW", "ZWG",
+    "ZWL",
+];
+
+/// Currencies without minor units, sorted
+const NO_DECIMALS: &[&str] = &[
+    "BIF", "CLP", "DJF", "GNF", "ISK", "JPY", "KMF", "KRW", "PYG", "RWF", "UGX", "VND", "VUV",
+    "XAF", "XOF", "XPF",
+];
+
+/// Currencies whose minor unit is a thousandth, sorted
+const THREE_DECIMALS: &[&str] = &["BHD", "IQD", "JOD", "KWD", "LYD", "OMR", "TND"];
+
+/// Decimals of the minor unit of the currency, two for most of them
+pub fn minor_unit_digits(code: &str) -> i64 {
+    if NO_DECIMALS.binary_search(&code).is_ok() {
+        return 0;
+    }
+    if THREE_DECIMALS.binary_search(&code).is_ok() {
+        return 3;
+    }
+    2
+}
+
+pub fn validate_currency(code: &str) -> Result<(), ValidationError> {
+    match ISO_4217.binary_search(&code) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(ValidationError::new("unknown ISO 4217 currency")),
+    }
+}
+
+pub fn default_currency() -> String {
+    DEFAULT_CURRENCY.to_owned()
+}
+
+/// Gives the prices in that currency: the price set for it when the product has
+/// one, else its price converted from the store currency
+#[derive(Deserialize, Validate, Debug, ToSchema, IntoParams)]
+pub struct InCurrency {
+    #[validate(custom = "validate_currency")]
+    #[schema(example = "USD")]
+    pub currency: Option<String>,
+}
+
+/// Price of a product in another currency than its store's, set by hand
+#[derive(Queryable, Serialize, Debug, Clone, ToSchema)]
+pub struct ProductCurrencyPrice {
+    pub product_id: i32,
+    #[schema(example = "USD")]
+    pub currency: String,
+    #[serde(with = "price")]
+    #[schema(value_type = String, example = "10.99")]
+    pub price: BigDecimal,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct CurrencyPriceDto {
+    /// Decimal string or integer amount of minor units of the currency
+    #[validate(custom = "validate_amount")]
+    #[schema(value_type = String, example = "10.99")]
+    pub price: Amount,
+}
+
+/// One unit of `from_currency` is worth `rate` units of `to_currency`
+#[derive(Identifiable, Queryable, Serialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = exchange_rates)]
+pub struct ExchangeRate {
+    pub id: i32,
+    #[schema(example = "EUR")]
+    pub from_currency: String,
+    #[schema(example = "USD")]
+    pub to_currency: String,
+    #[schema(value_type = String, example = "1.08750000")]
+    pub rate: BigDecimal,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, Validate, Insertable, ToSchema)]
+#[diesel(table_name = exchange_rates)]
+#[validate(schema(function = "validate_pair"))]
+pub struct ExchangeRateDto {
+    #[validate(custom = "validate_currency")]
+    #[schema(example = "EUR")]
+    pub from_currency: String,
+    #[validate(custom = "validate_currency")]
+    #[schema(example = "USD")]
+    pub to_currency: String,
+    /// Decimal string, up to eight decimals
+    #[serde(deserialize_with = "price::decimal")]
+    #[validate(custom = "validate_rate")]
+    #[schema(value_type = String, example = "1.0875")]
+    pub rate: BigDecimal,
+}
+
+#[derive(Deserialize, Validate, AsChangeset, ToSchema)]
+#[diesel(table_name = exchange_rates)]
+pub struct UpdateExchangeRateDto {
+    /// Decimal string, up to eight decimals
+    #[serde(deserialize_with = "price::decimal")]
+    #[validate(custom = "validate_rate")]
+    #[schema(value_type = String, example = "1.0912")]
+    pub rate: BigDecimal,
+}
+
+fn validate_pair(rate: &ExchangeRateDto) -> Result<(), ValidationError> {
+    if rate.from_currency == rate.to_currency {
+        return Err(ValidationError::new("a currency has no rate to itself"));
+    }
+    Ok(())
+}
+
+fn validate_rate(rate: &BigDecimal) -> Result<(), ValidationError> {
+    if rate.with_scale(8) != *rate {
+        return Err(ValidationError::new("rate has more than eight decimals"));
+    }
+    if *rate <= BigDecimal::from(0) || *rate >= BigDecimal::from(MAX_RATE) {
+        return Err(ValidationError::new("rate out of range"));
+    }
+    Ok(())
+}
@@ -1,7 +1,16 @@
+mod audit;
 mod category;
+mod closure;
+mod currency;
+pub mod patch;
+pub mod price;
+mod product_prices;
 mod products;
 mod products_categories;
 mod results;
+pub mod schedule;
 mod store;
+mod suggestion;
+mod translation;
 
-pub use self::{category::*, products::*, products_categories::*, results::*, store::*};
+pub use self::{audit::*, category::*, closure::*, currency::*, product_prices::*, products::*, products_categories::*, results::*, store::*, suggestion::*, translation::*};
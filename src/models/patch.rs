@@ -0,0 +1,24 @@
+//! Field deserializers for JSON Merge Patch (RFC 7396) bodies, used with
+//! `#[serde(default)]` so that an absent member stays `None`
+
+use serde::{de::Error, Deserialize, Deserializer};
+
+/// Nullable column: `null` is `Some(None)` and clears the column
+pub fn nullable<'de, T, D>(d: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(d).map(Some)
+}
+
+/// Column that cannot be cleared, `null` is refused
+pub fn present<'de, T, D>(d: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(d)?
+        .map(Some)
+        .ok_or_else(|| D::Error::custom("null is only allowed on nullable fields"))
+}
@@ -0,0 +1,176 @@
+//! Prices are exact decimals of at most two decimals. They are read as an
+//! `Amount`, a decimal string, `"10.10"`, or an integer amount of minor units of
+//! their currency, `1010` cents or `1010` yen, and written as a string with two
+//! decimals, three for a conversion to a currency of thousandths. Exchange
+//! rates share the plain decimal notation through `decimal`
+
+use bigdecimal::BigDecimal;
+use serde::{
+    de::{Error, Unexpected, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::{fmt, str::FromStr};
+use validator::ValidationError;
+
+use super::minor_unit_digits;
+
+/// Highest price a product can be given
+pub const MAX_PRICE: i64 = 1_000_000;
+
+/// Two decimals at least, a price converted to a currency of thousandths keeps
+/// its third one
+pub fn serialize<S: Serializer>(price: &BigDecimal, s: S) -> Result<S::Ok, S::Error> {
+    let (_, scale) = price.as_bigint_and_exponent();
+    s.serialize_str(&price.with_scale(scale.max(2)).to_string())
+}
+
+/// Reads back a price as `serialize` writes it
+pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<BigDecimal, D::Error> {
+    d.deserialize_any(DecimalVisitor)
+}
+
+/// Plain decimal string of any scale, integers are whole numbers
+pub fn decimal<'de, D: Deserializer<'de>>(d: D) -> Result<BigDecimal, D::Error> {
+    d.deserialize_any(DecimalVisitor)
+}
+
+/// Merge patch of a price, which cannot be cleared
+pub fn patch<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Amount>, D::Error> {
+    d.deserialize_option(PatchVisitor)
+}
+
+/// A price as sent. The size of a minor unit depends on the currency, so an
+/// integer amount only becomes a price once the currency is known
+#[derive(Debug, Clone)]
+pub enum Amount {
+    Decimal(BigDecimal),
+    MinorUnits(i64),
+}
+
+impl Amount {
+    /// The price in `currency`, see `validate_price`
+    pub fn in_currency(&self, currency: &str) -> Result<BigDecimal, ValidationError> {
+        let price = match self {
+            Amount::Decimal(price) => price.clone(),
+            Amount::MinorUnits(units) => {
+                BigDecimal::new((*units).into(), minor_unit_digits(currency))
+            }
+        };
+        validate_price(&price)?;
+        Ok(price)
+    }
+}
+
+impl Serialize for Amount {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Amount::Decimal(price) => serialize(price, s),
+            Amount::MinorUnits(units) => s.serialize_i64(*units),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Amount {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Amount, D::Error> {
+        d.deserialize_any(AmountVisitor)
+    }
+}
+
+/// Checks what does not depend on the currency, `Amount::in_currency` does the rest
+pub fn validate_amount(amount: &Amount) -> Result<(), ValidationError> {
+    match amount {
+        Amount::Decimal(price) => validate_price(price),
+        Amount::MinorUnits(units) if *units < 0 => Err(ValidationError::new("price out of range")),
+        Amount::MinorUnits(_) => Ok(()),
+    }
+}
+
+/// Refuses the prices a `NUMERIC(12, 2)` column would round or that are out of
+/// the accepted range
+pub fn validate_price(price: &BigDecimal) -> Result<(), ValidationError> {
+    if price.with_scale(2) != *price {
+        return Err(ValidationError::new("price has more than two decimals"));
+    }
+    if *price < BigDecimal::from(0) || *price > BigDecimal::from(MAX_PRICE) {
+        return Err(ValidationError::new("price out of range"));
+    }
+    Ok(())
+}
+
+struct AmountVisitor;
+
+impl<'de> Visitor<'de> for AmountVisitor {
+    type Value = Amount;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a decimal string or an integer amount of minor units")
+    }
+
+    fn visit_str<E: Error>(self, v: &str) -> Result<Amount, E> {
+        plain(v)
+            .map(Amount::Decimal)
+            .ok_or_else(|| E::invalid_value(Unexpected::Str(v), &self))
+    }
+
+    fn visit_i64<E: Error>(self, v: i64) -> Result<Amount, E> {
+        Ok(Amount::MinorUnits(v))
+    }
+
+    fn visit_u64<E: Error>(self, v: u64) -> Result<Amount, E> {
+        i64::try_from(v)
+            .map(Amount::MinorUnits)
+            .map_err(|_| E::invalid_value(Unexpected::Unsigned(v), &self))
+    }
+}
+
+struct DecimalVisitor;
+
+impl<'de> Visitor<'de> for DecimalVisitor {
+    type Value = BigDecimal;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a decimal string")
+    }
+
+    fn visit_str<E: Error>(self, v: &str) -> Result<BigDecimal, E> {
+        plain(v).ok_or_else(|| E::invalid_value(Unexpected::Str(v), &self))
+    }
+
+    fn visit_i64<E: Error>(self, v: i64) -> Result<BigDecimal, E> {
+        Ok(v.into())
+    }
+
+    fn visit_u64<E: Error>(self, v: u64) -> Result<BigDecimal, E> {
+        Ok(v.into())
+    }
+}
+
+/// Plain decimal notation only, exponents would let a tiny string stand for a
+/// huge number
+fn plain(v: &str) -> Option<BigDecimal> {
+    let digits = v.strip_prefix('-').unwrap_or(v);
+    let (units, decimals) = digits.split_once('.').unwrap_or((digits, "0"));
+    let is_digits = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+    if !is_digits(units) || !is_digits(decimals) {
+        return None;
+    }
+    BigDecimal::from_str(v).ok()
+}
+
+struct PatchVisitor;
+
+impl<'de> Visitor<'de> for PatchVisitor {
+    type Value = Option<Amount>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        AmountVisitor.expecting(f)
+    }
+
+    fn visit_none<E: Error>(self) -> Result<Self::Value, E> {
+        Err(E::custom("null is only allowed on nullable fields"))
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+        Amount::deserialize(d).map(Some)
+    }
+}
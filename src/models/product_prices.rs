@@ -0,0 +1,30 @@
+use crate::models::{price, products::Product};
+use crate::schema::product_prices;
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, FixedOffset, Utc};
+use diesel::{Associations, Identifiable, Queryable};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+
+/// A price of the product, in force from `effective_from` until the next one.
+/// Recorded by the database whenever the product price changes
+#[derive(Identifiable, Queryable, Associations, Serialize, Debug, Clone, ToSchema)]
+#[diesel(belongs_to(Product, foreign_key = product_id))]
+#[diesel(table_name = product_prices)]
+pub struct ProductPrice {
+    pub id: i32,
+    pub product_id: i32,
+    #[serde(with = "price")]
+    #[schema(value_type = String, example = "3.50")]
+    pub price: BigDecimal,
+    #[schema(value_type = String)]
+    pub effective_from: DateTime<Utc>,
+}
+
+/// Narrows the history down to the price in force at that instant
+#[derive(Deserialize, Validate, Debug, ToSchema, IntoParams)]
+pub struct PriceAt {
+    #[schema(example = json!(Utc::now()))]
+    pub at: Option<DateTime<FixedOffset>>,
+}
@@ -1,64 +1,137 @@
-use bigdecimal::{BigDecimal, FromPrimitive};
-use chrono::NaiveDateTime;
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, NaiveDateTime, Utc};
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 use utoipa::{ToSchema, IntoParams};
-use validator::Validate;
+use validator::{Validate, ValidationError};
 
-use crate::{models::Store, schema::products};
+use crate::{
+    models::{Store, StoreResult},
+    schema::products,
+};
 
-use super::{Category, ProductsCategories};
+use super::{
+    patch::{nullable, present},
+    price::{self, validate_amount, Amount},
+    Category, ProductsCategories,
+};
 
-#[derive(Identifiable, Queryable, Validate, Associations, Serialize, Deserialize, Debug, Clone, QueryableByName, ToSchema)]
+#[derive(Identifiable, Queryable, Selectable, Validate, Associations, Serialize, Deserialize, Debug, Clone, QueryableByName, ToSchema)]
 #[diesel(table_name = products, belongs_to(Store))]
 pub struct Product {
     pub id: i32,
     pub name: String,
-    pub i18n_name: Option<String>,
+    #[serde(with = "price")]
+    #[schema(value_type = String, example = "10.00")]
     pub price: BigDecimal,
     pub description: Option<String>,
-    pub i18n_description: Option<String>,
     pub created_at: NaiveDateTime,
     pub store_id: Option<i32>,
+    /// Bumped on every change, sent as the `ETag`
+    pub version: i32,
+    pub updated_at: DateTime<Utc>,
+    /// Set while the row sits in the trash, see `restore`
+    pub deleted_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Deserialize, Serialize, Validate, ToSchema, Clone, Debug)]
 pub struct ProductDto {
     #[validate(length(min = 3, max = 256))]
     pub name: String,
-    pub i18n_name: Option<String>,
-    #[validate(range(min = 0, max = 1000000))]
-    pub price: f64,
+    /// Decimal string or integer amount of minor units of the store currency
+    #[validate(custom = "validate_amount")]
+    #[schema(value_type = String, example = "10.00")]
+    pub price: Amount,
     #[validate(length(min = 3, max = 1000))]
     pub description: Option<String>,
-    pub i18n_description: Option<String>,
-    #[validate(range(min = 1))]
-    pub category_id: Option<u64>,
+    /// Categories the product is filed under
+    #[serde(default)]
+    #[validate(custom = "validate_category_ids")]
+    pub category_ids: Vec<i32>,
     #[validate(range(min = 1))]
     pub store_id: Option<i32>,
 }
 
+fn validate_category_ids(category_ids: &[i32]) -> Result<(), ValidationError> {
+    if category_ids.iter().all(|id| *id >= 1) {
+        return Ok(());
+    }
+    Err(ValidationError::new("invalid category id"))
+}
+
 #[derive(Deserialize, Validate, ToSchema, IntoParams)]
 pub struct CategoryId {
     #[schema(example = 2)]
     pub category_id: Option<i32>
 }
 
+/// Relations embedded in a single product, comma separated, all of them when absent
+#[derive(Deserialize, Validate, ToSchema, IntoParams)]
+pub struct ProductExpand {
+    #[validate(custom = "validate_expand")]
+    #[schema(example = "categories,store")]
+    pub expand: Option<String>,
+}
+
+fn validate_expand(expand: &str) -> Result<(), ValidationError> {
+    if expand
+        .split(',')
+        .all(|relation| matches!(relation, "" | "categories" | "store"))
+    {
+        return Ok(());
+    }
+    Err(ValidationError::new("invalid relation"))
+}
+
+impl ProductExpand {
+    fn has(&self, relation: &str) -> bool {
+        match &self.expand {
+            Some(expand) => expand.split(',').any(|r| r == relation),
+            None => true,
+        }
+    }
+
+    pub fn categories(&self) -> bool {
+        self.has("categories")
+    }
+
+    pub fn store(&self) -> bool {
+        self.has("store")
+    }
+}
+
 #[derive(Deserialize, Validate, ToSchema, IntoParams)]
 pub struct StoreId {
     #[schema(example = 2)]
     pub store_id: Option<i32>
 }
 
-impl Into<InsertableProduct> for ProductDto {
-    fn into(self) -> InsertableProduct {
+/// Bounds on the amounts of the prices, whatever the currency of their store
+#[derive(Deserialize, Validate, ToSchema, IntoParams)]
+pub struct PriceRange {
+    #[schema(value_type = Option<String>, example = "5.00")]
+    pub min_price: Option<BigDecimal>,
+    #[schema(value_type = Option<String>, example = "20.00")]
+    pub max_price: Option<BigDecimal>,
+}
+
+/// Full-text search over the names and descriptions in every locale, in web
+/// search syntax (`"exact phrase"`, `or`, `-excluded`)
+#[derive(Deserialize, Validate, ToSchema, IntoParams)]
+pub struct TextSearch {
+    #[validate(length(min = 1, max = 256))]
+    #[schema(example = "red apple")]
+    pub q: Option<String>,
+}
+
+/// The price comes read in the currency of the store
+impl From<(ProductDto, BigDecimal)> for InsertableProduct {
+    fn from((prod, price): (ProductDto, BigDecimal)) -> Self {
         InsertableProduct {
-            name: self.name,
-            i18n_name: self.i18n_name,
-            description: self.description,
-            i18n_description: self.i18n_description,
-            price: BigDecimal::from_f64(self.price).expect("Float conversion error"),
-            store_id: self.store_id,
+            name: prod.name,
+            description: prod.description,
+            price,
+            store_id: prod.store_id,
         }
     }
 }
@@ -71,58 +144,146 @@ pub struct UpdateProductDto {
     #[validate(length(min = 3, max = 256))]
     #[schema(example = "a name")]
     pub name: String,
-    #[schema(example = "alt name")]
-    #[validate(length(max = 256))]
-    pub i18n_name: Option<String>,
-    #[validate(range(min = 1, max = 1000000))]
-    #[schema(example = 10.00)]
-    pub price: f64,
+    /// Decimal string or integer amount of minor units of the store currency
+    #[validate(custom = "validate_amount")]
+    #[schema(value_type = String, example = "10.00")]
+    pub price: Amount,
     #[validate(length(max = 1000))]
     #[schema(example = "description")]
     pub description: Option<String>,
-    #[validate(length(max = 1000))]
-    #[schema(example = "alt description")]
-    pub i18n_description: Option<String>,
     #[validate(range(min = 1))]
     #[schema(example = 2)]
     pub store_id: Option<i32>,
+    /// Replaces the product categories when given, left untouched otherwise
+    #[validate(custom = "validate_category_ids")]
+    #[schema(example = json!([1, 3]))]
+    pub category_ids: Option<Vec<i32>>,
 }
 
-impl Into<InsertableProduct> for UpdateProductDto {
-    fn into(self) -> InsertableProduct {
-        InsertableProduct {
-            name: self.name,
-            i18n_name: self.i18n_name,
-            description: self.description,
-            i18n_description: self.i18n_description,
-            price: BigDecimal::from_f64(self.price).expect("Product price conversion error"),
-            store_id: self.store_id,
+/// Merge patch of a product, absent fields are left untouched and `null` clears
+/// a nullable one
+#[derive(Deserialize, Validate, Debug, ToSchema, Default)]
+pub struct PatchProductDto {
+    #[serde(default, deserialize_with = "present")]
+    #[validate(length(min = 3, max = 256))]
+    #[schema(example = "a name")]
+    pub name: Option<String>,
+    /// Decimal string or integer amount of minor units of the store currency
+    #[serde(default, deserialize_with = "price::patch")]
+    #[validate(custom = "validate_amount")]
+    #[schema(value_type = Option<String>, example = "10.00")]
+    pub price: Option<Amount>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[validate(length(max = 1000))]
+    #[schema(value_type = Option<String>, example = "description")]
+    pub description: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[validate(range(min = 1))]
+    #[schema(value_type = Option<i32>, example = 2)]
+    pub store_id: Option<Option<i32>>,
+    /// Replaces the product categories, `null` removes them all
+    #[serde(default, deserialize_with = "nullable")]
+    #[validate(custom = "validate_category_ids")]
+    #[schema(value_type = Option<Vec<i32>>, example = json!([1, 3]))]
+    pub category_ids: Option<Option<Vec<i32>>>,
+}
+
+/// A full update is a patch setting every field
+impl From<UpdateProductDto> for PatchProductDto {
+    fn from(prod: UpdateProductDto) -> Self {
+        PatchProductDto {
+            name: Some(prod.name),
+            price: Some(prod.price),
+            description: Some(prod.description),
+            store_id: Some(prod.store_id),
+            category_ids: prod.category_ids.map(Some),
+        }
+    }
+}
+
+#[derive(AsChangeset)]
+#[diesel(table_name = products)]
+pub struct ProductChangeset {
+    pub name: Option<String>,
+    pub price: Option<BigDecimal>,
+    pub description: Option<Option<String>>,
+    pub store_id: Option<Option<i32>>,
+}
+
+impl ProductChangeset {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none()
+            && self.price.is_none()
+            && self.description.is_none()
+            && self.store_id.is_none()
+    }
+}
+
+/// The price comes read in the currency of the store
+impl From<(PatchProductDto, Option<BigDecimal>)> for ProductChangeset {
+    fn from((prod, price): (PatchProductDto, Option<BigDecimal>)) -> Self {
+        ProductChangeset {
+            name: prod.name,
+            price,
+            description: prod.description,
+            store_id: prod.store_id,
         }
     }
 }
 
-#[derive(Insertable, AsChangeset)]
+#[derive(Insertable)]
 #[diesel(table_name = products)]
 pub struct InsertableProduct {
     pub name: String,
-    pub i18n_name: Option<String>,
     pub price: BigDecimal,
     pub description: Option<String>,
-    pub i18n_description: Option<String>,
     pub store_id: Option<i32>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct ProductsResult {
     pub id: i32,
     pub name: String,
-    pub i18n_name: Option<String>,
+    #[serde(with = "price")]
+    #[schema(value_type = String, example = "10.00")]
     pub price: BigDecimal,
     pub description: Option<String>,
-    pub i18n_description: Option<String>,
     pub created_at: NaiveDateTime,
     pub store_id: Option<i32>,
+    pub version: i32,
+    pub updated_at: DateTime<Utc>,
     pub categories: Vec<Category>,
+    /// Currency `price` was converted to, see `currency`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "USD")]
+    pub currency: Option<String>,
+    /// Relevance to `q`, only given with it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 0.6079271)]
+    pub rank: Option<f32>,
+    /// HTML fragments matching `q`, the text escaped and the matches wrapped in `<mark>`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "<mark>Red</mark> <mark>apple</mark> … crisp <mark>apples</mark> from the orchard")]
+    pub snippet: Option<String>,
+    /// Similarity of the name to a `fuzzy` search, from 0 to 1
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 0.6666667)]
+    pub similarity: Option<f32>,
+}
+
+/// Single product with the relations requested through `expand`
+#[derive(Serialize, ToSchema)]
+pub struct ProductDetails {
+    #[serde(flatten)]
+    pub product: Product,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub categories: Option<Vec<Category>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub store: Option<StoreResult>,
+    /// Currency `price` was converted to, see `currency`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "USD")]
+    pub currency: Option<String>,
 }
 
 // impl Into<ProductsResult> for ((Product, Vec<(ProductsCategories, Category)>), Option<Store>) {
@@ -130,10 +291,8 @@ pub struct ProductsResult {
 //         ProductsResult {
 //             id: self.0.0.id,
 //             name: self.0.0.name,
-//             i18n_name: self.0.0.i18n_name,
 //             price: self.0.0.price,
 //             description: self.0.0.description,
-//             i18n_description: self.0.0.i18n_description,
 //             created_at: self.0.0.created_at,
 //             store: self.1,
 //             categories: self.0.1.into_iter().map(|tup| tup.1).collect(),
@@ -141,18 +300,22 @@ pub struct ProductsResult {
 //     }
 // }
 
-impl Into<ProductsResult> for (Product, Vec<(ProductsCategories, Category)>) {
-    fn into(self) -> ProductsResult {
+impl From<(Product, Vec<(ProductsCategories, Category)>)> for ProductsResult {
+    fn from((product, categories): (Product, Vec<(ProductsCategories, Category)>)) -> Self {
         ProductsResult {
-            id: self.0.id,
-            name: self.0.name,
-            i18n_name: self.0.i18n_name,
-            price: self.0.price,
-            description: self.0.description,
-            i18n_description: self.0.i18n_description,
-            created_at: self.0.created_at,
-            store_id: self.0.store_id,
-            categories: self.1.into_iter().map(|tup| tup.1).collect(),
+            id: product.id,
+            name: product.name,
+            price: product.price,
+            description: product.description,
+            created_at: product.created_at,
+            store_id: product.store_id,
+            version: product.version,
+            updated_at: product.updated_at,
+            categories: categories.into_iter().map(|tup| tup.1).collect(),
+            currency: None,
+            rank: None,
+            snippet: None,
+            similarity: None,
         }
     }
 }
@@ -1,4 +1,3 @@
-use actix_web::{error::BlockingError, http::StatusCode, web, HttpResponse, HttpResponseBuilder};
 use serde::Serialize;
 use utoipa::ToSchema;
 
@@ -23,62 +22,101 @@ where
     T: Serialize,
 {
     pub result: T,
+    pub total: i64,
     pub total_pages: i64,
     pub page: i64,
     pub per_page: i64,
 }
 
 impl<T: Serialize> PaginatedResult<T> {
-    pub fn new(data: T, total_pages: i64, page: i64, per_page: i64) -> PaginatedResult<T> {
+    pub fn new(
+        data: T,
+        total: i64,
+        total_pages: i64,
+        page: i64,
+        per_page: i64,
+    ) -> PaginatedResult<T> {
         PaginatedResult {
             result: data,
+            total,
             total_pages,
             page,
             per_page,
         }
     }
-}
 
-pub enum ResultEnum<T: Serialize> {
-    Paginated(Result<Result<(T, i64, i64, i64), diesel::result::Error>, BlockingError>),
-    NotPaginated(Result<Result<T, diesel::result::Error>, BlockingError>),
+    /// Transforms the page content, keeping the pagination metadata
+    pub fn map<U: Serialize, F: FnOnce(T) -> U>(self, f: F) -> PaginatedResult<U> {
+        PaginatedResult {
+            result: f(self.result),
+            total: self.total,
+            total_pages: self.total_pages,
+            page: self.page,
+            per_page: self.per_page,
+        }
+    }
 }
 
-pub trait CanRespond<T>
+/// Keyset page, the cursors are absent at either end of the listing
+#[derive(Serialize, ToSchema)]
+pub struct CursorPaginatedResult<T>
 where
     T: Serialize,
 {
-    fn respond(&self, status: StatusCode) -> HttpResponse;
+    pub result: T,
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
+    pub per_page: i64,
+    /// Cursors to the first and last page, only sent in the `Link` header
+    #[serde(skip)]
+    pub first_cursor: String,
+    #[serde(skip)]
+    pub last_cursor: String,
+}
+
+impl<T: Serialize> CursorPaginatedResult<T> {
+    pub fn map<U: Serialize, F: FnOnce(T) -> U>(self, f: F) -> CursorPaginatedResult<U> {
+        CursorPaginatedResult {
+            result: f(self.result),
+            next_cursor: self.next_cursor,
+            prev_cursor: self.prev_cursor,
+            per_page: self.per_page,
+            first_cursor: self.first_cursor,
+            last_cursor: self.last_cursor,
+        }
+    }
 }
 
-impl<T> CanRespond<T> for ResultEnum<T>
+/// Listing page in whichever pagination mode was requested
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum Page<T>
 where
     T: Serialize,
 {
-    fn respond(&self, status: StatusCode) -> HttpResponse {
+    Offset(PaginatedResult<T>),
+    Cursor(CursorPaginatedResult<T>),
+}
+
+impl<T: Serialize> Page<T> {
+    pub fn result(&self) -> &T {
+        match self {
+            Page::Offset(page) => &page.result,
+            Page::Cursor(page) => &page.result,
+        }
+    }
+
+    pub fn result_mut(&mut self) -> &mut T {
+        match self {
+            Page::Offset(page) => &mut page.result,
+            Page::Cursor(page) => &mut page.result,
+        }
+    }
+
+    pub fn map<U: Serialize, F: FnOnce(T) -> U>(self, f: F) -> Page<U> {
         match self {
-            ResultEnum::Paginated(val) => match val {
-                Ok(Ok((data, total_pages, page, per_page))) => HttpResponseBuilder::new(status)
-                    .json(web::Json(PaginatedResult::new(
-                        data,
-                        *total_pages,
-                        *page,
-                        *per_page,
-                    ))),
-                Ok(Err(err)) => HttpResponseBuilder::new(StatusCode::BAD_REQUEST)
-                    .json(web::Json(QResult::new(0, Some(err.to_string())))),
-                Err(err) => HttpResponse::InternalServerError()
-                    .json(web::Json(QResult::new(0, Some(err.to_string())))),
-            },
-            ResultEnum::NotPaginated(val) => match val {
-                Ok(Ok(val)) => {
-                    HttpResponseBuilder::new(status).json(web::Json(QResult::new(val, None)))
-                }
-                Ok(Err(err)) => HttpResponseBuilder::new(StatusCode::BAD_REQUEST)
-                    .json(web::Json(QResult::new(0, Some(err.to_string())))),
-                Err(err) => HttpResponse::InternalServerError()
-                    .json(web::Json(QResult::new(0, Some(err.to_string())))),
-            },
+            Page::Offset(page) => Page::Offset(page.map(f)),
+            Page::Cursor(page) => Page::Cursor(page.map(f)),
         }
     }
 }
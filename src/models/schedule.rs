@@ -0,0 +1,384 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use validator::ValidationError;
+
+/// Opening hours as `HH:MM` in 24-hour time, seconds are accepted on input
+pub mod hhmm {
+    use chrono::NaiveTime;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(time: &Option<NaiveTime>, s: S) -> Result<S::Ok, S::Error> {
+        match time {
+            Some(time) => s.serialize_str(&time.format("%H:%M").to_string()),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<NaiveTime>, D::Error> {
+        match Option::<String>::deserialize(d)? {
+            Some(time) => NaiveTime::parse_from_str(&time, "%H:%M")
+                .or_else(|_| NaiveTime::parse_from_str(&time, "%H:%M:%S"))
+                .map(Some)
+                .map_err(|_| {
+                    D::Error::custom(format!("Wrong time format, expected HH:MM: {}", time))
+                }),
+            None => Ok(None),
+        }
+    }
+
+    /// Merge patch of a nullable time, `null` clears it
+    pub fn patch<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Option<NaiveTime>>, D::Error> {
+        deserialize(d).map(Some)
+    }
+}
+
+/// Opening slot, closing at or before its opening means it ends the next day
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Slot {
+    pub open: NaiveTime,
+    pub close: NaiveTime,
+}
+
+impl Slot {
+    pub fn overnight(&self) -> bool {
+        self.close < self.open
+    }
+}
+
+/// Opening hours of a day, in the shape of a `worktimes` row
+pub trait DayHours {
+    fn times(&self) -> [Option<NaiveTime>; 4];
+
+    /// Ordered slots of the day. Slots must be complete, non empty and must not
+    /// overlap, only the last one may cross midnight
+    fn slots(&self) -> Result<Vec<Slot>, ValidationError> {
+        let [am_open, am_close, pm_open, pm_close] = self.times();
+        let mut slots = vec![];
+        for (open, close) in [(am_open, am_close), (pm_open, pm_close)] {
+            match (open, close) {
+                (Some(open), Some(close)) if open == close => {
+                    return Err(ValidationError::new("empty opening slot"))
+                }
+                (Some(open), Some(close)) => slots.push(Slot { open, close }),
+                (None, None) => (),
+                _ => {
+                    return Err(ValidationError::new(
+                        "opening slot needs both open and close",
+                    ))
+                }
+            }
+        }
+        if let [am, pm] = slots[..] {
+            if am.overnight() {
+                return Err(ValidationError::new(
+                    "only the last slot can cross midnight",
+                ));
+            }
+            if pm.open < am.close {
+                return Err(ValidationError::new("overlapping opening slots"));
+            }
+        }
+        Ok(slots)
+    }
+}
+
+/// Opening hours of a week day
+pub trait DaySchedule: DayHours {
+    fn day_id(&self) -> i32;
+}
+
+/// Hours replacing the week day ones on a range of dates
+pub trait DateOverride: DayHours {
+    fn covers(&self, date: NaiveDate) -> bool;
+}
+
+pub fn validate_day<D: DayHours>(day: &D) -> Result<(), ValidationError> {
+    day.slots().map(|_| ())
+}
+
+/// Checks every day and that a slot crossing midnight closes before the next day opens
+pub fn validate_week<D: DaySchedule>(days: &[D]) -> Result<(), ValidationError> {
+    for day in days {
+        let last = match day.slots()?.last() {
+            Some(slot) if slot.overnight() => *slot,
+            _ => continue,
+        };
+        let next_day = day.day_id() % 7 + 1;
+        for next in days.iter().filter(|next| next.day_id() == next_day) {
+            if let Some(first) = next.slots()?.first() {
+                if first.open < last.close {
+                    return Err(ValidationError::new("overnight slot overlaps the next day"));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether a store is open at an instant and when that changes next
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpenStatus {
+    pub open: bool,
+    pub next_change_at: Option<NaiveDateTime>,
+}
+
+/// Instant of a wall clock time in `tz`. A time falling in a DST gap happens at
+/// the first instant after it, an ambiguous one at its first occurrence
+pub fn local_instant<T: TimeZone>(tz: &T, local: NaiveDateTime) -> Option<DateTime<T>> {
+    tz.from_local_datetime(&local).earliest().or_else(|| {
+        tz.from_local_datetime(&(local + Duration::hours(1)))
+            .earliest()
+    })
+}
+
+/// Slots of a date, from the override covering it if any. `day_id` 1 is Monday
+fn slots_on<D: DaySchedule, O: DateOverride>(
+    days: &[D],
+    overrides: &[O],
+    date: NaiveDate,
+) -> Vec<Slot> {
+    if let Some(special) = overrides.iter().find(|special| special.covers(date)) {
+        return special.slots().unwrap_or_default();
+    }
+    let day_id = date.weekday().number_from_monday() as i32;
+    days.iter()
+        .filter(|day| day.day_id() == day_id)
+        .flat_map(|day| day.slots().unwrap_or_default())
+        .collect()
+}
+
+/// Opening intervals of the week around `at`, consecutive ones merged
+fn intervals<D: DaySchedule, O: DateOverride>(
+    days: &[D],
+    overrides: &[O],
+    at: NaiveDateTime,
+) -> Vec<(NaiveDateTime, NaiveDateTime)> {
+    let mut intervals: Vec<(NaiveDateTime, NaiveDateTime)> = vec![];
+    for offset in -1..=7 {
+        let date = at.date() + Duration::days(offset);
+        for slot in slots_on(days, overrides, date) {
+            let close_date = match slot.overnight() {
+                true => date + Duration::days(1),
+                false => date,
+            };
+            intervals.push((date.and_time(slot.open), close_date.and_time(slot.close)));
+        }
+    }
+    intervals.sort();
+    intervals
+        .into_iter()
+        .fold(vec![], |mut merged, (open, close)| {
+            match merged.last_mut() {
+                Some(last) if open <= last.1 => last.1 = last.1.max(close),
+                _ => merged.push((open, close)),
+            }
+            merged
+        })
+}
+
+/// Opening status at `at`, a store on holiday stays closed until the flag is lifted.
+/// Only the week after `at` is looked at for the next change
+pub fn status<D: DaySchedule, O: DateOverride>(
+    days: &[D],
+    overrides: &[O],
+    is_holiday: bool,
+    at: NaiveDateTime,
+) -> OpenStatus {
+    if is_holiday {
+        return OpenStatus {
+            open: false,
+            next_change_at: None,
+        };
+    }
+    let intervals = intervals(days, overrides, at);
+    match intervals
+        .iter()
+        .find(|(open, close)| *open <= at && at < *close)
+    {
+        Some((_, close)) => OpenStatus {
+            open: true,
+            next_change_at: Some(*close),
+        },
+        None => OpenStatus {
+            open: false,
+            next_change_at: intervals
+                .iter()
+                .map(|(open, _)| *open)
+                .find(|open| *open > at),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::Europe::Paris;
+
+    struct Day {
+        id: i32,
+        hours: [Option<NaiveTime>; 4],
+    }
+
+    impl DayHours for Day {
+        fn times(&self) -> [Option<NaiveTime>; 4] {
+            self.hours
+        }
+    }
+
+    impl DaySchedule for Day {
+        fn day_id(&self) -> i32 {
+            self.id
+        }
+    }
+
+    struct Closure {
+        starts_on: NaiveDate,
+        ends_on: NaiveDate,
+        hours: [Option<NaiveTime>; 4],
+    }
+
+    impl DayHours for Closure {
+        fn times(&self) -> [Option<NaiveTime>; 4] {
+            self.hours
+        }
+    }
+
+    impl DateOverride for Closure {
+        fn covers(&self, date: NaiveDate) -> bool {
+            self.starts_on <= date && date <= self.ends_on
+        }
+    }
+
+    fn time(hhmm: &str) -> Option<NaiveTime> {
+        Some(NaiveTime::parse_from_str(hhmm, "%H:%M").unwrap())
+    }
+
+    fn hours(times: [&str; 4]) -> [Option<NaiveTime>; 4] {
+        times.map(|hhmm| match hhmm {
+            "" => None,
+            hhmm => time(hhmm),
+        })
+    }
+
+    fn date(ymd: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(ymd, "%Y-%m-%d").unwrap()
+    }
+
+    fn at(ymd: &str, hhmm: &str) -> NaiveDateTime {
+        date(ymd).and_time(time(hhmm).unwrap())
+    }
+
+    /// Every day of the week with the same hours
+    fn week(times: [&str; 4]) -> Vec<Day> {
+        (1..=7)
+            .map(|id| Day {
+                id,
+                hours: hours(times),
+            })
+            .collect()
+    }
+
+    const NO_CLOSURES: &[Closure] = &[];
+
+    #[test]
+    fn slots_reject_incomplete_empty_and_overlapping_hours() {
+        for times in [
+            ["09:00", "", "", ""],
+            ["09:00", "09:00", "", ""],
+            ["09:00", "13:00", "12:00", "18:00"],
+            ["22:00", "02:00", "14:00", "18:00"],
+        ] {
+            let day = Day {
+                id: 1,
+                hours: hours(times),
+            };
+            assert!(validate_day(&day).is_err(), "{:?}", times);
+        }
+        let day = Day {
+            id: 1,
+            hours: hours(["09:00", "13:00", "14:00", "02:00"]),
+        };
+        assert_eq!(day.slots().unwrap().len(), 2);
+        assert!(day.slots().unwrap()[1].overnight());
+    }
+
+    #[test]
+    fn overnight_slot_must_close_before_the_next_day_opens() {
+        let mut days = week(["", "", "19:00", "02:00"]);
+        assert!(validate_week(&days).is_ok());
+        // Sunday night runs into Monday morning
+        days[0].hours = hours(["01:00", "05:00", "", ""]);
+        assert!(validate_week(&days).is_err());
+    }
+
+    #[test]
+    fn overnight_slot_is_open_past_midnight() {
+        let days = week(["", "", "19:00", "02:00"]);
+        // 2023-02-13 is a Monday
+        let late = status(&days, NO_CLOSURES, false, at("2023-02-14", "01:30"));
+        assert!(late.open);
+        assert_eq!(late.next_change_at, Some(at("2023-02-14", "02:00")));
+        let early = status(&days, NO_CLOSURES, false, at("2023-02-14", "02:00"));
+        assert!(!early.open);
+        assert_eq!(early.next_change_at, Some(at("2023-02-14", "19:00")));
+    }
+
+    #[test]
+    fn slots_running_into_each_other_are_one_interval() {
+        let days = week(["00:00", "12:00", "12:00", "00:00"]);
+        let open = status(&days, NO_CLOSURES, false, at("2023-02-13", "11:00"));
+        assert!(open.open);
+        // Open around the clock, closing is only seen at the end of the week looked at
+        assert_eq!(open.next_change_at, Some(at("2023-02-21", "00:00")));
+    }
+
+    #[test]
+    fn holiday_stays_closed() {
+        let days = week(["09:00", "18:00", "", ""]);
+        let closed = status(&days, NO_CLOSURES, true, at("2023-02-13", "10:00"));
+        assert_eq!(
+            closed,
+            OpenStatus {
+                open: false,
+                next_change_at: None
+            }
+        );
+    }
+
+    #[test]
+    fn closures_override_the_week_day() {
+        let days = week(["09:00", "18:00", "", ""]);
+        let closures = [
+            Closure {
+                starts_on: date("2023-02-13"),
+                ends_on: date("2023-02-14"),
+                hours: hours(["", "", "", ""]),
+            },
+            Closure {
+                starts_on: date("2023-02-15"),
+                ends_on: date("2023-02-15"),
+                hours: hours(["10:00", "13:00", "", ""]),
+            },
+        ];
+        let closed = status(&days, &closures, false, at("2023-02-13", "10:00"));
+        assert!(!closed.open);
+        assert_eq!(closed.next_change_at, Some(at("2023-02-15", "10:00")));
+        let special = status(&days, &closures, false, at("2023-02-15", "12:00"));
+        assert!(special.open);
+        assert_eq!(special.next_change_at, Some(at("2023-02-15", "13:00")));
+        let back = status(&days, &closures, false, at("2023-02-15", "14:00"));
+        assert!(!back.open);
+        assert_eq!(back.next_change_at, Some(at("2023-02-16", "09:00")));
+    }
+
+    #[test]
+    fn local_instant_skips_the_dst_gap() {
+        // Paris clocks went from 02:00 to 03:00 on 2023-03-26
+        let skipped = local_instant(&Paris, at("2023-03-26", "02:30")).unwrap();
+        assert_eq!(skipped.to_rfc3339(), "2023-03-26T03:30:00+02:00");
+        let regular = local_instant(&Paris, at("2023-03-26", "09:00")).unwrap();
+        assert_eq!(regular.to_rfc3339(), "2023-03-26T09:00:00+02:00");
+        // And back from 03:00 to 02:00 on 2023-10-29, the first 02:30 is taken
+        let repeated = local_instant(&Paris, at("2023-10-29", "02:30")).unwrap();
+        assert_eq!(repeated.to_rfc3339(), "2023-10-29T02:30:00+02:00");
+    }
+}
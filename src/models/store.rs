@@ -1,14 +1,21 @@
 use crate::schema::stores;
 use crate::schema::worktimes;
-use chrono::NaiveDateTime;
+use chrono::{DateTime, FixedOffset, NaiveTime, Offset, Utc};
+use chrono_tz::Tz;
 use diesel::QueryableByName;
 use diesel::{AsChangeset, Associations, Identifiable, Insertable, Queryable};
-use regex::Regex;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 use validator::{Validate, ValidationError};
 
-use super::Product;
+use super::{
+    currency::{default_currency, validate_currency},
+    patch::present,
+    schedule::{
+        hhmm, local_instant, status, validate_day, validate_week, DayHours, DaySchedule,
+    },
+    Product, StoreClosure,
+};
 
 #[derive(Identifiable, Queryable, Serialize, Deserialize, Debug, Clone, QueryableByName, ToSchema)]
 #[diesel(table_name = stores)]
@@ -16,18 +23,50 @@ pub struct Store {
     pub id: i32,
     pub name: String,
     pub is_holiday: bool,
-    pub created_at: NaiveDateTime,
+    pub created_at: DateTime<Utc>,
     pub prod_count: i32,
+    #[schema(example = "Europe/Paris")]
+    pub timezone: String,
+    /// Bumped on every change of the store or its worktimes, sent as the `ETag`
+    pub version: i32,
+    pub updated_at: DateTime<Utc>,
+    /// Set while the row sits in the trash, see `restore`
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// ISO 4217 code the prices of its products are in
+    #[schema(example = "EUR")]
+    pub currency: String,
 }
 
-#[derive(Serialize)]
+impl Store {
+    /// Zone the worktimes are written in, UTC if the stored name is unknown
+    pub fn tz(&self) -> Tz {
+        self.timezone.parse().unwrap_or(Tz::UTC)
+    }
+}
+
+#[derive(Serialize, ToSchema)]
 pub struct StoreResult {
     pub id: i32,
     pub is_holiday: bool,
-    pub created_at: NaiveDateTime,
+    pub created_at: DateTime<Utc>,
     pub name: String,
     pub prod_count: i32,
+    #[schema(example = "Europe/Paris")]
+    pub timezone: String,
+    #[schema(example = "EUR")]
+    pub currency: String,
+    pub version: i32,
+    pub updated_at: DateTime<Utc>,
     pub worktimes: Vec<Worktimes>,
+    /// Closures and special hours that are not over yet
+    pub closures: Vec<StoreClosure>,
+    pub open_now: bool,
+    #[schema(value_type = Option<String>, example = "2023-02-14T18:30:00+01:00")]
+    pub next_change_at: Option<DateTime<FixedOffset>>,
+    /// Similarity of the name to a `fuzzy` search, from 0 to 1
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 0.5714286)]
+    pub similarity: Option<f32>,
 }
 
 #[derive(Serialize)]
@@ -35,19 +74,86 @@ pub struct StoreResultWithProducts {
     pub id: i32,
     pub name: String,
     pub is_holiday: bool,
-    pub created_at: NaiveDateTime,
+    pub created_at: DateTime<Utc>,
     pub prod_count: i32,
+    pub timezone: String,
+    pub currency: String,
+    pub version: i32,
+    pub updated_at: DateTime<Utc>,
     pub worktimes: Vec<Worktimes>,
+    pub closures: Vec<StoreClosure>,
+    pub open_now: bool,
+    pub next_change_at: Option<DateTime<FixedOffset>>,
     pub products: Vec<Product>,
 }
 
+/// Opening status of a store at the requested instant, dates are given in the
+/// store time zone
+#[derive(Serialize, ToSchema)]
+pub struct StoreStatus {
+    pub store_id: i32,
+    #[schema(value_type = String, example = "2023-02-14T12:00:00+01:00")]
+    pub at: DateTime<FixedOffset>,
+    pub open: bool,
+    #[schema(value_type = Option<String>, example = "2023-02-14T14:00:00+01:00")]
+    pub next_change_at: Option<DateTime<FixedOffset>>,
+}
+
+impl StoreStatus {
+    /// Worktimes are wall clock times of the store zone
+    pub fn new(
+        store: &Store,
+        worktimes: &[Worktimes],
+        closures: &[StoreClosure],
+        at: DateTime<Utc>,
+    ) -> Self {
+        let tz = store.tz();
+        let at = at.with_timezone(&tz);
+        let status = status(worktimes, closures, store.is_holiday, at.naive_local());
+        let next_change_at = status.next_change_at.and_then(|next| local_instant(&tz, next));
+        StoreStatus {
+            store_id: store.id,
+            at: fixed(at),
+            open: status.open,
+            next_change_at: next_change_at.map(fixed),
+        }
+    }
+}
+
+fn fixed(at: DateTime<Tz>) -> DateTime<FixedOffset> {
+    at.with_timezone(&at.offset().fix())
+}
+
+pub fn validate_timezone(timezone: &str) -> Result<(), ValidationError> {
+    match timezone.parse::<Tz>() {
+        Ok(_) => Ok(()),
+        Err(_) => Err(ValidationError::new("unknown IANA time zone")),
+    }
+}
+
+fn default_timezone() -> String {
+    "UTC".to_owned()
+}
+
 #[derive(Deserialize, Validate, ToSchema)]
+#[validate(schema(function = "validate_create_week"))]
 pub struct CreateStoreDto {
     #[validate(length(min = 2, max = 256))]
     #[schema(example = "tarek's store")]
     pub name: String,
     pub is_holiday: bool,
-    #[schema(example = json!(vec![CreateWorktimeDto { day_id:1, am_open: Some("10:00".to_owned()), am_close: Some("12:00".to_owned()), pm_open: Some("02:00".to_owned()), pm_close: Some("07:00".to_owned())}]))]
+    /// IANA zone the worktimes are written in, UTC by default
+    #[serde(default = "default_timezone")]
+    #[validate(custom = "validate_timezone")]
+    #[schema(example = "Europe/Paris")]
+    pub timezone: String,
+    /// ISO 4217 code the prices of its products are in, EUR by default
+    #[serde(default = "default_currency")]
+    #[validate(custom = "validate_currency")]
+    #[schema(example = "EUR")]
+    pub currency: String,
+    #[schema(example = json!(vec![CreateWorktimeDto { day_id:1, am_open: NaiveTime::from_hms_opt(10, 0, 0), am_close: NaiveTime::from_hms_opt(12, 0, 0), pm_open: NaiveTime::from_hms_opt(14, 0, 0), pm_close: NaiveTime::from_hms_opt(1, 0, 0)}]))]
+    #[validate]
     pub worktimes: [CreateWorktimeDto; 7],
 }
 
@@ -57,37 +163,92 @@ pub struct UpdateStoreDto {
     #[schema(example = "A new name")]
     pub name: String,
     pub is_holiday: bool,
+    /// Kept when absent
+    #[validate(custom = "validate_timezone")]
+    #[schema(example = "Europe/Paris")]
+    pub timezone: Option<String>,
+    /// Kept when absent, only changes while the store has no products
+    #[validate(custom = "validate_currency")]
+    #[schema(example = "EUR")]
+    pub currency: Option<String>,
     #[validate]
     pub worktimes: Vec<UpdateWorktimeDto>,
 }
 
-#[derive(Identifiable, Associations, Deserialize, Queryable, Debug, Serialize)]
+/// Merge patch of a store, absent fields are left untouched
+#[derive(Deserialize, Validate, ToSchema, Default)]
+pub struct PatchStoreDto {
+    #[serde(default, deserialize_with = "present")]
+    #[validate(length(min = 2, max = 256))]
+    #[schema(example = "A new name")]
+    pub name: Option<String>,
+    #[serde(default, deserialize_with = "present")]
+    pub is_holiday: Option<bool>,
+    #[serde(default, deserialize_with = "present")]
+    #[validate(custom = "validate_timezone")]
+    #[schema(example = "Europe/Paris")]
+    pub timezone: Option<String>,
+    /// Only changes while the store has no products
+    #[serde(default, deserialize_with = "present")]
+    #[validate(custom = "validate_currency")]
+    #[schema(example = "EUR")]
+    pub currency: Option<String>,
+    /// Worktimes to change, matched on their id
+    #[serde(default, deserialize_with = "present")]
+    #[validate]
+    pub worktimes: Option<Vec<UpdateWorktimeDto>>,
+}
+
+impl From<UpdateStoreDto> for PatchStoreDto {
+    fn from(store: UpdateStoreDto) -> Self {
+        PatchStoreDto {
+            name: Some(store.name),
+            is_holiday: Some(store.is_holiday),
+            timezone: store.timezone,
+            currency: store.currency,
+            worktimes: Some(store.worktimes),
+        }
+    }
+}
+
+#[derive(Identifiable, Associations, Deserialize, Queryable, Debug, Serialize, ToSchema)]
 #[diesel(table_name = worktimes, belongs_to(Store, foreign_key = store_id))]
 pub struct Worktimes {
     pub id: i32,
     pub day_id: i32,
     pub store_id: i32,
-    pub am_open: Option<String>,
-    pub am_close: Option<String>,
-    pub pm_open: Option<String>,
-    pub pm_close: Option<String>,
+    #[serde(default, with = "hhmm")]
+    #[schema(value_type = Option<String>, example = "08:00")]
+    pub am_open: Option<NaiveTime>,
+    #[serde(default, with = "hhmm")]
+    #[schema(value_type = Option<String>, example = "12:00")]
+    pub am_close: Option<NaiveTime>,
+    #[serde(default, with = "hhmm")]
+    #[schema(value_type = Option<String>, example = "14:00")]
+    pub pm_open: Option<NaiveTime>,
+    #[serde(default, with = "hhmm")]
+    #[schema(value_type = Option<String>, example = "18:30")]
+    pub pm_close: Option<NaiveTime>,
 }
 
 #[derive(Serialize, Deserialize, Validate)]
+#[validate(schema(function = "validate_day"))]
 pub struct CreateWorktimeDto {
     // store_id: i32,
     #[validate(range(min = 1, max = 7))]
     pub day_id: i32,
-    #[validate(custom = "validate_worktimes")]
-    pub am_open: Option<String>,
-    #[validate(custom = "validate_worktimes")]
-    pub am_close: Option<String>,
-    #[validate(custom = "validate_worktimes")]
-    pub pm_open: Option<String>,
-    #[validate(custom = "validate_worktimes")]
-    pub pm_close: Option<String>,
+    #[serde(default, with = "hhmm")]
+    pub am_open: Option<NaiveTime>,
+    #[serde(default, with = "hhmm")]
+    pub am_close: Option<NaiveTime>,
+    #[serde(default, with = "hhmm")]
+    pub pm_open: Option<NaiveTime>,
+    #[serde(default, with = "hhmm")]
+    pub pm_close: Option<NaiveTime>,
 }
 
+/// Only the given times change and `null` clears one, the whole week is checked
+/// once they are applied
 #[derive(Deserialize, Validate, AsChangeset, Debug)]
 #[diesel(table_name = worktimes)]
 pub struct UpdateWorktimeDto {
@@ -97,14 +258,14 @@ pub struct UpdateWorktimeDto {
     pub store_id: Option<i32>,
     #[validate(range(min = 1, max = 7))]
     pub day_id: Option<i32>,
-    #[validate(custom = "validate_worktimes")]
-    pub am_open: Option<String>,
-    #[validate(custom = "validate_worktimes")]
-    pub am_close: Option<String>,
-    #[validate(custom = "validate_worktimes")]
-    pub pm_open: Option<String>,
-    #[validate(custom = "validate_worktimes")]
-    pub pm_close: Option<String>,
+    #[serde(default, deserialize_with = "hhmm::patch")]
+    pub am_open: Option<Option<NaiveTime>>,
+    #[serde(default, deserialize_with = "hhmm::patch")]
+    pub am_close: Option<Option<NaiveTime>>,
+    #[serde(default, deserialize_with = "hhmm::patch")]
+    pub pm_open: Option<Option<NaiveTime>>,
+    #[serde(default, deserialize_with = "hhmm::patch")]
+    pub pm_close: Option<Option<NaiveTime>>,
 }
 
 #[derive(Serialize, Deserialize, AsChangeset, Insertable, Validate)]
@@ -113,14 +274,38 @@ pub struct InsertableWorktime {
     pub store_id: i32,
     #[validate(range(min = 1, max = 7))]
     pub day_id: i32,
-    #[validate(custom = "validate_worktimes")]
-    pub am_open: Option<String>,
-    #[validate(custom = "validate_worktimes")]
-    pub am_close: Option<String>,
-    #[validate(custom = "validate_worktimes")]
-    pub pm_open: Option<String>,
-    #[validate(custom = "validate_worktimes")]
-    pub pm_close: Option<String>,
+    pub am_open: Option<NaiveTime>,
+    pub am_close: Option<NaiveTime>,
+    pub pm_open: Option<NaiveTime>,
+    pub pm_close: Option<NaiveTime>,
+}
+
+impl DayHours for Worktimes {
+    fn times(&self) -> [Option<NaiveTime>; 4] {
+        [self.am_open, self.am_close, self.pm_open, self.pm_close]
+    }
+}
+
+impl DaySchedule for Worktimes {
+    fn day_id(&self) -> i32 {
+        self.day_id
+    }
+}
+
+impl DayHours for CreateWorktimeDto {
+    fn times(&self) -> [Option<NaiveTime>; 4] {
+        [self.am_open, self.am_close, self.pm_open, self.pm_close]
+    }
+}
+
+impl DaySchedule for CreateWorktimeDto {
+    fn day_id(&self) -> i32 {
+        self.day_id
+    }
+}
+
+fn validate_create_week(store: &CreateStoreDto) -> Result<(), validator::ValidationError> {
+    validate_week(&store.worktimes)
 }
 
 pub trait MyInto<T> {
@@ -156,23 +341,24 @@ impl TransformTo<Vec<InsertableWorktime>> for CreateStoreDto {
     }
 }
 
-pub fn validate_worktimes(worktime: &str) -> Result<(), ValidationError> {
-    let time_regex = Regex::new(r#"^(0[0-9]|1[0-2]):[0-5][0-9]$"#).unwrap();
-    match time_regex.is_match(worktime) {
-        true => Ok(()),
-        false => Err(ValidationError::new("Wrong time format : HH:MM")),
-    }
-}
-
-impl Into<StoreResult> for (Store, Vec<Worktimes>) {
-    fn into(self) -> StoreResult {
+impl From<(Store, Vec<Worktimes>, Vec<StoreClosure>)> for StoreResult {
+    fn from((store, worktimes, closures): (Store, Vec<Worktimes>, Vec<StoreClosure>)) -> Self {
+        let status = StoreStatus::new(&store, &worktimes, &closures, Utc::now());
         StoreResult {
-            id: self.0.id,
-            name: self.0.name,
-            created_at: self.0.created_at,
-            is_holiday: self.0.is_holiday,
-            prod_count: self.0.prod_count,
-            worktimes: self.1,
+            id: store.id,
+            name: store.name,
+            created_at: store.created_at,
+            is_holiday: store.is_holiday,
+            prod_count: store.prod_count,
+            timezone: store.timezone,
+            currency: store.currency,
+            version: store.version,
+            updated_at: store.updated_at,
+            worktimes,
+            closures,
+            open_now: status.open,
+            next_change_at: status.next_change_at,
+            similarity: None,
         }
     }
 }
@@ -0,0 +1,21 @@
+use diesel::{
+    sql_types::{Integer, Text},
+    QueryableByName,
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// Product, category or store whose name starts with the text typed so far
+#[derive(QueryableByName, Serialize, Debug, ToSchema)]
+pub struct Suggestion {
+    /// `product`, `category` or `store`
+    #[serde(rename = "type")]
+    #[diesel(sql_type = Text)]
+    #[schema(example = "product")]
+    pub entity_type: String,
+    #[diesel(sql_type = Integer)]
+    pub id: i32,
+    #[diesel(sql_type = Text)]
+    #[schema(example = "Écoméditique bio")]
+    pub name: String,
+}
@@ -0,0 +1,290 @@
+use crate::{schema::translations, utils::DEFAULT_LOCALE};
+use chrono::{DateTime, Utc};
+use diesel::{AsChangeset, Insertable, Queryable};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
+
+use super::{
+    Category, CategoryResult, Page, Product, ProductDetails, ProductsResult, Store, StoreResult,
+    StoreResultWithProducts,
+};
+
+/// Fields that can be translated, with the longest text each one takes
+const TRANSLATABLE: &[(&str, &str, usize)] = &[
+    ("product", "name", 256),
+    ("product", "description", 1000),
+    ("category", "name", 256),
+    ("store", "name", 256),
+];
+
+/// Entity types found in `translations.entity_type`
+pub const TRANSLATED_ENTITIES: &[&str] = &["product", "category", "store"];
+
+/// Longest text `field` of `entity` takes, `None` when it cannot be translated
+pub fn max_length(entity: &str, field: &str) -> Option<usize> {
+    TRANSLATABLE
+        .iter()
+        .find(|(e, f, _)| *e == entity && *f == field)
+        .map(|(_, _, max)| *max)
+}
+
+/// Text of a field in another locale than the default one
+#[derive(Queryable, Serialize, Debug, Clone, ToSchema)]
+pub struct Translation {
+    pub id: i32,
+    #[schema(example = "product")]
+    pub entity_type: String,
+    pub entity_id: i32,
+    #[schema(example = "name")]
+    pub field: String,
+    /// BCP 47 language tag
+    #[schema(example = "fr-CA")]
+    pub locale: String,
+    #[schema(example = "Pomme")]
+    pub value: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, Validate, Insertable, ToSchema)]
+#[diesel(table_name = translations)]
+#[validate(schema(function = "validate_translation"))]
+pub struct TranslationDto {
+    #[schema(example = "product")]
+    pub entity_type: String,
+    #[validate(range(min = 1))]
+    #[schema(example = 1)]
+    pub entity_id: i32,
+    #[schema(example = "name")]
+    pub field: String,
+    /// BCP 47 language tag, stored in its canonical case
+    #[validate(custom = "validate_locale")]
+    #[schema(example = "fr-CA")]
+    pub locale: String,
+    #[validate(length(min = 1))]
+    #[schema(example = "Pomme")]
+    pub value: String,
+}
+
+#[derive(Deserialize, Validate, AsChangeset, ToSchema)]
+#[diesel(table_name = translations)]
+pub struct UpdateTranslationDto {
+    /// Checked against the length the field takes
+    #[validate(length(min = 1))]
+    #[schema(example = "Pomme")]
+    pub value: String,
+}
+
+fn validate_translation(translation: &TranslationDto) -> Result<(), ValidationError> {
+    match max_length(&translation.entity_type, &translation.field) {
+        None => Err(ValidationError::new("field cannot be translated")),
+        Some(max) if translation.value.chars().count() > max => {
+            Err(ValidationError::new("value too long for the field"))
+        }
+        Some(_) => Ok(()),
+    }
+}
+
+/// Well formed BCP 47 tag: a 2-3 letter language then 1-8 character subtags.
+/// The default locale is the text of the entity itself and takes no translation
+pub fn validate_locale(locale: &str) -> Result<(), ValidationError> {
+    let mut subtags = locale.split('-');
+    let language = subtags.next().unwrap_or_default();
+    let well_formed = locale.len() <= 35
+        && (2..=3).contains(&language.len())
+        && language.chars().all(|c| c.is_ascii_alphabetic())
+        && subtags.all(|subtag| {
+            (1..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+        });
+    if !well_formed {
+        return Err(ValidationError::new("invalid BCP 47 language tag"));
+    }
+    if locale.eq_ignore_ascii_case(DEFAULT_LOCALE) {
+        return Err(ValidationError::new("default locale is not translated"));
+    }
+    Ok(())
+}
+
+/// Canonical case of a tag: `zh-Hant-TW`, `fr-CA`, `es-419`
+pub fn normalize_locale(locale: &str) -> String {
+    locale
+        .split('-')
+        .enumerate()
+        .map(|(i, subtag)| match subtag.len() {
+            _ if i == 0 => subtag.to_ascii_lowercase(),
+            2 => subtag.to_ascii_uppercase(),
+            4 if subtag.chars().all(|c| c.is_ascii_alphabetic()) => {
+                let (first, rest) = subtag.split_at(1);
+                first.to_ascii_uppercase() + &rest.to_ascii_lowercase()
+            }
+            _ => subtag.to_ascii_lowercase(),
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Translations picked for a response, by entity and field
+#[derive(Default)]
+pub struct Localized(HashMap<(String, i32, String), String>);
+
+impl Localized {
+    pub fn insert(&mut self, translation: Translation) {
+        self.0.insert(
+            (
+                translation.entity_type,
+                translation.entity_id,
+                translation.field,
+            ),
+            translation.value,
+        );
+    }
+
+    fn get(&self, entity: &str, id: i32, field: &str) -> Option<String> {
+        self.0
+            .get(&(entity.to_owned(), id, field.to_owned()))
+            .cloned()
+    }
+}
+
+/// Responses showing translatable fields
+pub trait Localize {
+    /// Entities shown, as entity type and id
+    fn entities(&self) -> Vec<(&'static str, i32)>;
+    /// Swaps the default locale text for the picked translations, fields
+    /// without one keep it
+    fn localize(&mut self, localized: &Localized);
+}
+
+impl Localize for Product {
+    fn entities(&self) -> Vec<(&'static str, i32)> {
+        vec![("product", self.id)]
+    }
+
+    fn localize(&mut self, localized: &Localized) {
+        if let Some(name) = localized.get("product", self.id, "name") {
+            self.name = name;
+        }
+        if let Some(description) = localized.get("product", self.id, "description") {
+            self.description = Some(description);
+        }
+    }
+}
+
+impl Localize for Category {
+    fn entities(&self) -> Vec<(&'static str, i32)> {
+        vec![("category", self.id)]
+    }
+
+    fn localize(&mut self, localized: &Localized) {
+        if let Some(name) = localized.get("category", self.id, "name") {
+            self.name = name;
+        }
+    }
+}
+
+impl Localize for CategoryResult {
+    fn entities(&self) -> Vec<(&'static str, i32)> {
+        self.category.entities()
+    }
+
+    fn localize(&mut self, localized: &Localized) {
+        self.category.localize(localized);
+    }
+}
+
+impl Localize for Store {
+    fn entities(&self) -> Vec<(&'static str, i32)> {
+        vec![("store", self.id)]
+    }
+
+    fn localize(&mut self, localized: &Localized) {
+        if let Some(name) = localized.get("store", self.id, "name") {
+            self.name = name;
+        }
+    }
+}
+
+impl Localize for StoreResult {
+    fn entities(&self) -> Vec<(&'static str, i32)> {
+        vec![("store", self.id)]
+    }
+
+    fn localize(&mut self, localized: &Localized) {
+        if let Some(name) = localized.get("store", self.id, "name") {
+            self.name = name;
+        }
+    }
+}
+
+impl Localize for ProductsResult {
+    fn entities(&self) -> Vec<(&'static str, i32)> {
+        let mut entities = vec![("product", self.id)];
+        entities.extend(self.categories.iter().flat_map(Localize::entities));
+        entities
+    }
+
+    fn localize(&mut self, localized: &Localized) {
+        if let Some(name) = localized.get("product", self.id, "name") {
+            self.name = name;
+        }
+        if let Some(description) = localized.get("product", self.id, "description") {
+            self.description = Some(description);
+        }
+        self.categories.localize(localized);
+    }
+}
+
+impl Localize for ProductDetails {
+    fn entities(&self) -> Vec<(&'static str, i32)> {
+        let mut entities = self.product.entities();
+        entities.extend(self.categories.iter().flat_map(Localize::entities));
+        entities.extend(self.store.iter().flat_map(Localize::entities));
+        entities
+    }
+
+    fn localize(&mut self, localized: &Localized) {
+        self.product.localize(localized);
+        if let Some(categories) = &mut self.categories {
+            categories.localize(localized);
+        }
+        if let Some(store) = &mut self.store {
+            store.localize(localized);
+        }
+    }
+}
+
+impl Localize for StoreResultWithProducts {
+    fn entities(&self) -> Vec<(&'static str, i32)> {
+        let mut entities = vec![("store", self.id)];
+        entities.extend(self.products.iter().flat_map(Localize::entities));
+        entities
+    }
+
+    fn localize(&mut self, localized: &Localized) {
+        if let Some(name) = localized.get("store", self.id, "name") {
+            self.name = name;
+        }
+        self.products.localize(localized);
+    }
+}
+
+impl<T: Localize> Localize for Vec<T> {
+    fn entities(&self) -> Vec<(&'static str, i32)> {
+        self.iter().flat_map(Localize::entities).collect()
+    }
+
+    fn localize(&mut self, localized: &Localized) {
+        self.iter_mut().for_each(|item| item.localize(localized));
+    }
+}
+
+impl<T: Localize + Serialize> Localize for Page<T> {
+    fn entities(&self) -> Vec<(&'static str, i32)> {
+        self.result().entities()
+    }
+
+    fn localize(&mut self, localized: &Localized) {
+        self.result_mut().localize(localized);
+    }
+}
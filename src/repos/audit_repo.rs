@@ -0,0 +1,72 @@
+use crate::{
+    models::{AuditEvent, NewAuditEvent, Page},
+    repos::pagination::{Keyset, Paginate, PaginationDto, SortKey},
+    routes::{AuditFilter, DateFilter},
+    schema::audit_events,
+    utils::{ApiError, Connection},
+};
+use actix_web::web;
+use diesel::{dsl::sql, prelude::*, sql_types::Text};
+
+/// Columns an audit cursor can be built on, the log is read newest first
+pub const AUDIT_CURSOR_COLUMNS: &[&str] = &["created_at"];
+
+/// Adds `event` to the log, within the transaction of the write it describes
+pub fn record(conn: &mut PgConnection, event: NewAuditEvent) -> QueryResult<usize> {
+    diesel::insert_into(audit_events::table)
+        .values(&event)
+        .execute(conn)
+}
+
+pub async fn get_many(
+    mut conn: Connection,
+    pagination: PaginationDto,
+    filter: AuditFilter,
+    date: DateFilter,
+) -> Result<Page<Vec<AuditEvent>>, ApiError> {
+    web::block(move || {
+        let mut query = audit_events::table
+            .filter(audit_events::created_at.between(date.get_after(), date.get_before()))
+            .into_boxed();
+        if let Some(entity) = filter.entity {
+            query = query.filter(audit_events::entity_type.eq(entity));
+        }
+        if let Some(entity_id) = filter.entity_id {
+            query = query.filter(audit_events::entity_id.eq(entity_id));
+        }
+        if let Some(actor) = filter.actor {
+            query = query.filter(audit_events::actor.eq(actor));
+        }
+        match pagination.cursor_page(("created_at", true), AUDIT_CURSOR_COLUMNS)? {
+            Some(cursor) => {
+                let query = match cursor.predicate() {
+                    Some(predicate) => query.filter(predicate),
+                    None => query,
+                };
+                let rows = query
+                    .order(sql::<Text>(&cursor.order()))
+                    .limit(cursor.limit())
+                    .load::<AuditEvent>(&mut conn)?;
+                Ok(Page::Cursor(cursor.finish(rows)))
+            }
+            None => query
+                .order((audit_events::created_at.desc(), audit_events::id.desc()))
+                .paginate(pagination.page)
+                .per_page(pagination.per_page)
+                .load_and_count_pages::<AuditEvent>(&mut conn)
+                .map(Page::Offset)
+                .map_err(ApiError::from),
+        }
+    })
+    .await?
+}
+
+impl Keyset for AuditEvent {
+    fn id(&self) -> i32 {
+        self.id
+    }
+
+    fn sort_key(&self, _by: &str) -> SortKey {
+        SortKey::Timestamptz(self.created_at)
+    }
+}
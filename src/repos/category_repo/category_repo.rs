@@ -1,21 +1,30 @@
 use crate::{
-    models::{CanRespond, Category, CategoryDto, ResultEnum, UpdateCategoryDto},
-    repos::pagination::{Paginate, PaginationDto},
+    models::{Category, CategoryDto, CategoryResult, NewAuditEvent, Page, PatchCategoryDto},
+    repos::{
+        audit_repo::record,
+        fuzzy,
+        pagination::{Keyset, Paginate, PaginationDto, SortKey},
+    },
     routes::{OrderBy, SearchBy, Stringify},
     schema::categories,
-    utils::Connection,
+    utils::{Actor, ApiError, Connection, Precondition},
 };
-use actix_web::{http::StatusCode, web, HttpResponse};
-use diesel::{self, dsl::sql, prelude::*, sql_types::Text};
+use actix_web::web;
+use chrono::{DateTime, Utc};
+use diesel::{self, dsl::sql, prelude::*, sql_types::Text, Connection as _};
 
-pub async fn get_category(mut conn: Connection, cat_id: i32) -> HttpResponse {
-    let result = web::block(move || {
+/// Columns a category cursor can be built on
+pub const CATEGORY_CURSOR_COLUMNS: &[&str] = &["id", "name", "created_at"];
+
+pub async fn get_category(mut conn: Connection, cat_id: i32) -> Result<Category, ApiError> {
+    web::block(move || {
         categories::table
             .find(cat_id)
+            .filter(categories::deleted_at.is_null())
             .get_result::<Category>(&mut conn)
+            .map_err(ApiError::from)
     })
-    .await;
-    ResultEnum::NotPaginated(result).respond(StatusCode::OK)
+    .await?
 }
 
 pub async fn get_many(
@@ -23,57 +32,230 @@ pub async fn get_many(
     pagination: PaginationDto,
     order: Option<OrderBy>,
     search_by: SearchBy,
-) -> HttpResponse {
-    let result = web::block(move || {
-        categories::table
-            .filter(categories::name.ilike(search_by.get_name()))
-            .order(sql::<Text>(&order.stringify()))
-            .paginate(pagination.page)
-            .per_page(pagination.per_page)
-            .load_and_count_pages::<Category>(&mut conn)
+) -> Result<Page<Vec<CategoryResult>>, ApiError> {
+    web::block(move || {
+        conn.transaction(|conn| {
+            let similar_to = search_by.fuzzy_name().map(str::to_owned);
+            let mut query = categories::table
+                .filter(categories::name.ilike(search_by.get_name()))
+                .filter(categories::deleted_at.is_null())
+                .into_boxed();
+            if let Some(name) = &similar_to {
+                fuzzy::set_threshold(conn)?;
+                query = query.filter(fuzzy::matches("category", name));
+            }
+            // Most similar names first unless another order is asked for, the
+            // similarity cannot back a cursor
+            let similar = similar_to.as_deref().filter(|_| order.is_none());
+            let sorting = match similar {
+                Some(_) => ("similarity", true),
+                None => order.sorting(),
+            };
+            let page = match pagination.cursor_page(sorting, CATEGORY_CURSOR_COLUMNS)? {
+                Some(cursor) => {
+                    let query = match cursor.predicate() {
+                        Some(predicate) => query.filter(predicate),
+                        None => query,
+                    };
+                    let rows = query
+                        .order(sql::<Text>(&cursor.order()))
+                        .limit(cursor.limit())
+                        .load::<Category>(conn)?;
+                    Page::Cursor(cursor.finish(rows))
+                }
+                None => {
+                    let query = match similar {
+                        Some(name) => query.order((
+                            fuzzy::similarity("category", name).desc(),
+                            categories::id.asc(),
+                        )),
+                        None => query.order(sql::<Text>(&order.stringify())),
+                    };
+                    Page::Offset(
+                        query
+                            .paginate(pagination.page)
+                            .per_page(pagination.per_page)
+                            .load_and_count_pages::<Category>(conn)?,
+                    )
+                }
+            };
+            let mut similarities = match &similar_to {
+                Some(name) => {
+                    let ids = page.result().iter().map(|category| category.id).collect();
+                    fuzzy::similarities(conn, "category", name, ids)?
+                }
+                None => Default::default(),
+            };
+            Ok(page.map(|categories| {
+                categories
+                    .into_iter()
+                    .map(|category| CategoryResult {
+                        similarity: similarities.remove(&category.id),
+                        category,
+                    })
+                    .collect()
+            }))
+        })
     })
-    .await;
-    ResultEnum::Paginated(result).respond(StatusCode::OK)
+    .await?
 }
 
-pub async fn add_category(mut conn: Connection, cat: CategoryDto) -> HttpResponse {
-    let result = web::block(move || {
-        diesel::insert_into(categories::table)
-            .values(categories::name.eq(cat.name))
-            .get_result::<Category>(&mut conn)
+pub async fn add_category(
+    mut conn: Connection,
+    cat: CategoryDto,
+    actor: Actor,
+) -> Result<Category, ApiError> {
+    web::block(move || {
+        conn.transaction(|conn| {
+            let category = diesel::insert_into(categories::table)
+                .values(categories::name.eq(cat.name))
+                .get_result::<Category>(conn)?;
+            let event = NewAuditEvent::new(&actor, "category", category.id, "create");
+            record(conn, event.change(None::<&Category>, Some(&category)))?;
+            Ok(category)
+        })
     })
-    .await;
-    ResultEnum::NotPaginated(result).respond(StatusCode::CREATED)
+    .await?
 }
 
 pub async fn update_category(
     mut conn: Connection,
-    cat: UpdateCategoryDto,
+    cat: PatchCategoryDto,
     cat_id: i32,
-) -> HttpResponse {
-    let result = web::block(move || {
-        diesel::update(categories::table.filter(categories::id.eq(cat_id)))
-            .set(categories::name.eq(cat.name))
-            .get_result::<Category>(&mut conn)
+    precondition: Precondition,
+    actor: Actor,
+) -> Result<Category, ApiError> {
+    web::block(move || {
+        conn.transaction(|conn| {
+            let current = lock_row(conn, cat_id, &precondition)?;
+            let name = match cat.name {
+                Some(name) => name,
+                None => return Ok(current),
+            };
+            let category = diesel::update(categories::table.find(cat_id))
+                .set(categories::name.eq(name))
+                .get_result::<Category>(conn)?;
+            let event = NewAuditEvent::new(&actor, "category", cat_id, "update");
+            record(conn, event.change(Some(&current), Some(&category)))?;
+            Ok(category)
+        })
     })
-    .await;
-    ResultEnum::NotPaginated(result).respond(StatusCode::OK)
+    .await?
 }
 
-pub async fn delete_category(mut conn: Connection, cat_id: i32) -> HttpResponse {
-    let result = web::block(move || {
-        diesel::delete(categories::table.filter(categories::id.eq(cat_id)))
-            .get_result::<Category>(&mut conn)
+pub async fn delete_category(
+    mut conn: Connection,
+    cat_id: i32,
+    precondition: Precondition,
+    actor: Actor,
+) -> Result<Category, ApiError> {
+    web::block(move || {
+        conn.transaction(|conn| {
+            let current = lock_row(conn, cat_id, &precondition)?;
+            let category = diesel::update(categories::table.find(cat_id))
+                .set(categories::deleted_at.eq(Utc::now()))
+                .get_result::<Category>(conn)?;
+            let event = NewAuditEvent::new(&actor, "category", cat_id, "delete");
+            record(conn, event.change(Some(&current), Some(&category)))?;
+            Ok(category)
+        })
     })
-    .await;
-    ResultEnum::NotPaginated(result).respond(StatusCode::OK)
+    .await?
 }
 
-pub async fn delete_many(mut conn: Connection, cat_ids: Vec<i32>) -> HttpResponse {
-    let result = web::block(move || {
-        diesel::delete(categories::table.filter(categories::id.eq_any(cat_ids)))
-            .get_result::<Category>(&mut conn)
+/// Brings a deleted category back, a live one is returned as is
+pub async fn restore_category(
+    mut conn: Connection,
+    cat_id: i32,
+    actor: Actor,
+) -> Result<Category, ApiError> {
+    web::block(move || {
+        conn.transaction(|conn| {
+            let current = categories::table
+                .find(cat_id)
+                .for_update()
+                .first::<Category>(conn)
+                .optional()?
+                .ok_or_else(|| ApiError::NotFound(format!("Category {} not found", cat_id)))?;
+            if current.deleted_at.is_none() {
+                return Ok(current);
+            }
+            let category = diesel::update(categories::table.find(cat_id))
+                .set(categories::deleted_at.eq(None::<DateTime<Utc>>))
+                .get_result::<Category>(conn)?;
+            let event = NewAuditEvent::new(&actor, "category", cat_id, "restore");
+            record(conn, event.change(Some(&current), Some(&category)))?;
+            Ok(category)
+        })
     })
-    .await;
-    ResultEnum::NotPaginated(result).respond(StatusCode::OK)
+    .await?
+}
+
+/// Locks the live category row until the end of the transaction, checks its
+/// version and returns it
+fn lock_row(
+    conn: &mut PgConnection,
+    cat_id: i32,
+    precondition: &Precondition,
+) -> Result<Category, ApiError> {
+    let category = categories::table
+        .find(cat_id)
+        .filter(categories::deleted_at.is_null())
+        .for_update()
+        .first::<Category>(conn)
+        .optional()?
+        .ok_or_else(|| ApiError::NotFound(format!("Category {} not found", cat_id)))?;
+    precondition.check(&format!("Category {}", cat_id), category.version)?;
+    Ok(category)
+}
+
+/// Every category deleted has to be at one of the versions of `precondition`
+pub async fn delete_many(
+    mut conn: Connection,
+    cat_ids: Vec<i32>,
+    precondition: Precondition,
+    actor: Actor,
+) -> Result<Category, ApiError> {
+    web::block(move || {
+        conn.transaction(|conn| {
+            let current = categories::table
+                .filter(categories::id.eq_any(&cat_ids))
+                .filter(categories::deleted_at.is_null())
+                .order(categories::id)
+                .for_update()
+                .load::<Category>(conn)?;
+            for category in &current {
+                precondition.check(&format!("Category {}", category.id), category.version)?;
+            }
+            let deleted = diesel::update(categories::table)
+                .filter(categories::id.eq_any(&cat_ids))
+                .filter(categories::deleted_at.is_null())
+                .set(categories::deleted_at.eq(Utc::now()))
+                .get_results::<Category>(conn)?;
+            for before in &current {
+                let after = deleted.iter().find(|category| category.id == before.id);
+                let event = NewAuditEvent::new(&actor, "category", before.id, "delete");
+                record(conn, event.change(Some(before), after))?;
+            }
+            deleted
+                .into_iter()
+                .next()
+                .ok_or_else(|| ApiError::NotFound("Resource not found".to_owned()))
+        })
+    })
+    .await?
+}
+
+impl Keyset for Category {
+    fn id(&self) -> i32 {
+        self.id
+    }
+
+    fn sort_key(&self, by: &str) -> SortKey {
+        match by {
+            "id" => SortKey::Int(self.id),
+            "name" => SortKey::Text(self.name.clone()),
+            _ => SortKey::Timestamp(self.created_at),
+        }
+    }
 }
@@ -0,0 +1,129 @@
+use crate::{
+    models::{ExchangeRate, ExchangeRateDto, NewAuditEvent, UpdateExchangeRateDto},
+    repos::audit_repo::record,
+    schema::exchange_rates,
+    utils::{Actor, ApiError, Connection},
+};
+use actix_web::web;
+use bigdecimal::BigDecimal;
+use chrono::Utc;
+use diesel::{prelude::*, Connection as _};
+use std::collections::HashMap;
+
+fn rate_not_found(rate_id: i32) -> ApiError {
+    ApiError::NotFound(format!("Exchange rate {} not found", rate_id))
+}
+
+/// Rates converting each of `from` to `to`. A missing pair falls back on the
+/// inverse of the reverse one, currencies with neither are left out
+pub fn rates_to(
+    conn: &mut PgConnection,
+    from: &[String],
+    to: &str,
+) -> QueryResult<HashMap<String, BigDecimal>> {
+    let rows = exchange_rates::table
+        .filter(
+            exchange_rates::from_currency
+                .eq_any(from)
+                .and(exchange_rates::to_currency.eq(to))
+                .or(exchange_rates::from_currency
+                    .eq(to)
+                    .and(exchange_rates::to_currency.eq_any(from))),
+        )
+        .load::<ExchangeRate>(conn)?;
+    let (direct, reverse): (Vec<_>, Vec<_>) =
+        rows.into_iter().partition(|rate| rate.to_currency == to);
+    let mut rates: HashMap<String, BigDecimal> = reverse
+        .into_iter()
+        .map(|rate| (rate.to_currency, BigDecimal::from(1) / rate.rate))
+        .collect();
+    rates.extend(
+        direct
+            .into_iter()
+            .map(|rate| (rate.from_currency, rate.rate)),
+    );
+    Ok(rates)
+}
+
+pub async fn get_rates(mut conn: Connection) -> Result<Vec<ExchangeRate>, ApiError> {
+    web::block(move || {
+        exchange_rates::table
+            .order((exchange_rates::from_currency, exchange_rates::to_currency))
+            .load::<ExchangeRate>(&mut conn)
+            .map_err(ApiError::from)
+    })
+    .await?
+}
+
+pub async fn get_rate(mut conn: Connection, rate_id: i32) -> Result<ExchangeRate, ApiError> {
+    web::block(move || {
+        exchange_rates::table
+            .find(rate_id)
+            .first::<ExchangeRate>(&mut conn)
+            .optional()?
+            .ok_or_else(|| rate_not_found(rate_id))
+    })
+    .await?
+}
+
+pub async fn add_rate(
+    mut conn: Connection,
+    rate: ExchangeRateDto,
+    actor: Actor,
+) -> Result<ExchangeRate, ApiError> {
+    web::block(move || {
+        conn.transaction(|conn| {
+            let added = diesel::insert_into(exchange_rates::table)
+                .values(&rate)
+                .get_result::<ExchangeRate>(conn)?;
+            let event = NewAuditEvent::new(&actor, "exchange_rate", added.id, "create");
+            record(conn, event.change(None::<&ExchangeRate>, Some(&added)))?;
+            Ok(added)
+        })
+    })
+    .await?
+}
+
+pub async fn update_rate(
+    mut conn: Connection,
+    rate_id: i32,
+    rate: UpdateExchangeRateDto,
+    actor: Actor,
+) -> Result<ExchangeRate, ApiError> {
+    web::block(move || {
+        conn.transaction(|conn| {
+            let current = exchange_rates::table
+                .find(rate_id)
+                .for_update()
+                .first::<ExchangeRate>(conn)
+                .optional()?
+                .ok_or_else(|| rate_not_found(rate_id))?;
+            let updated = diesel::update(exchange_rates::table.find(rate_id))
+                .set((&rate, exchange_rates::updated_at.eq(Utc::now())))
+                .get_result::<ExchangeRate>(conn)?;
+            let event = NewAuditEvent::new(&actor, "exchange_rate", rate_id, "update");
+            record(conn, event.change(Some(&current), Some(&updated)))?;
+            Ok(updated)
+        })
+    })
+    .await?
+}
+
+pub async fn delete_rate(
+    mut conn: Connection,
+    rate_id: i32,
+    actor: Actor,
+) -> Result<ExchangeRate, ApiError> {
+    web::block(move || {
+        conn.transaction(|conn| {
+            let deleted = diesel::delete(exchange_rates::table.find(rate_id))
+                .get_result::<ExchangeRate>(conn)
+                .optional()?
+                .ok_or_else(|| rate_not_found(rate_id))?;
+            let event = NewAuditEvent::new(&actor, "exchange_rate", rate_id, "delete");
+            record(conn, event.change(Some(&deleted), None::<&ExchangeRate>))?;
+            Ok(deleted)
+        })
+    })
+    .await?
+}
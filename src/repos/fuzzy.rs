@@ -0,0 +1,94 @@
+use diesel::{
+    dsl::sql,
+    expression::BoxableExpression,
+    pg::Pg,
+    prelude::*,
+    sql_types::{Array, Bool, Float, Integer, Text},
+};
+use std::collections::HashMap;
+
+/// Lowest word similarity of a name to the text of a fuzzy search
+pub const FUZZY_THRESHOLD: f32 = 0.4;
+
+fn table_of(entity: &'static str) -> &'static str {
+    match entity {
+        "product" => "products",
+        "store" => "stores",
+        _ => "categories",
+    }
+}
+
+/// Sets the threshold of the `<%` operator until the end of the transaction,
+/// the fuzzy search has to run inside the same one
+pub fn set_threshold(conn: &mut PgConnection) -> QueryResult<()> {
+    diesel::sql_query("SELECT set_config('pg_trgm.word_similarity_threshold', $1, true)")
+        .bind::<Text, _>(FUZZY_THRESHOLD.to_string())
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Rows of `entity` with a name, or a translated name, close enough to `text`
+/// by trigrams. Accents and case are left aside, each side goes through the
+/// trigram indexes
+pub fn matches<QS>(
+    entity: &'static str,
+    text: &str,
+) -> Box<dyn BoxableExpression<QS, Pg, SqlType = Bool>> {
+    let table = table_of(entity);
+    Box::new(
+        sql::<Bool>(&format!(
+            "{}.id IN (SELECT id FROM {} WHERE fuzzy_text(",
+            table, table
+        ))
+        .bind::<Text, _>(text.to_owned())
+        .sql(&format!(
+            ") <% fuzzy_text(name) UNION SELECT entity_id FROM translations \
+             WHERE entity_type = '{}' AND field = 'name' AND fuzzy_text(",
+            entity
+        ))
+        .bind::<Text, _>(text.to_owned())
+        .sql(") <% fuzzy_text(value))"),
+    )
+}
+
+/// Similarity of the name of `entity`, or of its closest translation, to `text`
+pub fn similarity<QS>(
+    entity: &'static str,
+    text: &str,
+) -> Box<dyn BoxableExpression<QS, Pg, SqlType = Float>> {
+    let table = table_of(entity);
+    Box::new(
+        sql::<Float>("fuzzy_similarity(")
+            .bind::<Text, _>(text.to_owned())
+            .sql(&format!(", '{}', {}.id, {}.name)", entity, table, table)),
+    )
+}
+
+#[derive(QueryableByName)]
+struct Similarity {
+    #[diesel(sql_type = Integer)]
+    id: i32,
+    #[diesel(sql_type = Float)]
+    similarity: f32,
+}
+
+/// Similarity to `text` of each of `ids`, see `similarity`
+pub fn similarities(
+    conn: &mut PgConnection,
+    entity: &'static str,
+    text: &str,
+    ids: Vec<i32>,
+) -> QueryResult<HashMap<i32, f32>> {
+    let rows = diesel::sql_query(format!(
+        "SELECT id, fuzzy_similarity($1, '{}', id, name) AS similarity FROM {} WHERE id = ANY($2)",
+        entity,
+        table_of(entity)
+    ))
+    .bind::<Text, _>(text)
+    .bind::<Array<Integer>, _>(ids)
+    .load::<Similarity>(conn)?;
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.id, row.similarity))
+        .collect())
+}
@@ -1,5 +1,11 @@
+pub mod audit_repo;
 pub mod category_repo;
+pub mod exchange_rate_repo;
+pub mod fuzzy;
 pub mod pagination;
 pub mod product_repo;
+pub mod purge_repo;
 pub mod store_repo;
+pub mod suggest_repo;
+pub mod translation_repo;
 
@@ -1,12 +1,27 @@
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, NaiveDateTime, Utc};
 use diesel::{
-    pg::Pg, prelude::*, query_builder::*, query_dsl::methods::LoadQuery, sql_types::BigInt,
+    dsl::sql,
+    expression::BoxableExpression,
+    pg::Pg,
+    prelude::*,
+    query_builder::*,
+    query_dsl::methods::LoadQuery,
+    sql_types::{BigInt, Bool, Integer, Numeric, Text, Timestamp, Timestamptz},
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use utoipa::{IntoParams, ToSchema};
 use validator::Validate;
 
+use crate::{
+    models::{CursorPaginatedResult, PaginatedResult},
+    utils::ApiError,
+};
+
 pub const DEFAULT_PER_PAGE: i64 = 20;
 pub const DEFAULT_PAGE: i64 = 1;
+/// Highest page number, keeps the offset of any page size within an `i64`
+pub const MAX_PAGE: i64 = 1_000_000;
 
 pub trait Paginate: Sized {
     fn paginate(self, page: Option<i64>) -> Paginated<Self>;
@@ -22,7 +37,7 @@ impl<T> Paginate for T {
             query: self,
             per_page: DEFAULT_PER_PAGE,
             page,
-            offset: (page - 1) * DEFAULT_PER_PAGE,
+            offset: (page - 1).saturating_mul(DEFAULT_PER_PAGE),
         }
     }
 }
@@ -43,7 +58,7 @@ impl<T> Paginated<T> {
         };
         Paginated {
             per_page,
-            offset: (self.page - 1) * per_page,
+            offset: (self.page - 1).saturating_mul(per_page),
             ..self
         }
     }
@@ -51,17 +66,24 @@ impl<T> Paginated<T> {
     pub fn load_and_count_pages<'a, U>(
         self,
         conn: &mut PgConnection,
-    ) -> QueryResult<(Vec<U>, i64, i64, i64)>
+    ) -> QueryResult<PaginatedResult<Vec<U>>>
     where
         Self: LoadQuery<'a, PgConnection, (U, i64)>,
+        U: Serialize,
     {
         let per_page = self.per_page;
         let page = self.page;
         let results = self.load::<(U, i64)>(conn)?;
-        let total = results.get(0).map(|x| x.1).unwrap_or(0);
+        let total = results.first().map(|x| x.1).unwrap_or(0);
         let records = results.into_iter().map(|x| x.0).collect();
         let total_pages = (total as f64 / per_page as f64).ceil() as i64;
-        Ok((records, total_pages, page, per_page))
+        Ok(PaginatedResult::new(
+            records,
+            total,
+            total_pages,
+            page,
+            per_page,
+        ))
     }
 }
 
@@ -91,24 +113,232 @@ pub struct PaginationDto {
     #[validate(range(min = 1))]
     #[schema(example = 10)]
     pub per_page: Option<i64>,
-    #[validate(range(min = 1))]
+    #[validate(range(min = 1, max = "MAX_PAGE"))]
     #[schema(example = 2)]
     pub page: Option<i64>,
+    /// Switches to keyset pagination, empty for the first page then the
+    /// `next_cursor`/`prev_cursor` of the previous response
+    #[schema(example = "")]
+    pub cursor: Option<String>,
 }
 
 impl PaginationDto {
     pub fn get_per_page(&self) -> i32 {
-        if let Some(per_page) = self.per_page {
-            return per_page as i32;
-        } else {
-            return DEFAULT_PER_PAGE as i32;
+        match self.per_page {
+            Some(per_page) => per_page as i32,
+            _ => DEFAULT_PER_PAGE as i32,
         }
     }
     pub fn get_page(&self) -> i32 {
-        if let Some(page) = self.page {
-            return page as  i32;
-        } else {
-            return DEFAULT_PAGE as i32;
+        match self.page {
+            Some(page) => page as i32,
+            _ => DEFAULT_PAGE as i32,
+        }
+    }
+
+    /// Rejects pages larger than the configured maximum
+    pub fn limited(self, max_per_page: i64) -> Result<Self, ApiError> {
+        match self.per_page {
+            Some(per_page) if per_page > max_per_page => Err(ApiError::Validation {
+                message: format!("per_page cannot exceed {}", max_per_page),
+                fields: vec!["per_page".to_owned()],
+            }),
+            _ => Ok(self),
+        }
+    }
+
+    /// Keyset page to load when a `cursor` was given, `None` for offset pagination.
+    /// `sorting` is the requested order, only `columns` can back a cursor
+    pub fn cursor_page(
+        &self,
+        sorting: (&str, bool),
+        columns: &[&'static str],
+    ) -> Result<Option<CursorPage>, ApiError> {
+        let token = match &self.cursor {
+            Some(token) => token,
+            None => return Ok(None),
+        };
+        if self.page.is_some() {
+            return Err(ApiError::Validation {
+                message: "page and cursor cannot be combined".to_owned(),
+                fields: vec!["page".to_owned(), "cursor".to_owned()],
+            });
+        }
+        let cursor = match token.is_empty() {
+            true => None,
+            false => Some(Cursor::decode(token)?),
+        };
+        let (by, desc) = match &cursor {
+            Some(cursor) => (cursor.by.as_str(), cursor.desc),
+            None => sorting,
+        };
+        let by = columns
+            .iter()
+            .find(|column| **column == by)
+            .ok_or_else(|| ApiError::Validation {
+                message: format!("Cursor pagination cannot order by {}", by),
+                fields: vec!["by".to_owned()],
+            })?;
+        let (position, backward) = match cursor {
+            Some(cursor) => (cursor.position, cursor.backward),
+            None => (None, false),
+        };
+        Ok(Some(CursorPage {
+            by,
+            desc,
+            position,
+            backward,
+            per_page: self.per_page.unwrap_or(DEFAULT_PER_PAGE),
+        }))
+    }
+}
+
+/// Value of the ordering column carried by a cursor
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum SortKey {
+    Int(i32),
+    Text(String),
+    Numeric(BigDecimal),
+    Timestamp(NaiveDateTime),
+    Timestamptz(DateTime<Utc>),
+}
+
+/// Position in an ordered listing, handed out base64 encoded. Without a row
+/// position it points at either end of the listing
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Cursor {
+    by: String,
+    desc: bool,
+    position: Option<(SortKey, i32)>,
+    backward: bool,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).expect("Cursor serialization failed");
+        base64::encode_config(json, base64::URL_SAFE_NO_PAD)
+    }
+
+    pub fn decode(token: &str) -> Result<Self, ApiError> {
+        base64::decode_config(token, base64::URL_SAFE_NO_PAD)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .ok_or_else(|| ApiError::Validation {
+                message: "Invalid cursor".to_owned(),
+                fields: vec!["cursor".to_owned()],
+            })
+    }
+}
+
+/// Rows that can be walked with a cursor
+pub trait Keyset {
+    fn id(&self) -> i32;
+    fn sort_key(&self, by: &str) -> SortKey;
+}
+
+/// One page of a keyset walk, ordered by `by` then id in the same direction
+#[derive(Debug, Clone)]
+pub struct CursorPage {
+    pub by: &'static str,
+    desc: bool,
+    position: Option<(SortKey, i32)>,
+    backward: bool,
+    per_page: i64,
+}
+
+impl CursorPage {
+    /// Direction the rows are fetched in, reversed when walking backward
+    pub fn is_desc(&self) -> bool {
+        self.desc != self.backward
+    }
+
+    /// Keeps the rows past the cursor, `None` from either end of the listing
+    pub fn predicate<QS>(&self) -> Option<Box<dyn BoxableExpression<QS, Pg, SqlType = Bool>>> {
+        let (key, id) = self.position.clone()?;
+        let cmp = if self.is_desc() { "<" } else { ">" };
+        let head = sql::<Bool>(&format!("({}, id) {} (", self.by, cmp));
+        Some(match key {
+            SortKey::Int(key) => Box::new(
+                head.bind::<Integer, _>(key)
+                    .sql(", ")
+                    .bind::<Integer, _>(id)
+                    .sql(")"),
+            ),
+            SortKey::Text(key) => Box::new(
+                head.bind::<Text, _>(key)
+                    .sql(", ")
+                    .bind::<Integer, _>(id)
+                    .sql(")"),
+            ),
+            SortKey::Numeric(key) => Box::new(
+                head.bind::<Numeric, _>(key)
+                    .sql(", ")
+                    .bind::<Integer, _>(id)
+                    .sql(")"),
+            ),
+            SortKey::Timestamp(key) => Box::new(
+                head.bind::<Timestamp, _>(key)
+                    .sql(", ")
+                    .bind::<Integer, _>(id)
+                    .sql(")"),
+            ),
+            SortKey::Timestamptz(key) => Box::new(
+                head.bind::<Timestamptz, _>(key)
+                    .sql(", ")
+                    .bind::<Integer, _>(id)
+                    .sql(")"),
+            ),
+        })
+    }
+
+    /// ORDER BY clause, ties broken by id
+    pub fn order(&self) -> String {
+        let dir = if self.is_desc() { "DESC" } else { "ASC" };
+        format!("{} {}, id {}", self.by, dir, dir)
+    }
+
+    /// One extra row is fetched to know whether another page follows
+    pub fn limit(&self) -> i64 {
+        self.per_page + 1
+    }
+
+    fn cursor_at(&self, position: Option<(SortKey, i32)>, backward: bool) -> String {
+        Cursor {
+            by: self.by.to_owned(),
+            desc: self.desc,
+            position,
+            backward,
+        }
+        .encode()
+    }
+
+    /// Turns the rows loaded with `predicate`, `order` and `limit` into the page
+    pub fn finish<U: Keyset + Serialize>(&self, mut rows: Vec<U>) -> CursorPaginatedResult<Vec<U>> {
+        let has_more = rows.len() as i64 > self.per_page;
+        rows.truncate(self.per_page as usize);
+        let (has_next, has_prev) = match self.backward {
+            true => {
+                rows.reverse();
+                (self.position.is_some(), has_more)
+            }
+            false => (has_more, self.position.is_some()),
+        };
+        let position = |row: &U| Some((row.sort_key(self.by), row.id()));
+        let next_cursor = rows
+            .last()
+            .filter(|_| has_next)
+            .map(|row| self.cursor_at(position(row), false));
+        let prev_cursor = rows
+            .first()
+            .filter(|_| has_prev)
+            .map(|row| self.cursor_at(position(row), true));
+        CursorPaginatedResult {
+            result: rows,
+            next_cursor,
+            prev_cursor,
+            per_page: self.per_page,
+            first_cursor: self.cursor_at(None, false),
+            last_cursor: self.cursor_at(None, true),
         }
     }
 }
@@ -1,3 +1,6 @@
+mod price_repo;
+mod product_filter;
+#[allow(clippy::module_inception)]
 mod product_repo;
 
-pub use self::product_repo::*;
+pub use self::{price_repo::*, product_filter::*, product_repo::*};
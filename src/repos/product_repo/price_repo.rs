@@ -0,0 +1,262 @@
+use crate::{
+    models::{
+        minor_unit_digits, price::Amount, NewAuditEvent, Product, ProductCurrencyPrice,
+        ProductPrice, DEFAULT_CURRENCY,
+    },
+    repos::{
+        audit_repo::record, exchange_rate_repo::rates_to, product_repo::product_repo::lock_row,
+    },
+    schema::{product_currency_prices, product_prices, products, stores},
+    utils::{Actor, ApiError, Connection, Precondition},
+};
+use actix_web::web;
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+use diesel::{prelude::*, Connection as _};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+fn find_product(conn: &mut PgConnection, prod_id: i32) -> Result<Product, ApiError> {
+    products::table
+        .find(prod_id)
+        .filter(products::deleted_at.is_null())
+        .select(Product::as_select())
+        .first::<Product>(conn)
+        .optional()?
+        .ok_or_else(|| ApiError::NotFound(format!("Product {} not found", prod_id)))
+}
+
+/// Price history of a live product, oldest first. With `at`, only the price in
+/// force at that instant, none when the product had no price yet
+pub async fn get_prices(
+    mut conn: Connection,
+    prod_id: i32,
+    at: Option<DateTime<Utc>>,
+) -> Result<Vec<ProductPrice>, ApiError> {
+    web::block(move || {
+        let product = find_product(&mut conn, prod_id)?;
+        let prices = ProductPrice::belonging_to(&product);
+        match at {
+            Some(at) => prices
+                .filter(product_prices::effective_from.le(at))
+                .order((
+                    product_prices::effective_from.desc(),
+                    product_prices::id.desc(),
+                ))
+                .limit(1)
+                .load::<ProductPrice>(&mut conn),
+            None => prices
+                .order((product_prices::effective_from, product_prices::id))
+                .load::<ProductPrice>(&mut conn),
+        }
+        .map_err(ApiError::from)
+    })
+    .await?
+}
+
+/// Currency of the prices of the products in `store_id`, see `prices_in`
+pub fn store_currency(conn: &mut PgConnection, store_id: Option<i32>) -> QueryResult<String> {
+    let currency = match store_id {
+        Some(store_id) => stores::table
+            .find(store_id)
+            .select(stores::currency)
+            .first::<String>(conn)
+            .optional()?,
+        None => None,
+    };
+    Ok(currency.unwrap_or_else(|| DEFAULT_CURRENCY.to_owned()))
+}
+
+/// Prices are amounts in the store currency, so a product only moves to a store
+/// of another currency along with a new price
+pub fn check_same_currency(
+    conn: &mut PgConnection,
+    from: Option<i32>,
+    to: Option<i32>,
+) -> Result<(), ApiError> {
+    if from == to {
+        return Ok(());
+    }
+    let (from, to) = (store_currency(conn, from)?, store_currency(conn, to)?);
+    match from == to {
+        true => Ok(()),
+        false => Err(ApiError::Validation {
+            message: format!("Price is in {}, the store prices in {}", from, to),
+            fields: vec!["store_id".to_owned(), "price".to_owned()],
+        }),
+    }
+}
+
+/// The price an amount sent in `currency` stands for
+pub fn read_amount(amount: &Amount, currency: &str) -> Result<BigDecimal, ApiError> {
+    amount
+        .in_currency(currency)
+        .map_err(|err| ApiError::Validation {
+            message: err.code.into_owned(),
+            fields: vec!["price".to_owned()],
+        })
+}
+
+/// Prices of `products` in `currency`, in the same order. A price set for the
+/// currency wins, the others are converted from the store currency and rounded
+/// half up to its minor unit
+pub fn prices_in(
+    conn: &mut PgConnection,
+    products: &[Product],
+    currency: &str,
+) -> Result<Vec<BigDecimal>, ApiError> {
+    let ids: Vec<i32> = products.iter().map(|product| product.id).collect();
+    let set: HashMap<i32, BigDecimal> = product_currency_prices::table
+        .filter(product_currency_prices::product_id.eq_any(&ids))
+        .filter(product_currency_prices::currency.eq(currency))
+        .select((
+            product_currency_prices::product_id,
+            product_currency_prices::price,
+        ))
+        .load::<(i32, BigDecimal)>(conn)?
+        .into_iter()
+        .collect();
+    let store_ids: Vec<i32> = products
+        .iter()
+        .filter_map(|product| product.store_id)
+        .collect();
+    let store_currencies: HashMap<i32, String> = stores::table
+        .filter(stores::id.eq_any(&store_ids))
+        .select((stores::id, stores::currency))
+        .load::<(i32, String)>(conn)?
+        .into_iter()
+        .collect();
+    let from = |product: &Product| {
+        product
+            .store_id
+            .and_then(|store_id| store_currencies.get(&store_id))
+            .map_or(DEFAULT_CURRENCY, String::as_str)
+    };
+    let mut sources: Vec<String> = products.iter().map(|p| from(p).to_owned()).collect();
+    sources.sort_unstable();
+    sources.dedup();
+    let rates = rates_to(conn, &sources, currency)?;
+    products
+        .iter()
+        .map(|product| {
+            if let Some(price) = set.get(&product.id) {
+                return Ok(price.clone());
+            }
+            let from = from(product);
+            if from == currency {
+                return Ok(product.price.clone());
+            }
+            let rate = rates.get(from).ok_or_else(|| ApiError::Validation {
+                message: format!("No exchange rate from {} to {}", from, currency),
+                fields: vec!["currency".to_owned()],
+            })?;
+            let digits = minor_unit_digits(currency);
+            Ok((&product.price * rate + BigDecimal::new(5.into(), digits + 1)).with_scale(digits))
+        })
+        .collect()
+}
+
+pub async fn get_currency_prices(
+    mut conn: Connection,
+    prod_id: i32,
+) -> Result<Vec<ProductCurrencyPrice>, ApiError> {
+    web::block(move || {
+        find_product(&mut conn, prod_id)?;
+        product_currency_prices::table
+            .filter(product_currency_prices::product_id.eq(prod_id))
+            .order(product_currency_prices::currency)
+            .load::<ProductCurrencyPrice>(&mut conn)
+            .map_err(ApiError::from)
+    })
+    .await?
+}
+
+/// Currency prices are shown in the audit log under the product, keyed by currency
+fn snapshot(price: &ProductCurrencyPrice) -> Value {
+    json!({ "currency_prices": { &price.currency: price.price.with_scale(2).to_string() } })
+}
+
+/// Currency prices change the product representation, so they bump its version
+fn touch_product(conn: &mut PgConnection, prod_id: i32) -> QueryResult<usize> {
+    diesel::update(products::table.find(prod_id))
+        .set(products::version.eq(products::version))
+        .execute(conn)
+}
+
+/// Sets the price of the product in `currency`, replacing the previous one
+pub async fn set_currency_price(
+    mut conn: Connection,
+    prod_id: i32,
+    currency: String,
+    price: Amount,
+    precondition: Precondition,
+    actor: Actor,
+) -> Result<ProductCurrencyPrice, ApiError> {
+    let price = read_amount(&price, &currency)?;
+    web::block(move || {
+        conn.transaction(|conn| {
+            let product = lock_row(conn, prod_id, &precondition)?;
+            if store_currency(conn, product.store_id)? == currency {
+                return Err(ApiError::Validation {
+                    message: format!("Product {} is priced in {} already", prod_id, currency),
+                    fields: vec!["currency".to_owned()],
+                });
+            }
+            let current = product_currency_prices::table
+                .find((prod_id, &currency))
+                .for_update()
+                .first::<ProductCurrencyPrice>(conn)
+                .optional()?;
+            let set = diesel::insert_into(product_currency_prices::table)
+                .values((
+                    product_currency_prices::product_id.eq(prod_id),
+                    product_currency_prices::currency.eq(&currency),
+                    product_currency_prices::price.eq(&price),
+                ))
+                .on_conflict((
+                    product_currency_prices::product_id,
+                    product_currency_prices::currency,
+                ))
+                .do_update()
+                .set((
+                    product_currency_prices::price.eq(&price),
+                    product_currency_prices::updated_at.eq(Utc::now()),
+                ))
+                .get_result::<ProductCurrencyPrice>(conn)?;
+            touch_product(conn, prod_id)?;
+            let event = NewAuditEvent::new(&actor, "product", prod_id, "set_currency_price");
+            let before = current.as_ref().map(snapshot);
+            record(conn, event.change(before.as_ref(), Some(&snapshot(&set))))?;
+            Ok(set)
+        })
+    })
+    .await?
+}
+
+pub async fn delete_currency_price(
+    mut conn: Connection,
+    prod_id: i32,
+    currency: String,
+    precondition: Precondition,
+    actor: Actor,
+) -> Result<ProductCurrencyPrice, ApiError> {
+    web::block(move || {
+        conn.transaction(|conn| {
+            lock_row(conn, prod_id, &precondition)?;
+            let deleted = diesel::delete(product_currency_prices::table.find((prod_id, &currency)))
+                .get_result::<ProductCurrencyPrice>(conn)
+                .optional()?
+                .ok_or_else(|| {
+                    ApiError::NotFound(format!("Product {} has no {} price", prod_id, currency))
+                })?;
+            touch_product(conn, prod_id)?;
+            let event = NewAuditEvent::new(&actor, "product", prod_id, "delete_currency_price");
+            record(
+                conn,
+                event.change(Some(&snapshot(&deleted)), None::<&Value>),
+            )?;
+            Ok(deleted)
+        })
+    })
+    .await?
+}
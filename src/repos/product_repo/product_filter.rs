@@ -0,0 +1,240 @@
+use crate::{
+    models::Product,
+    repos::{
+        fuzzy,
+        pagination::{CursorPage, Keyset, SortKey},
+    },
+    routes::{OrderBy, SearchBy, Stringify},
+    schema::{products, products_categories},
+    utils::DEFAULT_LOCALE,
+};
+use bigdecimal::BigDecimal;
+use diesel::{
+    dsl::{sql, AsSelect},
+    expression::BoxableExpression,
+    pg::Pg,
+    prelude::*,
+    sql_types::{Array, Bool, Float, Integer, Text},
+};
+use std::collections::HashMap;
+
+pub type BoxedProductQuery<'a> = products::BoxedQuery<'a, Pg, AsSelect<Product, Pg>>;
+
+/// Columns a product cursor can be built on, the nullable description is left out
+pub const PRODUCT_CURSOR_COLUMNS: &[&str] = &["id", "name", "price", "created_at"];
+
+/// Composes the optional filters of the product listing on top of a boxed query,
+/// every value goes through bind parameters
+pub struct ProductFilter<'a> {
+    query: BoxedProductQuery<'a>,
+}
+
+impl<'a> Default for ProductFilter<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> ProductFilter<'a> {
+    pub fn new() -> Self {
+        ProductFilter {
+            query: products::table
+                .select(Product::as_select())
+                .filter(products::deleted_at.is_null())
+                .into_boxed(),
+        }
+    }
+
+    pub fn category(self, category_id: Option<i32>) -> Self {
+        match category_id {
+            Some(cat_id) => ProductFilter {
+                query: self.query.filter(
+                    products::id.eq_any(
+                        products_categories::table
+                            .filter(products_categories::category_id.eq(cat_id))
+                            .select(products_categories::product_id),
+                    ),
+                ),
+            },
+            None => self,
+        }
+    }
+
+    pub fn store(self, store_id: Option<i32>) -> Self {
+        match store_id {
+            Some(id) => ProductFilter {
+                query: self.query.filter(products::store_id.eq(id)),
+            },
+            None => self,
+        }
+    }
+
+    /// Matches name and/or description, either one matching when both are given.
+    /// With `fuzzy` the name is matched by similarity
+    pub fn search(self, search: &SearchBy) -> Self {
+        let name: Box<dyn BoxableExpression<products::table, Pg, SqlType = Bool>> =
+            match search.fuzzy_name() {
+                Some(name) => fuzzy::matches("product", name),
+                None => Box::new(products::name.ilike(search.get_name())),
+            };
+        let query = match (&search.name, &search.description) {
+            (Some(_), Some(_)) => self
+                .query
+                .filter(name.or(products::description.ilike(search.get_description()))),
+            (Some(_), None) => self.query.filter(name),
+            (None, Some(_)) => self
+                .query
+                .filter(products::description.ilike(search.get_description())),
+            (None, None) => self.query,
+        };
+        ProductFilter { query }
+    }
+
+    /// Full-text match of `q` against the names and descriptions, translations
+    /// included. The words are also stemmed in the languages of `locales`
+    pub fn text_search(self, q: Option<String>, locales: Vec<String>) -> Self {
+        match q {
+            Some(q) => ProductFilter {
+                query: self.query.filter(
+                    sql::<Bool>("products.search_vector @@ product_search_query(")
+                        .bind::<Text, _>(q)
+                        .sql(", ")
+                        .bind::<Array<Text>, _>(locales)
+                        .sql(")"),
+                ),
+            },
+            None => self,
+        }
+    }
+
+    /// Compares the raw amounts, each in the currency of its store
+    pub fn price_range(self, min: Option<BigDecimal>, max: Option<BigDecimal>) -> Self {
+        let mut query = self.query;
+        if let Some(min) = min {
+            query = query.filter(products::price.ge(min));
+        }
+        if let Some(max) = max {
+            query = query.filter(products::price.le(max));
+        }
+        ProductFilter { query }
+    }
+
+    /// Orders by the requested column, ties broken by id so pages stay stable
+    pub fn order(self, order: Option<OrderBy>) -> Self {
+        let (by, desc) = order.sorting();
+        self.sort(by, desc)
+    }
+
+    /// Best matches of `q` first, ties broken by id
+    pub fn rank(self, q: String, locales: Vec<String>) -> Self {
+        let rank = sql::<Float>("ts_rank(products.search_vector, product_search_query(")
+            .bind::<Text, _>(q)
+            .sql(", ")
+            .bind::<Array<Text>, _>(locales)
+            .sql("))");
+        ProductFilter {
+            query: self.query.order((rank.desc(), products::id.asc())),
+        }
+    }
+
+    /// Names most similar to `name` first, ties broken by id
+    pub fn similar(self, name: &str) -> Self {
+        let similarity = fuzzy::similarity("product", name);
+        ProductFilter {
+            query: self.query.order((similarity.desc(), products::id.asc())),
+        }
+    }
+
+    /// Restricts to the rows past the cursor, in the walking direction
+    pub fn keyset(self, page: &CursorPage) -> Self {
+        let query = match page.predicate() {
+            Some(predicate) => self.query.filter(predicate),
+            None => self.query,
+        };
+        ProductFilter { query }.sort(page.by, page.is_desc())
+    }
+
+    fn sort(self, by: &str, desc: bool) -> Self {
+        let query = match (by, desc) {
+            ("name", false) => self.query.order(products::name.asc()),
+            ("name", true) => self.query.order(products::name.desc()),
+            ("description", false) => self.query.order(products::description.asc()),
+            ("description", true) => self.query.order(products::description.desc()),
+            ("price", false) => self.query.order(products::price.asc()),
+            ("price", true) => self.query.order(products::price.desc()),
+            ("id", false) => self.query.order(products::id.asc()),
+            ("id", true) => self.query.order(products::id.desc()),
+            (_, false) => self.query.order(products::created_at.asc()),
+            (_, true) => self.query.order(products::created_at.desc()),
+        };
+        let query = match desc {
+            true => query.then_order_by(products::id.desc()),
+            false => query.then_order_by(products::id.asc()),
+        };
+        ProductFilter { query }
+    }
+
+    pub fn into_query(self) -> BoxedProductQuery<'a> {
+        self.query
+    }
+}
+
+impl Keyset for Product {
+    fn id(&self) -> i32 {
+        self.id
+    }
+
+    fn sort_key(&self, by: &str) -> SortKey {
+        match by {
+            "id" => SortKey::Int(self.id),
+            "name" => SortKey::Text(self.name.clone()),
+            "price" => SortKey::Numeric(self.price.clone()),
+            _ => SortKey::Timestamp(self.created_at),
+        }
+    }
+}
+
+/// Relevance of a product to a full-text query, with the matches highlighted
+#[derive(QueryableByName)]
+struct SearchHit {
+    #[diesel(sql_type = Integer)]
+    id: i32,
+    #[diesel(sql_type = Float)]
+    rank: f32,
+    #[diesel(sql_type = Text)]
+    snippet: String,
+}
+
+/// Rank and snippet of each of `ids` for `q`, the snippet fragments come from
+/// the default text and the translations with the matches in `<mark>`. The
+/// text is HTML escaped, only the marks are markup. Words are highlighted as
+/// stemmed in the most preferred of `locales`, one of the languages
+/// `product_search_query` matches in
+pub fn search_hits(
+    conn: &mut PgConnection,
+    q: &str,
+    locales: &[String],
+    ids: Vec<i32>,
+) -> QueryResult<HashMap<i32, (f32, String)>> {
+    let preferred = locales
+        .iter()
+        .find(|locale| *locale != "*")
+        .map_or(DEFAULT_LOCALE, String::as_str);
+    let hits = diesel::sql_query(
+        "SELECT p.id, ts_rank(p.search_vector, query) AS rank, \
+         ts_headline(locale_search_config($4), \
+         replace(replace(replace(product_search_text(p.id, p.name, p.description), \
+         '&', '&amp;'), '<', '&lt;'), '>', '&gt;'), query, \
+         'StartSel=<mark>, StopSel=</mark>, MaxFragments=2, MaxWords=20, MinWords=5, FragmentDelimiter=\" … \"') AS snippet \
+         FROM products p, product_search_query($1, $2) query WHERE p.id = ANY($3)",
+    )
+    .bind::<Text, _>(q)
+    .bind::<Array<Text>, _>(locales)
+    .bind::<Array<Integer>, _>(ids)
+    .bind::<Text, _>(preferred)
+    .load::<SearchHit>(conn)?;
+    Ok(hits
+        .into_iter()
+        .map(|hit| (hit.id, (hit.rank, hit.snippet)))
+        .collect())
+}
@@ -1,172 +1,439 @@
 use crate::{
     models::{
-        CanRespond, Category, InsertableProduct, Product, ProductDto, ProductsCategories,
-        ProductsResult, ResultEnum, Store, UpdateProductDto,
+        Category, InsertableProduct, NewAuditEvent, Page, PatchProductDto, PriceRange, Product,
+        ProductChangeset, ProductDetails, ProductDto, ProductExpand, ProductsCategories,
+        ProductsResult, Store, Worktimes,
+    },
+    repos::{
+        audit_repo::record,
+        fuzzy,
+        pagination::{Paginate, PaginationDto},
+        product_repo::{
+            check_same_currency, prices_in, read_amount, search_hits, store_currency,
+            ProductFilter, PRODUCT_CURSOR_COLUMNS,
+        },
+        store_repo::current_closures,
     },
-    repos::pagination::PaginationDto,
     routes::{OrderBy, SearchBy, Stringify},
-    schema::{categories, products, products_categories},
-    utils::Connection,
-};
-use actix_web::{http::StatusCode, web, HttpResponse};
-use diesel::{
-    self,
-    prelude::*,
-    sql_query,
-    sql_types::{Integer, Text},
+    schema::{categories, products, products_categories, stores, worktimes},
+    utils::{AcceptedLocales, Actor, ApiError, Connection, Precondition},
 };
+use actix_web::web;
+use chrono::{DateTime, Utc};
+use diesel::{self, prelude::*, Connection as _};
 
-pub async fn get_product(mut conn: Connection, prod_id: i32) -> HttpResponse {
-    let result = web::block(move || {
-        products::table.find(prod_id).first::<Product>(&mut conn)
-        //TODO: get categories
+pub async fn get_product(
+    mut conn: Connection,
+    prod_id: i32,
+    expand: ProductExpand,
+    currency: Option<String>,
+) -> Result<ProductDetails, ApiError> {
+    web::block(move || {
+        let mut product = products::table
+            .find(prod_id)
+            .filter(products::deleted_at.is_null())
+            .select(Product::as_select())
+            .first::<Product>(&mut conn)
+            .optional()?
+            .ok_or_else(|| ApiError::NotFound(format!("Product {} not found", prod_id)))?;
+        let categories = match expand.categories() {
+            true => Some(
+                ProductsCategories::belonging_to(&product)
+                    .inner_join(categories::table)
+                    .filter(categories::deleted_at.is_null())
+                    .order(categories::id)
+                    .select(categories::all_columns)
+                    .load::<Category>(&mut conn)?,
+            ),
+            false => None,
+        };
+        let store = match (expand.store(), product.store_id) {
+            (true, Some(store_id)) => stores::table
+                .find(store_id)
+                .filter(stores::deleted_at.is_null())
+                .first::<Store>(&mut conn)
+                .optional()?,
+            _ => None,
+        };
+        let store = match store {
+            Some(store) => {
+                let worktimes = Worktimes::belonging_to(&store)
+                    .order(worktimes::day_id)
+                    .load::<Worktimes>(&mut conn)?;
+                let closures = current_closures(&mut conn, std::slice::from_ref(&store))?.remove(0);
+                Some((store, worktimes, closures).into())
+            }
+            None => None,
+        };
+        if let Some(currency) = &currency {
+            product.price =
+                prices_in(&mut conn, std::slice::from_ref(&product), currency)?.remove(0);
+        }
+        Ok(ProductDetails {
+            product,
+            categories,
+            store,
+            currency,
+        })
     })
-    .await;
-    ResultEnum::NotPaginated(result).respond(StatusCode::OK)
-}
-
-enum Test {
-    WithPc(Result<Vec<(Product, Option<Store>, Option<ProductsCategories>)>, diesel::result::Error>),
-    WithoutPc(Result<Vec<(Product, Option<Store>)>, diesel::result::Error>)
+    .await?
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn get_many(
     mut conn: Connection,
     pagination: PaginationDto,
     order: Option<OrderBy>,
     search: SearchBy,
     category_id: Option<i32>,
-    store: Option<i32>
-) -> HttpResponse {
-    let result = web::block(move || {
-        // 1st DB call
-        let res = if let Some(cat_id) = category_id {
-            let mut db_query_one = String::from("SELECT * from products p left join stores s on s.id = p.store_id");
-            db_query_one.push_str(" right join products_categories pc on pc.product_id = p.id and pc.category_id = $1");
-            let mut db_query_two = format!(" WHERE (p.name ILIKE $2 OR p.description ILIKE $3) ");
-            if let Some(id) = store {
-                db_query_two.push_str(&format!("AND p.store_id = {}", id))
-            }
-            db_query_two.push_str(&format!(" ORDER BY p.{} LIMIT $4 OFFSET $5", order.stringify()));
-            db_query_one.push_str(&db_query_two);
-            Test::WithPc(sql_query(db_query_one)
-                .bind::<Integer, _>(cat_id as i32)
-                .bind::<Text, _>(search.get_name())
-                .bind::<Text, _>(search.get_description())
-                .bind::<Integer, _>(pagination.get_per_page())
-                .bind::<Integer, _>((pagination.get_page() - 1) * pagination.get_per_page())
-                .load::<(Product, Option<Store>, Option<ProductsCategories>)>(&mut conn))
-        } else {
-            let mut db_query_one = String::from("SELECT distinct p.id, p.name, p.i18n_name, p.description, p.i18n_description, p.price, p.store_id, p.created_at, s.id, s.created_at, s.is_holiday, s.name, s.prod_count from products p left join stores s on s.id = p.store_id");
-            let mut db_query_two = String::from(" left join products_categories pc on pc.product_id = p.id WHERE (p.name ILIKE $1 OR p.description ILIKE  $2) ");
-            if let Some(id) = store {
-                db_query_two.push_str(&format!("AND p.store_id = {}", id))
+    store: Option<i32>,
+    price: PriceRange,
+    currency: Option<String>,
+    q: Option<String>,
+    locales: AcceptedLocales,
+) -> Result<Page<Vec<ProductsResult>>, ApiError> {
+    web::block(move || {
+        conn.transaction(|conn| {
+            let similar_to = search.fuzzy_name().map(str::to_owned);
+            if similar_to.is_some() {
+                fuzzy::set_threshold(conn)?;
             }
-            db_query_two.push_str(&format!(" ORDER BY p.{} LIMIT $3 OFFSET $4", order.stringify()));
-            db_query_one.push_str(&db_query_two);
-            let res = sql_query(db_query_one)
-                .bind::<Text,_>(search.get_name())
-                .bind::<Text,_>(search.get_description())
-                .bind::<Integer,_>(pagination.get_per_page())
-                .bind::<Integer,_>((pagination.get_page() - 1) * pagination.get_per_page())
-                .load::<(Product, Option<Store>)>(&mut conn);
-            Test::WithoutPc(res)
-        };
-        let (products, _) = match res {
-            Test::WithPc(val) => {
-                let data = val.unwrap();
-                let products = data.clone()
-                    .into_iter()
-                    .map(|data: (Product, Option<Store>, Option<ProductsCategories>)| data.0)
-                    .collect::<Vec<Product>>();
-                let products_stores = data
-                    .into_iter()
-                    .map(|data: (Product, Option<Store>, Option<ProductsCategories>)| data.1)
-                    .collect::<Vec<Option<Store>>>();
-                (products, products_stores)
-            },
-            Test::WithoutPc(val) => {
-                let data = val.unwrap();
-                let products = data.clone()
+            let filter = ProductFilter::new()
+                .category(category_id)
+                .store(store)
+                .search(&search)
+                .text_search(q.clone(), locales.0.clone())
+                .price_range(price.min_price, price.max_price);
+            // Matches of `q` come best first unless another order is asked for,
+            // then the names most similar to a fuzzy one. Neither the rank nor the
+            // similarity can back a cursor
+            let ranked = q.clone().filter(|_| order.is_none());
+            let similar = similar_to
+                .as_deref()
+                .filter(|_| order.is_none() && ranked.is_none());
+            let sorting = match (&ranked, similar) {
+                (Some(_), _) => ("rank", true),
+                (None, Some(_)) => ("similarity", true),
+                (None, None) => order.sorting(),
+            };
+            // 1st DB call
+            let page = match pagination.cursor_page(sorting, PRODUCT_CURSOR_COLUMNS)? {
+                Some(cursor) => Page::Cursor(
+                    cursor.finish(
+                        filter
+                            .keyset(&cursor)
+                            .into_query()
+                            .limit(cursor.limit())
+                            .load::<Product>(conn)?,
+                    ),
+                ),
+                None => {
+                    let filter = match (ranked, similar) {
+                        (Some(q), _) => filter.rank(q, locales.0.clone()),
+                        (None, Some(name)) => filter.similar(name),
+                        (None, None) => filter.order(order),
+                    };
+                    Page::Offset(
+                        filter
+                            .into_query()
+                            .paginate(pagination.page)
+                            .per_page(pagination.per_page)
+                            .load_and_count_pages::<Product>(conn)?,
+                    )
+                }
+            };
+            // 2nd DB call
+            let cats = ProductsCategories::belonging_to(page.result())
+                .inner_join(categories::table)
+                .filter(categories::deleted_at.is_null())
+                .load::<(ProductsCategories, Category)>(conn)?
+                .grouped_by(page.result());
+            // 3rd DB call, prices are converted once the cursors are taken
+            let prices = match &currency {
+                Some(currency) => Some(prices_in(conn, page.result(), currency)?),
+                None => None,
+            };
+            // 4th DB call
+            let mut hits = match &q {
+                Some(q) => {
+                    let ids = page.result().iter().map(|product| product.id).collect();
+                    search_hits(conn, q, &locales.0, ids)?
+                }
+                None => Default::default(),
+            };
+            // 5th DB call
+            let mut similarities = match &similar_to {
+                Some(name) => {
+                    let ids = page.result().iter().map(|product| product.id).collect();
+                    fuzzy::similarities(conn, "product", name, ids)?
+                }
+                None => Default::default(),
+            };
+            Ok(page.map(|products| {
+                // data transformation
+                let mut products = products
                     .into_iter()
-                    .map(|data: (Product, Option<Store>)| data.0)
-                    .collect::<Vec<Product>>();
-                let products_stores = data
-                    .into_iter()
-                    .map(|data: (Product, Option<Store>)| data.1)
-                    .collect::<Vec<Option<Store>>>();
-                (products, products_stores)
-            },
-        };
-        // 2nd DB call
-        let cats = ProductsCategories::belonging_to(&products)
-            .inner_join(categories::table)
-            .load::<(ProductsCategories, Category)>(&mut conn)
-            .unwrap()
-            .grouped_by(&products);
-        let x = {
-            if products.len() >= pagination.get_per_page() as usize {
-                pagination.get_page() + 1
-            } else {
-                pagination.get_page()
-            }
-        };
-        Ok((
-            // data transformation
-            products
-                .into_iter()
-                .zip(cats)
-                .map(|data: (Product, Vec<(ProductsCategories, Category)>)| data.into())
-                .collect::<Vec<ProductsResult>>()
-            ,
-            x as i64,
-            pagination.get_page() as i64,
-            pagination.get_per_page() as i64 
-        ))
+                    .zip(cats)
+                    .map(|data: (Product, Vec<(ProductsCategories, Category)>)| data.into())
+                    .collect::<Vec<ProductsResult>>();
+                for (product, price) in products.iter_mut().zip(prices.into_iter().flatten()) {
+                    product.price = price;
+                    product.currency = currency.clone();
+                }
+                for product in products.iter_mut() {
+                    if let Some((rank, snippet)) = hits.remove(&product.id) {
+                        product.rank = Some(rank);
+                        product.snippet = Some(snippet);
+                    }
+                    product.similarity = similarities.remove(&product.id);
+                }
+                products
+            }))
+        })
     })
-    .await;
-    ResultEnum::Paginated(result).respond(StatusCode::OK)
+    .await?
 }
 
-pub async fn add_product(mut conn: Connection, prod: ProductDto) -> HttpResponse {
-    let result = web::block(move || {
-        diesel::insert_into(products::table)
-            .values(<ProductDto as Into<InsertableProduct>>::into(prod))
-            .get_result::<Product>(&mut conn)
-        //TODO: Add product category if Some(prod.category_id)
+pub async fn add_product(
+    mut conn: Connection,
+    mut prod: ProductDto,
+    actor: Actor,
+) -> Result<ProductsResult, ApiError> {
+    web::block(move || {
+        conn.transaction(|conn| {
+            let category_ids = std::mem::take(&mut prod.category_ids);
+            if let Some(store_id) = prod.store_id {
+                share_store(conn, store_id)?;
+            }
+            let price = read_amount(&prod.price, &store_currency(conn, prod.store_id)?)?;
+            let product = diesel::insert_into(products::table)
+                .values(InsertableProduct::from((prod, price)))
+                .returning(Product::as_returning())
+                .get_result::<Product>(conn)?;
+            replace_categories(conn, product.id, category_ids)?;
+            let product = with_categories(conn, product)?;
+            let event = NewAuditEvent::new(&actor, "product", product.id, "create");
+            record(conn, event.change(None::<&ProductsResult>, Some(&product)))?;
+            Ok(product)
+        })
     })
-    .await;
-    ResultEnum::NotPaginated(result).respond(StatusCode::CREATED)
+    .await?
 }
 
 pub async fn update_product(
     mut conn: Connection,
     prod_id: i32,
-    prod: UpdateProductDto,
-) -> HttpResponse {
-    let result = web::block(move || {
-        diesel::update(products::table)
-            .filter(products::columns::id.eq(prod_id))
-            .set(&<UpdateProductDto as Into<InsertableProduct>>::into(prod))
-            .get_result::<Product>(&mut conn)
-        //TODO: Add product category if Some(prod.category_id)
-        //FIXME: Rather create new endpoint
+    mut prod: PatchProductDto,
+    precondition: Precondition,
+    actor: Actor,
+) -> Result<ProductsResult, ApiError> {
+    web::block(move || {
+        conn.transaction(|conn| {
+            let current = lock_row(conn, prod_id, &precondition)?;
+            let category_ids = prod.category_ids.take();
+            // A product left in a deleted store keeps it, moving there is refused
+            match prod.store_id {
+                Some(Some(store_id)) if Some(store_id) != current.store_id => {
+                    share_store(conn, store_id)?
+                }
+                _ => (),
+            }
+            let store_id = prod.store_id.unwrap_or(current.store_id);
+            let price = match &prod.price {
+                Some(amount) => Some(read_amount(amount, &store_currency(conn, store_id)?)?),
+                None => {
+                    check_same_currency(conn, current.store_id, store_id)?;
+                    None
+                }
+            };
+            let changes = ProductChangeset::from((prod, price));
+            if changes.is_empty() && category_ids.is_none() {
+                return with_categories(conn, current);
+            }
+            let before = with_categories(conn, current)?;
+            let product = match changes.is_empty() {
+                // Categories belong to the product version, the row is touched anyway
+                true => diesel::update(products::table.find(prod_id))
+                    .set(products::version.eq(products::version))
+                    .returning(Product::as_returning())
+                    .get_result::<Product>(conn)?,
+                false => diesel::update(products::table.find(prod_id))
+                    .set(&changes)
+                    .returning(Product::as_returning())
+                    .get_result::<Product>(conn)?,
+            };
+            if let Some(category_ids) = category_ids {
+                replace_categories(conn, product.id, category_ids.unwrap_or_default())?;
+            }
+            let product = with_categories(conn, product)?;
+            let event = NewAuditEvent::new(&actor, "product", prod_id, "update");
+            record(conn, event.change(Some(&before), Some(&product)))?;
+            Ok(product)
+        })
+    })
+    .await?
+}
+
+/// Locks the live product row until the end of the transaction, checks its
+/// version and returns it
+pub(super) fn lock_row(
+    conn: &mut PgConnection,
+    prod_id: i32,
+    precondition: &Precondition,
+) -> Result<Product, ApiError> {
+    let product = products::table
+        .find(prod_id)
+        .filter(products::deleted_at.is_null())
+        .select(Product::as_select())
+        .for_update()
+        .first::<Product>(conn)
+        .optional()?
+        .ok_or_else(|| ApiError::NotFound(format!("Product {} not found", prod_id)))?;
+    precondition.check(&format!("Product {}", prod_id), product.version)?;
+    Ok(product)
+}
+
+/// Keeps the live category from being deleted until the end of the transaction
+fn share_category(conn: &mut PgConnection, cat_id: i32) -> Result<(), ApiError> {
+    categories::table
+        .find(cat_id)
+        .filter(categories::deleted_at.is_null())
+        .select(categories::id)
+        .for_share()
+        .first::<i32>(conn)
+        .optional()?
+        .ok_or_else(|| ApiError::NotFound(format!("Category {} not found", cat_id)))?;
+    Ok(())
+}
+
+/// `share_category` of every category of `cat_ids` at once
+fn share_categories(conn: &mut PgConnection, cat_ids: &[i32]) -> Result<(), ApiError> {
+    let live = categories::table
+        .filter(categories::id.eq_any(cat_ids))
+        .filter(categories::deleted_at.is_null())
+        .select(categories::id)
+        .for_share()
+        .load::<i32>(conn)?;
+    match cat_ids.iter().find(|cat_id| !live.contains(cat_id)) {
+        Some(cat_id) => Err(ApiError::NotFound(format!("Category {} not found", cat_id))),
+        None => Ok(()),
+    }
+}
+
+/// Keeps the live store from being deleted until the end of the transaction
+fn share_store(conn: &mut PgConnection, store_id: i32) -> Result<(), ApiError> {
+    stores::table
+        .find(store_id)
+        .filter(stores::deleted_at.is_null())
+        .select(stores::id)
+        .for_share()
+        .first::<i32>(conn)
+        .optional()?
+        .ok_or_else(|| ApiError::NotFound(format!("Store {} not found", store_id)))?;
+    Ok(())
+}
+
+/// Bumps the version of the product, its categories belong to it
+fn touch(conn: &mut PgConnection, prod_id: i32) -> QueryResult<usize> {
+    diesel::update(products::table.find(prod_id))
+        .set(products::version.eq(products::version))
+        .execute(conn)
+}
+
+/// Swaps the categories of the product for `category_ids`
+fn replace_categories(
+    conn: &mut PgConnection,
+    prod_id: i32,
+    mut category_ids: Vec<i32>,
+) -> Result<(), ApiError> {
+    category_ids.sort_unstable();
+    category_ids.dedup();
+    share_categories(conn, &category_ids)?;
+    diesel::delete(products_categories::table.filter(products_categories::product_id.eq(prod_id)))
+        .execute(conn)?;
+    let rows: Vec<_> = category_ids
+        .into_iter()
+        .map(|cat_id| {
+            (
+                products_categories::product_id.eq(prod_id),
+                products_categories::category_id.eq(cat_id),
+            )
+        })
+        .collect();
+    diesel::insert_into(products_categories::table)
+        .values(&rows)
+        .execute(conn)?;
+    Ok(())
+}
+
+fn with_categories(conn: &mut PgConnection, product: Product) -> Result<ProductsResult, ApiError> {
+    let categories = ProductsCategories::belonging_to(&product)
+        .inner_join(categories::table)
+        .filter(categories::deleted_at.is_null())
+        .order(categories::id)
+        .load::<(ProductsCategories, Category)>(conn)?;
+    Ok((product, categories).into())
+}
+
+pub async fn delete_product(
+    mut conn: Connection,
+    prod_id: i32,
+    precondition: Precondition,
+    actor: Actor,
+) -> Result<Product, ApiError> {
+    web::block(move || {
+        conn.transaction(|conn| {
+            let current = lock_row(conn, prod_id, &precondition)?;
+            let product = diesel::update(products::table.find(prod_id))
+                .set(products::deleted_at.eq(Utc::now()))
+                .returning(Product::as_returning())
+                .get_result::<Product>(conn)?;
+            let event = NewAuditEvent::new(&actor, "product", prod_id, "delete");
+            record(conn, event.change(Some(&current), Some(&product)))?;
+            Ok(product)
+        })
     })
-    .await;
-    ResultEnum::NotPaginated(result).respond(StatusCode::CREATED)
+    .await?
 }
 
-pub async fn delete_product(mut conn: Connection, prod_id: i32) -> HttpResponse {
-    let result = web::block(move || {
-        diesel::delete(products::table.filter(products::id.eq(prod_id)))
-            .get_result::<Product>(&mut conn)
+/// Brings a deleted product back with its categories, a live one is returned as is
+pub async fn restore_product(
+    mut conn: Connection,
+    prod_id: i32,
+    actor: Actor,
+) -> Result<ProductsResult, ApiError> {
+    web::block(move || {
+        conn.transaction(|conn| {
+            let current = products::table
+                .find(prod_id)
+                .select(Product::as_select())
+                .for_update()
+                .first::<Product>(conn)
+                .optional()?
+                .ok_or_else(|| ApiError::NotFound(format!("Product {} not found", prod_id)))?;
+            if current.deleted_at.is_none() {
+                return with_categories(conn, current);
+            }
+            let product = diesel::update(products::table.find(prod_id))
+                .set(products::deleted_at.eq(None::<DateTime<Utc>>))
+                .returning(Product::as_returning())
+                .get_result::<Product>(conn)?;
+            let event = NewAuditEvent::new(&actor, "product", prod_id, "restore");
+            record(conn, event.change(Some(&current), Some(&product)))?;
+            with_categories(conn, product)
+        })
     })
-    .await;
-    ResultEnum::NotPaginated(result).respond(StatusCode::OK)
+    .await?
 }
 
-pub async fn attach_category(mut conn: Connection, prod_id: i32, cat_id: i32) -> HttpResponse {
-    let result = web::block(move || {
+pub async fn attach_category(
+    mut conn: Connection,
+    prod_id: i32,
+    cat_id: i32,
+    precondition: Precondition,
+    actor: Actor,
+) -> Result<ProductsCategories, ApiError> {
+    web::block(move || {
         // match products::table
         //     .filter(products::columns::id.eq(prod_id))
         //     .get_result::<Product>(&mut conn)
@@ -186,39 +453,76 @@ pub async fn attach_category(mut conn: Connection, prod_id: i32, cat_id: i32) ->
         //         }
         //     }
         //     Err(err) => return Err(err),
-        diesel::insert_into(products_categories::table)
-            .values((
-                products_categories::columns::product_id.eq(prod_id),
-                products_categories::columns::category_id.eq(cat_id),
-            ))
-            .get_result::<ProductsCategories>(&mut conn)
+        conn.transaction(|conn| {
+            lock_row(conn, prod_id, &precondition)?;
+            share_category(conn, cat_id)?;
+            let attached = diesel::insert_into(products_categories::table)
+                .values((
+                    products_categories::columns::product_id.eq(prod_id),
+                    products_categories::columns::category_id.eq(cat_id),
+                ))
+                .get_result::<ProductsCategories>(conn)?;
+            touch(conn, prod_id)?;
+            let event = NewAuditEvent::new(&actor, "product", prod_id, "attach_category");
+            record(
+                conn,
+                event.change(None::<&ProductsCategories>, Some(&attached)),
+            )?;
+            Ok(attached)
+        })
         // }
     })
-    .await;
-    ResultEnum::NotPaginated(result).respond(StatusCode::OK)
+    .await?
 }
 
-pub async fn attach_store(mut conn: Connection, prod_id: i32, store_id: i32) -> HttpResponse {
-    let result = web::block(move || {
-        diesel::update(products::table)
-            .filter(products::id.eq(prod_id))
-            .set(products::columns::store_id.eq(store_id))
-            .get_result::<Product>(&mut conn)
+pub async fn attach_store(
+    mut conn: Connection,
+    prod_id: i32,
+    store_id: i32,
+    precondition: Precondition,
+    actor: Actor,
+) -> Result<Product, ApiError> {
+    web::block(move || {
+        conn.transaction(|conn| {
+            let current = lock_row(conn, prod_id, &precondition)?;
+            share_store(conn, store_id)?;
+            check_same_currency(conn, current.store_id, Some(store_id))?;
+            let product = diesel::update(products::table.find(prod_id))
+                .set(products::columns::store_id.eq(store_id))
+                .returning(Product::as_returning())
+                .get_result::<Product>(conn)?;
+            let event = NewAuditEvent::new(&actor, "product", prod_id, "attach_store");
+            record(conn, event.change(Some(&current), Some(&product)))?;
+            Ok(product)
+        })
     })
-    .await;
-    ResultEnum::NotPaginated(result).respond(StatusCode::OK)
-}
-
-pub async fn dettach_category(mut conn: Connection, prod_id: i32, cat_id: i32) -> HttpResponse {
-    let result = web::block(move || {
-        diesel::delete(
-            products_categories::table
-                .filter(products_categories::columns::product_id.eq(prod_id))
-                .filter(products_categories::columns::category_id.eq(cat_id)),
-        )
-        .get_result::<ProductsCategories>(&mut conn)
-        // }
+    .await?
+}
+
+pub async fn dettach_category(
+    mut conn: Connection,
+    prod_id: i32,
+    cat_id: i32,
+    precondition: Precondition,
+    actor: Actor,
+) -> Result<ProductsCategories, ApiError> {
+    web::block(move || {
+        conn.transaction(|conn| {
+            lock_row(conn, prod_id, &precondition)?;
+            let detached = diesel::delete(
+                products_categories::table
+                    .filter(products_categories::columns::product_id.eq(prod_id))
+                    .filter(products_categories::columns::category_id.eq(cat_id)),
+            )
+            .get_result::<ProductsCategories>(conn)?;
+            touch(conn, prod_id)?;
+            let event = NewAuditEvent::new(&actor, "product", prod_id, "detach_category");
+            record(
+                conn,
+                event.change(Some(&detached), None::<&ProductsCategories>),
+            )?;
+            Ok(detached)
+        })
     })
-    .await;
-    ResultEnum::NotPaginated(result).respond(StatusCode::OK)
+    .await?
 }
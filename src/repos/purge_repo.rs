@@ -0,0 +1,94 @@
+use crate::{
+    models::{Category, NewAuditEvent, Product, Store},
+    repos::audit_repo::record,
+    schema::{categories, products, stores, translations},
+    utils::{Actor, ApiError, Connection},
+};
+use actix_web::web;
+use chrono::{DateTime, Utc};
+use diesel::{prelude::*, Connection as _};
+use serde::Serialize;
+use serde_json::json;
+use utoipa::ToSchema;
+
+/// Rows removed for good, per table
+#[derive(Serialize, ToSchema)]
+pub struct Purged {
+    pub products: usize,
+    pub stores: usize,
+    pub categories: usize,
+}
+
+/// Hard deletes the rows deleted before `before`. Products of a purged store are
+/// detached from it, its worktimes and closures go with it. Every removed row
+/// and every detached product lands in the audit log, their translations are
+/// dropped along
+pub async fn purge_deleted(
+    mut conn: Connection,
+    before: DateTime<Utc>,
+    actor: Actor,
+) -> Result<Purged, ApiError> {
+    web::block(move || {
+        conn.transaction(|conn| {
+            let products = diesel::delete(products::table.filter(products::deleted_at.le(before)))
+                .returning(Product::as_returning())
+                .get_results::<Product>(conn)?;
+            for product in &products {
+                let event = NewAuditEvent::new(&actor, "product", product.id, "purge");
+                record(conn, event.change(Some(product), None::<&Product>))?;
+            }
+            drop_translations(conn, "product", products.iter().map(|p| p.id).collect())?;
+            let purged_stores = stores::table
+                .filter(stores::deleted_at.le(before))
+                .select(stores::id.nullable());
+            let detached = products::table
+                .filter(products::store_id.eq_any(purged_stores))
+                .select((products::id, products::store_id))
+                .for_update()
+                .load::<(i32, Option<i32>)>(conn)?;
+            diesel::update(products::table.filter(products::store_id.eq_any(purged_stores)))
+                .set(products::store_id.eq(None::<i32>))
+                .execute(conn)?;
+            for (id, store_id) in detached {
+                let event = NewAuditEvent::new(&actor, "product", id, "update");
+                record(
+                    conn,
+                    event.change(
+                        Some(&json!({ "store_id": store_id })),
+                        Some(&json!({ "store_id": null })),
+                    ),
+                )?;
+            }
+            let stores = diesel::delete(stores::table.filter(stores::deleted_at.le(before)))
+                .get_results::<Store>(conn)?;
+            for store in &stores {
+                let event = NewAuditEvent::new(&actor, "store", store.id, "purge");
+                record(conn, event.change(Some(store), None::<&Store>))?;
+            }
+            drop_translations(conn, "store", stores.iter().map(|s| s.id).collect())?;
+            let categories =
+                diesel::delete(categories::table.filter(categories::deleted_at.le(before)))
+                    .get_results::<Category>(conn)?;
+            for category in &categories {
+                let event = NewAuditEvent::new(&actor, "category", category.id, "purge");
+                record(conn, event.change(Some(category), None::<&Category>))?;
+            }
+            drop_translations(conn, "category", categories.iter().map(|c| c.id).collect())?;
+            Ok(Purged {
+                products: products.len(),
+                stores: stores.len(),
+                categories: categories.len(),
+            })
+        })
+    })
+    .await?
+}
+
+fn drop_translations(conn: &mut PgConnection, entity: &str, ids: Vec<i32>) -> QueryResult<usize> {
+    diesel::delete(
+        translations::table
+            .filter(translations::entity_type.eq(entity))
+            .filter(translations::entity_id.eq_any(ids)),
+    )
+    .execute(conn)
+}
@@ -0,0 +1,222 @@
+use crate::{
+    models::{ClosureDto, NewAuditEvent, Store, StoreClosure},
+    repos::audit_repo::record,
+    schema::{store_closures, stores},
+    utils::{Actor, ApiError, Connection, Precondition},
+};
+use actix_web::web;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use diesel::{prelude::*, Connection as _};
+
+/// Closures of `stores` that are not over yet, grouped like `stores`. A day of
+/// slack covers the stores whose local date is behind UTC
+pub fn current_closures(
+    conn: &mut PgConnection,
+    stores: &[Store],
+) -> QueryResult<Vec<Vec<StoreClosure>>> {
+    let yesterday = Utc::now().date_naive() - Duration::days(1);
+    Ok(StoreClosure::belonging_to(stores)
+        .filter(store_closures::ends_on.ge(yesterday))
+        .order(store_closures::starts_on)
+        .load::<StoreClosure>(conn)?
+        .grouped_by(stores))
+}
+
+/// Closures that can matter for an opening status at `at`, which looks at the
+/// day before and the week after in the store zone
+pub fn closures_around(
+    conn: &mut PgConnection,
+    store: &Store,
+    at: DateTime<Utc>,
+) -> QueryResult<Vec<StoreClosure>> {
+    let date = at.date_naive();
+    StoreClosure::belonging_to(store)
+        .filter(store_closures::ends_on.ge(date - Duration::days(2)))
+        .filter(store_closures::starts_on.le(date + Duration::days(9)))
+        .order(store_closures::starts_on)
+        .load::<StoreClosure>(conn)
+}
+
+fn find_store(conn: &mut PgConnection, store_id: i32) -> Result<Store, ApiError> {
+    stores::table
+        .find(store_id)
+        .filter(stores::deleted_at.is_null())
+        .first::<Store>(conn)
+        .optional()?
+        .ok_or_else(|| ApiError::NotFound(format!("Store {} not found", store_id)))
+}
+
+/// Locks the live store row until the end of the transaction, so the closures
+/// of a store change one at a time and the overlap check holds. Checks the
+/// store version, closures are part of it
+fn lock_store(
+    conn: &mut PgConnection,
+    store_id: i32,
+    precondition: &Precondition,
+) -> Result<Store, ApiError> {
+    let store = stores::table
+        .find(store_id)
+        .filter(stores::deleted_at.is_null())
+        .for_update()
+        .first::<Store>(conn)
+        .optional()?
+        .ok_or_else(|| ApiError::NotFound(format!("Store {} not found", store_id)))?;
+    precondition.check(&format!("Store {}", store_id), store.version)?;
+    Ok(store)
+}
+
+/// Closures are part of the store representation, changing one bumps its version
+fn touch_store(conn: &mut PgConnection, store_id: i32) -> QueryResult<usize> {
+    diesel::update(stores::table.find(store_id))
+        .set(stores::version.eq(stores::version))
+        .execute(conn)
+}
+
+/// A day can only have one set of hours, so closures of a store cannot overlap
+fn check_overlap(
+    conn: &mut PgConnection,
+    store_id: i32,
+    closure_id: Option<i32>,
+    starts_on: NaiveDate,
+    ends_on: NaiveDate,
+) -> Result<(), ApiError> {
+    let overlapping = store_closures::table
+        .filter(store_closures::store_id.eq(store_id))
+        .filter(store_closures::id.ne(closure_id.unwrap_or(0)))
+        .filter(store_closures::starts_on.le(ends_on))
+        .filter(store_closures::ends_on.ge(starts_on))
+        .select(store_closures::id)
+        .first::<i32>(conn)
+        .optional()?;
+    match overlapping {
+        Some(other) => Err(ApiError::Validation {
+            message: format!("Closure overlaps closure {}", other),
+            fields: vec!["starts_on".to_owned(), "ends_on".to_owned()],
+        }),
+        None => Ok(()),
+    }
+}
+
+pub async fn get_closures(
+    mut conn: Connection,
+    store_id: i32,
+) -> Result<Vec<StoreClosure>, ApiError> {
+    web::block(move || {
+        let store = find_store(&mut conn, store_id)?;
+        StoreClosure::belonging_to(&store)
+            .order(store_closures::starts_on)
+            .load::<StoreClosure>(&mut conn)
+            .map_err(ApiError::from)
+    })
+    .await?
+}
+
+pub async fn get_closure(
+    mut conn: Connection,
+    store_id: i32,
+    closure_id: i32,
+) -> Result<StoreClosure, ApiError> {
+    web::block(move || {
+        find_store(&mut conn, store_id)?;
+        store_closures::table
+            .filter(store_closures::store_id.eq(store_id))
+            .find(closure_id)
+            .first::<StoreClosure>(&mut conn)
+            .optional()?
+            .ok_or_else(|| closure_not_found(store_id, closure_id))
+    })
+    .await?
+}
+
+pub async fn add_closure(
+    mut conn: Connection,
+    store_id: i32,
+    closure: ClosureDto,
+    actor: Actor,
+) -> Result<StoreClosure, ApiError> {
+    web::block(move || {
+        conn.transaction(|conn| {
+            lock_store(conn, store_id, &Precondition::Any)?;
+            check_overlap(conn, store_id, None, closure.starts_on, closure.ends_on)?;
+            touch_store(conn, store_id)?;
+            let added = diesel::insert_into(store_closures::table)
+                .values((store_closures::store_id.eq(store_id), &closure))
+                .get_result::<StoreClosure>(conn)?;
+            let event = NewAuditEvent::new(&actor, "store_closure", added.id, "create");
+            record(conn, event.change(None::<&StoreClosure>, Some(&added)))?;
+            Ok(added)
+        })
+    })
+    .await?
+}
+
+pub async fn update_closure(
+    mut conn: Connection,
+    store_id: i32,
+    closure_id: i32,
+    closure: ClosureDto,
+    precondition: Precondition,
+    actor: Actor,
+) -> Result<StoreClosure, ApiError> {
+    web::block(move || {
+        conn.transaction(|conn| {
+            lock_store(conn, store_id, &precondition)?;
+            let current = store_closures::table
+                .filter(store_closures::store_id.eq(store_id))
+                .find(closure_id)
+                .for_update()
+                .first::<StoreClosure>(conn)
+                .optional()?
+                .ok_or_else(|| closure_not_found(store_id, closure_id))?;
+            check_overlap(
+                conn,
+                store_id,
+                Some(closure_id),
+                closure.starts_on,
+                closure.ends_on,
+            )?;
+            let updated = diesel::update(store_closures::table.find(closure_id))
+                .set(&closure)
+                .get_result::<StoreClosure>(conn)?;
+            touch_store(conn, store_id)?;
+            let event = NewAuditEvent::new(&actor, "store_closure", closure_id, "update");
+            record(conn, event.change(Some(&current), Some(&updated)))?;
+            Ok(updated)
+        })
+    })
+    .await?
+}
+
+pub async fn delete_closure(
+    mut conn: Connection,
+    store_id: i32,
+    closure_id: i32,
+    precondition: Precondition,
+    actor: Actor,
+) -> Result<StoreClosure, ApiError> {
+    web::block(move || {
+        conn.transaction(|conn| {
+            lock_store(conn, store_id, &precondition)?;
+            let deleted = diesel::delete(
+                store_closures::table
+                    .filter(store_closures::store_id.eq(store_id))
+                    .filter(store_closures::id.eq(closure_id)),
+            )
+            .get_result::<StoreClosure>(conn)
+            .optional()?
+            .ok_or_else(|| closure_not_found(store_id, closure_id))?;
+            touch_store(conn, store_id)?;
+            let event = NewAuditEvent::new(&actor, "store_closure", closure_id, "delete");
+            record(conn, event.change(Some(&deleted), None::<&StoreClosure>))?;
+            Ok(deleted)
+        })
+    })
+    .await?
+}
+
+fn closure_not_found(store_id: i32, closure_id: i32) -> ApiError {
+    ApiError::NotFound(format!(
+        "Closure {} not found for store {}",
+        closure_id, store_id
+    ))
+}
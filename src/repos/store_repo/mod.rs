@@ -1,3 +1,6 @@
+mod closure_repo;
+#[allow(clippy::module_inception)]
 mod store_repo;
 
+pub use closure_repo::*;
 pub use store_repo::*;
@@ -1,37 +1,104 @@
 use crate::{
     models::{
-        CanRespond, CreateStoreDto, Product, ResultEnum, Store, StoreResult,
-        StoreResultWithProducts, TransformTo, UpdateStoreDto, Worktimes,
+        schedule::validate_week, CreateStoreDto, NewAuditEvent, Page, PatchStoreDto, Product,
+        Store, StoreResult, StoreResultWithProducts, StoreStatus, TransformTo, Worktimes,
+    },
+    repos::{
+        audit_repo::record,
+        fuzzy,
+        pagination::{Keyset, Paginate, PaginationDto, SortKey},
+        store_repo::{closures_around, current_closures},
     },
-    repos::pagination::{Paginate, PaginationDto},
     routes::{DateFilter, SearchBy, StoresOrderBy, Stringify},
-    schema::{stores, stores::*, worktimes, products},
-    utils::Connection,
+    schema::{products, stores, stores::*, worktimes},
+    utils::{Actor, ApiError, Connection, Precondition},
+};
+use actix_web::web;
+use chrono::{DateTime, Utc};
+use diesel::{
+    dsl::sql,
+    expression::BoxableExpression,
+    pg::Pg,
+    prelude::*,
+    sql_types::{Bool, Integer, Text, Timestamptz},
+    Connection as _, QueryDsl,
 };
-use actix_web::{http::StatusCode, web, HttpResponse};
-use diesel::{delete, dsl::sql, prelude::*, sql_types::Text, QueryDsl};
 use serde::Serialize;
+use serde_json::json;
+
+/// Columns a store cursor can be built on
+pub const STORE_CURSOR_COLUMNS: &[&str] = &["id", "name", "created_at", "prod_count"];
+
+/// Stores open at `at`, read as the wall clock time of each store zone. Same
+/// rules as `schedule::status`: a slot closing before it opens runs into the
+/// next day (`day_id` 1 is Monday) and a closure replaces the worktimes of the
+/// dates it covers
+fn open_at_filter(
+    at: DateTime<Utc>,
+) -> Box<dyn BoxableExpression<stores::table, Pg, SqlType = Bool>> {
+    Box::new(
+        sql::<Bool>(
+            "NOT stores.is_holiday AND EXISTS (SELECT 1 FROM (SELECT CAST(",
+        )
+        .bind::<Timestamptz, _>(at)
+        .sql(
+            " AS TIMESTAMPTZ) AT TIME ZONE stores.timezone AS lt) t \
+            CROSS JOIN (VALUES (0), (1)) back(days) \
+            CROSS JOIN LATERAL (SELECT (t.lt - back.days * INTERVAL '1 day')::DATE AS day) d \
+            CROSS JOIN LATERAL (\
+                SELECT c.am_open, c.am_close, c.pm_open, c.pm_close FROM store_closures c \
+                WHERE c.store_id = stores.id AND d.day BETWEEN c.starts_on AND c.ends_on \
+                UNION ALL \
+                SELECT w.am_open, w.am_close, w.pm_open, w.pm_close FROM worktimes w \
+                WHERE w.store_id = stores.id AND w.day_id = EXTRACT(ISODOW FROM d.day) \
+                AND NOT EXISTS (SELECT 1 FROM store_closures c WHERE c.store_id = stores.id \
+                    AND d.day BETWEEN c.starts_on AND c.ends_on)) h \
+            WHERE (back.days = 0 AND (\
+                (h.am_open <= t.lt::TIME AND (t.lt::TIME < h.am_close OR h.am_close < h.am_open)) \
+                OR (h.pm_open <= t.lt::TIME AND (t.lt::TIME < h.pm_close OR h.pm_close < h.pm_open)))) \
+            OR (back.days = 1 AND (\
+                (h.am_close < h.am_open AND t.lt::TIME < h.am_close) \
+                OR (h.pm_close < h.pm_open AND t.lt::TIME < h.pm_close))))",
+        ),
+    )
+}
 
-pub async fn get_store(mut conn: Connection, shop_id: i32) -> HttpResponse {
-    let result = web::block(move || {
+pub async fn get_store(
+    mut conn: Connection,
+    shop_id: i32,
+) -> Result<StoreResultWithProducts, ApiError> {
+    web::block(move || {
         let store = stores::table
             .find(shop_id)
+            .filter(deleted_at.is_null())
             .first::<Store>(&mut conn)
-            .unwrap();
-        let worktimes = Worktimes::belonging_to(&store).load(&mut conn).unwrap();
-        let products: Vec<Product> = Product::belonging_to(&store).load(&mut conn).unwrap();
+            .optional()?
+            .ok_or_else(|| ApiError::NotFound(format!("Store {} not found", shop_id)))?;
+        let worktimes: Vec<Worktimes> = Worktimes::belonging_to(&store).load(&mut conn)?;
+        let closures = current_closures(&mut conn, std::slice::from_ref(&store))?.remove(0);
+        let products: Vec<Product> = Product::belonging_to(&store)
+            .select(Product::as_select())
+            .filter(products::deleted_at.is_null())
+            .load(&mut conn)?;
+        let status = StoreStatus::new(&store, &worktimes, &closures, Utc::now());
         Ok(StoreResultWithProducts {
             id: store.id,
             name: store.name,
             is_holiday: store.is_holiday,
             created_at: store.created_at,
+            timezone: store.timezone,
+            currency: store.currency,
+            version: store.version,
+            updated_at: store.updated_at,
             worktimes,
+            closures,
+            open_now: status.open,
+            next_change_at: status.next_change_at,
             products,
             prod_count: store.prod_count,
         })
     })
-    .await;
-    ResultEnum::NotPaginated(result).respond(StatusCode::OK)
+    .await?
 }
 
 pub async fn get_many(
@@ -40,127 +107,398 @@ pub async fn get_many(
     order: Option<StoresOrderBy>,
     search_by: SearchBy,
     date: DateFilter,
-) -> HttpResponse {
-    let result = web::block(move || {
-        let results = stores::table
-            .filter(
-                stores::name
-                    .ilike(search_by.get_name())
-                    .and(stores::is_holiday.eq_any(vec![
-                        search_by.get_is_holiday(),
-                        search_by.get_is_holiday_neg(),
-                    ]))
-                    .and(stores::created_at.between(date.get_after(), date.get_before())),
-            )
-            .order(sql::<Text>(&order.stringify()))
-            .paginate(pagination.page)
-            .per_page(pagination.per_page)
-            .load_and_count_pages::<Store>(&mut conn);
-        let data = results.unwrap();
-        let worktimes: Vec<Vec<Worktimes>> = Worktimes::belonging_to(&data.0)
-            .load::<Worktimes>(&mut conn)
-            .unwrap()
-            .grouped_by(&data.0);
-        // let id_indices: HashMap<_, _> = data.0
-        //     .iter()
-        //     .enumerate()
-        //     .map(|(i, u)| (u.id, i))
-        //     .collect();
-        // let mut result = data.0.iter().map(|_| Vec::new()).collect::<Vec<_>>();
-        // for child in worktimes {
-        //     result[id_indices[&child.store_id]].push(child);
-        // }
-        Ok((
-            // data transformation
-            data.0
-                .into_iter()
-                .zip(worktimes)
-                .map(|data: (Store, Vec<Worktimes>)| data.into())
-                .collect::<Vec<StoreResult>>(),
-            data.1,
-            data.2,
-            data.3,
-        ))
-    })
-    .await;
-    ResultEnum::Paginated(result).respond(StatusCode::OK)
-}
-
-pub async fn create_store(mut conn: Connection, store: CreateStoreDto) -> HttpResponse {
-    let result = web::block(move || {
-        let insert_store = diesel::insert_into(stores::table)
-            .values((
-                stores::columns::name.eq(&store.name),
-                stores::columns::is_holiday.eq(store.is_holiday),
-            ))
-            .get_result::<Store>(&mut conn);
-        match insert_store {
-            Ok(insert_store) => {
-                let worktimes = store.transform_to(insert_store.id);
-                diesel::insert_into(worktimes::table)
-                    .values(&worktimes)
-                    .execute(&mut conn)
+    open_at: Option<DateTime<Utc>>,
+) -> Result<Page<Vec<StoreResult>>, ApiError> {
+    web::block(move || {
+        conn.transaction(|conn| {
+            let similar_to = search_by.fuzzy_name().map(str::to_owned);
+            let mut query = stores::table
+                .filter(
+                    stores::name
+                        .ilike(search_by.get_name())
+                        .and(stores::is_holiday.eq_any(vec![
+                            search_by.get_is_holiday(),
+                            search_by.get_is_holiday_neg(),
+                        ]))
+                        .and(stores::created_at.between(date.get_after(), date.get_before())),
+                )
+                .filter(stores::deleted_at.is_null())
+                .into_boxed();
+            if let Some(open_at) = open_at {
+                query = query.filter(open_at_filter(open_at));
             }
-            Err(err) => Err(err),
-        }
+            if let Some(text) = &similar_to {
+                fuzzy::set_threshold(conn)?;
+                query = query.filter(fuzzy::matches("store", text));
+            }
+            // Most similar names first unless another order is asked for, the
+            // similarity cannot back a cursor
+            let similar = similar_to.as_deref().filter(|_| order.is_none());
+            let sorting = match similar {
+                Some(_) => ("similarity", true),
+                None => order.sorting(),
+            };
+            let page = match pagination.cursor_page(sorting, STORE_CURSOR_COLUMNS)? {
+                Some(cursor) => {
+                    let query = match cursor.predicate() {
+                        Some(predicate) => query.filter(predicate),
+                        None => query,
+                    };
+                    Page::Cursor(
+                        cursor.finish(
+                            query
+                                .order(sql::<Text>(&cursor.order()))
+                                .limit(cursor.limit())
+                                .load::<Store>(conn)?,
+                        ),
+                    )
+                }
+                None => {
+                    let query = match similar {
+                        Some(text) => {
+                            query.order((fuzzy::similarity("store", text).desc(), stores::id.asc()))
+                        }
+                        None => query.order(sql::<Text>(&order.stringify())),
+                    };
+                    Page::Offset(
+                        query
+                            .paginate(pagination.page)
+                            .per_page(pagination.per_page)
+                            .load_and_count_pages::<Store>(conn)?,
+                    )
+                }
+            };
+            let worktimes: Vec<Vec<Worktimes>> = Worktimes::belonging_to(page.result())
+                .load::<Worktimes>(conn)?
+                .grouped_by(page.result());
+            let closures = current_closures(conn, page.result())?;
+            let mut similarities = match &similar_to {
+                Some(text) => {
+                    let ids = page.result().iter().map(|store| store.id).collect();
+                    fuzzy::similarities(conn, "store", text, ids)?
+                }
+                None => Default::default(),
+            };
+            Ok(page.map(|stores| {
+                // data transformation
+                stores
+                    .into_iter()
+                    .zip(worktimes)
+                    .zip(closures)
+                    .map(|((store, worktimes), closures)| {
+                        let similarity = similarities.remove(&store.id);
+                        StoreResult {
+                            similarity,
+                            ..(store, worktimes, closures).into()
+                        }
+                    })
+                    .collect::<Vec<StoreResult>>()
+            }))
+        })
     })
-    .await;
-    ResultEnum::NotPaginated(result).respond(StatusCode::OK)
+    .await?
+}
+
+pub async fn create_store(
+    mut conn: Connection,
+    store: CreateStoreDto,
+    actor: Actor,
+) -> Result<StoreResult, ApiError> {
+    web::block(move || {
+        conn.transaction(|conn| {
+            let insert_store = diesel::insert_into(stores::table)
+                .values((
+                    stores::columns::name.eq(&store.name),
+                    stores::columns::is_holiday.eq(store.is_holiday),
+                    stores::columns::timezone.eq(&store.timezone),
+                    stores::columns::currency.eq(&store.currency),
+                ))
+                .get_result::<Store>(conn)?;
+            let worktimes = store.transform_to(insert_store.id);
+            let worktimes = diesel::insert_into(worktimes::table)
+                .values(&worktimes)
+                .get_results::<Worktimes>(conn)?;
+            let event = NewAuditEvent::new(&actor, "store", insert_store.id, "create");
+            let snapshot = StoreSnapshot::new(&insert_store, &worktimes);
+            record(conn, event.change(None::<&StoreSnapshot>, Some(&snapshot)))?;
+            Ok((insert_store, worktimes, vec![]).into())
+        })
+    })
+    .await?
+}
+
+/// Product prices are amounts in the store currency, which cannot change under
+/// them. Trashed products count, they can be restored
+fn check_unpriced(conn: &mut PgConnection, store: &Store) -> Result<(), ApiError> {
+    let priced = products::table
+        .filter(products::store_id.eq(store.id))
+        .select(products::id)
+        .first::<i32>(conn)
+        .optional()?;
+    match priced {
+        Some(_) => Err(ApiError::Validation {
+            message: format!(
+                "Store {} has products priced in {}",
+                store.id, store.currency
+            ),
+            fields: vec!["currency".to_owned()],
+        }),
+        None => Ok(()),
+    }
 }
 
 pub async fn update_store(
     mut conn: Connection,
     store_id: i32,
-    store: UpdateStoreDto,
-) -> HttpResponse {
-    let result = web::block(move || {
-        match diesel::update(stores::table)
-            .filter(id.eq(store_id))
-            .set((name.eq(&store.name), is_holiday.eq(&store.is_holiday)))
-            .get_result::<Store>(&mut conn)
-        {
-            Ok(val) => {
-                // NOTE: Batch update not yet supported by diesel hence the loop
-                for worktime in store.worktimes {
-                    match diesel::update(worktimes::table)
-                        .filter(worktimes::columns::id.eq(worktime.id))
-                        .set(&worktime)
-                        .get_result::<Worktimes>(&mut conn)
-                    {
-                        Ok(_) => (),
-                        Err(err) => return Err(err),
-                    }
+    store: PatchStoreDto,
+    precondition: Precondition,
+    actor: Actor,
+) -> Result<StoreResult, ApiError> {
+    web::block(move || {
+        conn.transaction(|conn| {
+            let current = lock_row(conn, store_id, &precondition)?;
+            if matches!(&store.currency, Some(code) if *code != current.currency) {
+                check_unpriced(conn, &current)?;
+            }
+            let current_worktimes = Worktimes::belonging_to(&current)
+                .order(worktimes::day_id)
+                .load::<Worktimes>(conn)?;
+            let changes = (
+                store.name.as_ref().map(|store_name| name.eq(store_name)),
+                store.is_holiday.map(|holiday| is_holiday.eq(holiday)),
+                store.timezone.as_ref().map(|tz| timezone.eq(tz)),
+                store.currency.as_ref().map(|code| currency.eq(code)),
+            );
+            let updated = match (changes, &store.worktimes) {
+                ((None, None, None, None), None) => {
+                    let closures =
+                        current_closures(conn, std::slice::from_ref(&current))?.remove(0);
+                    return Ok((current, current_worktimes, closures).into());
                 }
-                Ok(val)
+                // Worktimes belong to the store version, the row is touched anyway
+                (changes, _) => diesel::update(stores::table.find(store_id))
+                    .set((changes, version.eq(version)))
+                    .get_result::<Store>(conn)?,
+            };
+            // NOTE: Batch update not yet supported by diesel hence the loop
+            for worktime in store.worktimes.unwrap_or_default() {
+                diesel::update(worktimes::table)
+                    .filter(worktimes::columns::id.eq(worktime.id))
+                    .filter(worktimes::columns::store_id.eq(store_id))
+                    .set(&worktime)
+                    .get_result::<Worktimes>(conn)
+                    .optional()?
+                    .ok_or_else(|| {
+                        ApiError::NotFound(format!(
+                            "Worktime {} not found for store {}",
+                            worktime.id, store_id
+                        ))
+                    })?;
             }
-            Err(err) => Err(err),
-        }
+            let worktimes = Worktimes::belonging_to(&updated)
+                .order(worktimes::columns::day_id)
+                .load::<Worktimes>(conn)?;
+            validate_week(&worktimes).map_err(|err| ApiError::Validation {
+                message: err.code.into_owned(),
+                fields: vec!["worktimes".to_owned()],
+            })?;
+            let event = NewAuditEvent::new(&actor, "store", store_id, "update");
+            record(
+                conn,
+                event.change(
+                    Some(&StoreSnapshot::new(&current, &current_worktimes)),
+                    Some(&StoreSnapshot::new(&updated, &worktimes)),
+                ),
+            )?;
+            let closures = current_closures(conn, std::slice::from_ref(&updated))?.remove(0);
+            Ok((updated, worktimes, closures).into())
+        })
     })
-    .await;
-    ResultEnum::NotPaginated(result).respond(StatusCode::OK)
+    .await?
 }
-pub async fn delete_store(mut conn: Connection, shop_id: i32) -> HttpResponse {
-    let result = web::block(move || {
-        delete(stores::table.filter(stores::id.eq(shop_id))).get_result::<Store>(&mut conn)
+
+pub async fn delete_store(
+    mut conn: Connection,
+    shop_id: i32,
+    precondition: Precondition,
+    actor: Actor,
+) -> Result<Store, ApiError> {
+    web::block(move || {
+        conn.transaction(|conn| {
+            let current = lock_row(conn, shop_id, &precondition)?;
+            let store = diesel::update(stores::table.find(shop_id))
+                .set(deleted_at.eq(Utc::now()))
+                .get_result::<Store>(conn)?;
+            let event = NewAuditEvent::new(&actor, "store", shop_id, "delete");
+            record(conn, event.change(Some(&current), Some(&store)))?;
+            Ok(store)
+        })
+    })
+    .await?
+}
+
+/// Brings a deleted store back, its worktimes and closures were kept
+pub async fn restore_store(
+    mut conn: Connection,
+    store_id: i32,
+    actor: Actor,
+) -> Result<StoreResult, ApiError> {
+    web::block(move || {
+        conn.transaction(|conn| {
+            let current = stores::table
+                .find(store_id)
+                .for_update()
+                .first::<Store>(conn)
+                .optional()?
+                .ok_or_else(|| ApiError::NotFound(format!("Store {} not found", store_id)))?;
+            let store = match current.deleted_at {
+                Some(_) => {
+                    let store = diesel::update(stores::table.find(store_id))
+                        .set(deleted_at.eq(None::<DateTime<Utc>>))
+                        .get_result::<Store>(conn)?;
+                    let event = NewAuditEvent::new(&actor, "store", store_id, "restore");
+                    record(conn, event.change(Some(&current), Some(&store)))?;
+                    store
+                }
+                None => current,
+            };
+            let worktimes = Worktimes::belonging_to(&store)
+                .order(worktimes::day_id)
+                .load::<Worktimes>(conn)?;
+            let closures = current_closures(conn, std::slice::from_ref(&store))?.remove(0);
+            Ok((store, worktimes, closures).into())
+        })
     })
-    .await;
-    ResultEnum::NotPaginated(result).respond(StatusCode::OK)
+    .await?
+}
+
+/// Locks the live store row until the end of the transaction, checks its
+/// version and returns it
+fn lock_row(
+    conn: &mut PgConnection,
+    store_id: i32,
+    precondition: &Precondition,
+) -> Result<Store, ApiError> {
+    let store = stores::table
+        .find(store_id)
+        .filter(deleted_at.is_null())
+        .for_update()
+        .first::<Store>(conn)
+        .optional()?
+        .ok_or_else(|| ApiError::NotFound(format!("Store {} not found", store_id)))?;
+    precondition.check(&format!("Store {}", store_id), store.version)?;
+    Ok(store)
+}
+
+/// What the audit log keeps of a store, worktimes included
+#[derive(Serialize)]
+struct StoreSnapshot<'a> {
+    #[serde(flatten)]
+    store: &'a Store,
+    worktimes: &'a [Worktimes],
+}
+
+impl<'a> StoreSnapshot<'a> {
+    fn new(store: &'a Store, worktimes: &'a [Worktimes]) -> Self {
+        StoreSnapshot { store, worktimes }
+    }
 }
 
-pub async fn product_count(mut conn: Connection, store_id: i32) -> HttpResponse {
-    let result = web::block(move || {
+pub async fn product_count(mut conn: Connection, store_id: i32) -> Result<Count, ApiError> {
+    web::block(move || {
+        stores::table
+            .find(store_id)
+            .filter(stores::deleted_at.is_null())
+            .select(stores::id)
+            .first::<i32>(&mut conn)
+            .optional()?
+            .ok_or_else(|| ApiError::NotFound(format!("Store {} not found", store_id)))?;
         let count = products::table
             .filter(products::store_id.eq(store_id))
+            .filter(products::deleted_at.is_null())
             .count()
-            .get_result(&mut conn);
-        Ok(Count {
-            count: count.unwrap()
+            .get_result(&mut conn)?;
+        Ok(Count { count })
+    })
+    .await?
+}
+
+pub async fn store_status(
+    mut conn: Connection,
+    store_id: i32,
+    at: DateTime<Utc>,
+) -> Result<StoreStatus, ApiError> {
+    web::block(move || {
+        let store = stores::table
+            .find(store_id)
+            .filter(stores::deleted_at.is_null())
+            .first::<Store>(&mut conn)
+            .optional()?
+            .ok_or_else(|| ApiError::NotFound(format!("Store {} not found", store_id)))?;
+        let worktimes = Worktimes::belonging_to(&store).load::<Worktimes>(&mut conn)?;
+        let closures = closures_around(&mut conn, &store, at)?;
+        Ok(StoreStatus::new(&store, &worktimes, &closures, at))
+    })
+    .await?
+}
+
+pub async fn recount_products(mut conn: Connection, actor: Actor) -> Result<Count, ApiError> {
+    web::block(move || {
+        conn.transaction(|conn| {
+            let recounted = diesel::sql_query(
+                "UPDATE stores SET prod_count = counts.count \
+                 FROM (SELECT stores.id, stores.prod_count AS before, \
+                       COUNT(products.id)::INT AS count FROM stores \
+                       LEFT JOIN products ON products.store_id = stores.id \
+                       AND products.deleted_at IS NULL GROUP BY stores.id) counts \
+                 WHERE stores.id = counts.id AND stores.prod_count <> counts.count \
+                 RETURNING stores.id AS store_id, counts.before, counts.count",
+            )
+            .load::<Recounted>(conn)?;
+            for store in &recounted {
+                let event = NewAuditEvent::new(&actor, "store", store.store_id, "recount");
+                record(
+                    conn,
+                    event.change(
+                        Some(&json!({ "prod_count": store.before })),
+                        Some(&json!({ "prod_count": store.count })),
+                    ),
+                )?;
+            }
+            Ok(Count {
+                count: recounted.len() as i64,
+            })
         })
-    }).await;
-    ResultEnum::NotPaginated(result).respond(StatusCode::OK)
+    })
+    .await?
+}
+
+#[derive(QueryableByName)]
+struct Recounted {
+    #[diesel(sql_type = Integer)]
+    store_id: i32,
+    #[diesel(sql_type = Integer)]
+    before: i32,
+    #[diesel(sql_type = Integer)]
+    count: i32,
 }
 
 #[derive(Serialize)]
-struct Count {
+pub struct Count {
     count: i64,
 }
+
+impl Keyset for Store {
+    fn id(&self) -> i32 {
+        self.id
+    }
+
+    fn sort_key(&self, by: &str) -> SortKey {
+        match by {
+            "id" => SortKey::Int(self.id),
+            "name" => SortKey::Text(self.name.clone()),
+            "prod_count" => SortKey::Int(self.prod_count),
+            _ => SortKey::Timestamptz(self.created_at),
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use crate::{
+    models::Suggestion,
+    utils::{ApiError, Connection},
+};
+use actix_web::web;
+use diesel::{
+    prelude::*,
+    sql_types::{BigInt, Text},
+};
+
+/// `LIKE` pattern of the names starting with `text`, its wildcards taken literally
+fn prefix_pattern(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("{}%", escaped)
+}
+
+/// Live products, categories and stores whose name starts with `text`, accents
+/// and case aside. An exact name comes first, then the shortest ones
+pub async fn suggest(
+    mut conn: Connection,
+    text: String,
+    limit: i64,
+) -> Result<Vec<Suggestion>, ApiError> {
+    web::block(move || {
+        diesel::sql_query(
+            "SELECT entity_type, id, name FROM ( \
+                SELECT 'product' AS entity_type, id, name FROM products \
+                WHERE deleted_at IS NULL AND fuzzy_text(name) LIKE fuzzy_text($1) \
+                UNION ALL \
+                SELECT 'category', id, name FROM categories \
+                WHERE deleted_at IS NULL AND fuzzy_text(name) LIKE fuzzy_text($1) \
+                UNION ALL \
+                SELECT 'store', id, name FROM stores \
+                WHERE deleted_at IS NULL AND fuzzy_text(name) LIKE fuzzy_text($1) \
+             ) matches \
+             ORDER BY fuzzy_text(name) = fuzzy_text($2) DESC, char_length(name), name, \
+                entity_type, id \
+             LIMIT $3",
+        )
+        .bind::<Text, _>(prefix_pattern(&text))
+        .bind::<Text, _>(text)
+        .bind::<BigInt, _>(limit)
+        .load::<Suggestion>(&mut conn)
+        .map_err(ApiError::from)
+    })
+    .await?
+}
@@ -0,0 +1,303 @@
+use crate::{
+    models::{
+        max_length, normalize_locale, Localize, Localized, NewAuditEvent, Page, Translation,
+        TranslationDto, UpdateTranslationDto,
+    },
+    repos::{
+        audit_repo::record,
+        pagination::{Keyset, Paginate, PaginationDto, SortKey},
+    },
+    routes::TranslationFilter,
+    schema::{categories, products, stores, translations},
+    utils::{AcceptedLocales, Actor, ApiError, Connection, Precondition, DEFAULT_LOCALE},
+};
+use actix_web::web;
+use chrono::Utc;
+use diesel::{dsl::sql, prelude::*, sql_types::Text, Connection as _};
+use std::collections::{BTreeSet, HashMap};
+
+/// Columns a translation cursor can be built on
+pub const TRANSLATION_CURSOR_COLUMNS: &[&str] = &["id"];
+
+fn translation_not_found(translation_id: i32) -> ApiError {
+    ApiError::NotFound(format!("Translation {} not found", translation_id))
+}
+
+/// Bumps the version of the translated entity, a translation changes its
+/// representation and the product search vector is refreshed by the update.
+/// With `live`, entities in the trash are left out. Returns whether the entity
+/// was found
+fn touch(conn: &mut PgConnection, entity: &str, id: i32, live: bool) -> QueryResult<bool> {
+    let touched = match (entity, live) {
+        ("product", true) => diesel::update(products::table.find(id))
+            .filter(products::deleted_at.is_null())
+            .set(products::version.eq(products::version))
+            .execute(conn)?,
+        ("product", false) => diesel::update(products::table.find(id))
+            .set(products::version.eq(products::version))
+            .execute(conn)?,
+        ("category", true) => diesel::update(categories::table.find(id))
+            .filter(categories::deleted_at.is_null())
+            .set(categories::version.eq(categories::version))
+            .execute(conn)?,
+        ("category", false) => diesel::update(categories::table.find(id))
+            .set(categories::version.eq(categories::version))
+            .execute(conn)?,
+        ("store", true) => diesel::update(stores::table.find(id))
+            .filter(stores::deleted_at.is_null())
+            .set(stores::version.eq(stores::version))
+            .execute(conn)?,
+        ("store", false) => diesel::update(stores::table.find(id))
+            .set(stores::version.eq(stores::version))
+            .execute(conn)?,
+        _ => 0,
+    };
+    Ok(touched > 0)
+}
+
+pub async fn get_many(
+    mut conn: Connection,
+    pagination: PaginationDto,
+    filter: TranslationFilter,
+) -> Result<Page<Vec<Translation>>, ApiError> {
+    web::block(move || {
+        let mut query = translations::table.into_boxed();
+        if let Some(entity) = filter.entity {
+            query = query.filter(translations::entity_type.eq(entity));
+        }
+        if let Some(entity_id) = filter.entity_id {
+            query = query.filter(translations::entity_id.eq(entity_id));
+        }
+        if let Some(locale) = filter.locale {
+            query = query.filter(translations::locale.eq(normalize_locale(&locale)));
+        }
+        match pagination.cursor_page(("id", false), TRANSLATION_CURSOR_COLUMNS)? {
+            Some(cursor) => {
+                let query = match cursor.predicate() {
+                    Some(predicate) => query.filter(predicate),
+                    None => query,
+                };
+                let rows = query
+                    .order(sql::<Text>(&cursor.order()))
+                    .limit(cursor.limit())
+                    .load::<Translation>(&mut conn)?;
+                Ok(Page::Cursor(cursor.finish(rows)))
+            }
+            None => query
+                .order(translations::id)
+                .paginate(pagination.page)
+                .per_page(pagination.per_page)
+                .load_and_count_pages::<Translation>(&mut conn)
+                .map(Page::Offset)
+                .map_err(ApiError::from),
+        }
+    })
+    .await?
+}
+
+pub async fn get_translation(
+    mut conn: Connection,
+    translation_id: i32,
+) -> Result<Translation, ApiError> {
+    web::block(move || {
+        translations::table
+            .find(translation_id)
+            .first::<Translation>(&mut conn)
+            .optional()?
+            .ok_or_else(|| translation_not_found(translation_id))
+    })
+    .await?
+}
+
+/// The translated entity has to exist and be out of the trash
+/// Locks the translated entity, trashed or not, until the end of the
+/// transaction and checks its version, which the translation is part of
+fn lock_entity(
+    conn: &mut PgConnection,
+    entity: &str,
+    id: i32,
+    precondition: &Precondition,
+) -> Result<(), ApiError> {
+    let (resource, version) = match entity {
+        "product" => (
+            "Product",
+            products::table
+                .find(id)
+                .select(products::version)
+                .for_update()
+                .first::<i32>(conn)
+                .optional()?,
+        ),
+        "category" => (
+            "Category",
+            categories::table
+                .find(id)
+                .select(categories::version)
+                .for_update()
+                .first::<i32>(conn)
+                .optional()?,
+        ),
+        "store" => (
+            "Store",
+            stores::table
+                .find(id)
+                .select(stores::version)
+                .for_update()
+                .first::<i32>(conn)
+                .optional()?,
+        ),
+        _ => (entity, None),
+    };
+    match version {
+        Some(version) => precondition.check(&format!("{} {}", resource, id), version),
+        None => Ok(()),
+    }
+}
+
+pub async fn add_translation(
+    mut conn: Connection,
+    mut translation: TranslationDto,
+    actor: Actor,
+) -> Result<Translation, ApiError> {
+    translation.locale = normalize_locale(&translation.locale);
+    web::block(move || {
+        conn.transaction(|conn| {
+            let added = diesel::insert_into(translations::table)
+                .values(&translation)
+                .get_result::<Translation>(conn)?;
+            if !touch(conn, &added.entity_type, added.entity_id, true)? {
+                return Err(ApiError::NotFound(format!(
+                    "No {} {} to translate",
+                    added.entity_type, added.entity_id
+                )));
+            }
+            let event = NewAuditEvent::new(&actor, "translation", added.id, "create");
+            record(conn, event.change(None::<&Translation>, Some(&added)))?;
+            Ok(added)
+        })
+    })
+    .await?
+}
+
+pub async fn update_translation(
+    mut conn: Connection,
+    translation_id: i32,
+    translation: UpdateTranslationDto,
+    precondition: Precondition,
+    actor: Actor,
+) -> Result<Translation, ApiError> {
+    web::block(move || {
+        conn.transaction(|conn| {
+            let current = translations::table
+                .find(translation_id)
+                .for_update()
+                .first::<Translation>(conn)
+                .optional()?
+                .ok_or_else(|| translation_not_found(translation_id))?;
+            lock_entity(conn, &current.entity_type, current.entity_id, &precondition)?;
+            let max = max_length(&current.entity_type, &current.field).unwrap_or(usize::MAX);
+            if translation.value.chars().count() > max {
+                return Err(ApiError::Validation {
+                    message: format!("{} takes at most {} characters", current.field, max),
+                    fields: vec!["value".to_owned()],
+                });
+            }
+            let updated = diesel::update(translations::table.find(translation_id))
+                .set((&translation, translations::updated_at.eq(Utc::now())))
+                .get_result::<Translation>(conn)?;
+            touch(conn, &current.entity_type, current.entity_id, false)?;
+            let event = NewAuditEvent::new(&actor, "translation", translation_id, "update");
+            record(conn, event.change(Some(&current), Some(&updated)))?;
+            Ok(updated)
+        })
+    })
+    .await?
+}
+
+pub async fn delete_translation(
+    mut conn: Connection,
+    translation_id: i32,
+    precondition: Precondition,
+    actor: Actor,
+) -> Result<Translation, ApiError> {
+    web::block(move || {
+        conn.transaction(|conn| {
+            let current = translations::table
+                .find(translation_id)
+                .for_update()
+                .first::<Translation>(conn)
+                .optional()?
+                .ok_or_else(|| translation_not_found(translation_id))?;
+            lock_entity(conn, &current.entity_type, current.entity_id, &precondition)?;
+            let deleted = diesel::delete(translations::table.find(translation_id))
+                .get_result::<Translation>(conn)?;
+            touch(conn, &deleted.entity_type, deleted.entity_id, false)?;
+            let event = NewAuditEvent::new(&actor, "translation", translation_id, "delete");
+            record(conn, event.change(Some(&deleted), None::<&Translation>))?;
+            Ok(deleted)
+        })
+    })
+    .await?
+}
+
+/// Puts `value` in the locale best matching `accepted`, picked per entity among
+/// the locales it has translations in. Also returns the locales the text ended
+/// up in, for `Content-Language`
+pub async fn localize<T: Localize + Send + 'static>(
+    mut conn: Connection,
+    mut value: T,
+    accepted: AcceptedLocales,
+) -> Result<(T, Vec<String>), ApiError> {
+    let entities: BTreeSet<(&str, i32)> = value.entities().into_iter().collect();
+    if accepted.is_empty() || entities.is_empty() {
+        return Ok((value, vec![DEFAULT_LOCALE.to_owned()]));
+    }
+    web::block(move || {
+        let types: BTreeSet<&str> = entities.iter().map(|(entity, _)| *entity).collect();
+        let ids: BTreeSet<i32> = entities.iter().map(|(_, id)| *id).collect();
+        let rows = translations::table
+            .filter(translations::entity_type.eq_any(types))
+            .filter(translations::entity_id.eq_any(ids))
+            .load::<Translation>(&mut conn)?;
+        let mut by_entity: HashMap<(String, i32), Vec<Translation>> = HashMap::new();
+        for row in rows {
+            by_entity
+                .entry((row.entity_type.clone(), row.entity_id))
+                .or_default()
+                .push(row);
+        }
+        let mut localized = Localized::default();
+        let mut locales = BTreeSet::new();
+        for (entity, id) in entities {
+            let rows = by_entity
+                .remove(&(entity.to_owned(), id))
+                .unwrap_or_default();
+            let available: BTreeSet<&str> = rows.iter().map(|row| row.locale.as_str()).collect();
+            let available: Vec<&str> = available.into_iter().collect();
+            match accepted.negotiate(&available).map(str::to_owned) {
+                Some(locale) => {
+                    rows.into_iter()
+                        .filter(|row| row.locale == locale)
+                        .for_each(|row| localized.insert(row));
+                    locales.insert(locale);
+                }
+                None => {
+                    locales.insert(DEFAULT_LOCALE.to_owned());
+                }
+            }
+        }
+        value.localize(&localized);
+        Ok((value, locales.into_iter().collect()))
+    })
+    .await?
+}
+
+impl Keyset for Translation {
+    fn id(&self) -> i32 {
+        self.id
+    }
+
+    fn sort_key(&self, _by: &str) -> SortKey {
+        SortKey::Int(self.id)
+    }
+}
@@ -0,0 +1,54 @@
+use crate::{
+    models::QResult,
+    repos::purge_repo::{self, Purged},
+    utils::{json_error_handler, Actor, ApiError, AppData},
+};
+use actix_web::{
+    post,
+    web::{Data, ServiceConfig},
+    HttpResponse,
+};
+use actix_web_validator::{Query, QueryConfig};
+use chrono::{DateTime, FixedOffset, Utc};
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+
+/// Purges the rows deleted before that instant, all deleted rows when absent
+#[derive(Deserialize, Validate, Debug, ToSchema, IntoParams)]
+pub struct PurgeBefore {
+    #[schema(example = json!(Utc::now()))]
+    pub before: Option<DateTime<FixedOffset>>,
+}
+
+/// Permanently removes deleted products, stores and categories
+#[utoipa::path(
+    post,
+    path = "/admin/purge",
+    params(PurgeBefore),
+    responses(
+        (status = 200, description = "Number of rows removed per table", body = QResult<Purged>, example = json!(QResult {
+            rows: Purged { products: 3, stores: 1, categories: 0 },
+            error: None
+        })),
+    )
+)]
+#[post("purge")]
+async fn purge(
+    app_data: Data<AppData>,
+    before: Query<PurgeBefore>,
+    actor: Actor,
+) -> Result<HttpResponse, ApiError> {
+    let conn = app_data.pg_pool.get()?;
+    let before = before
+        .into_inner()
+        .before
+        .map_or_else(Utc::now, |before| before.with_timezone(&Utc));
+    let purged = purge_repo::purge_deleted(conn, before, actor).await?;
+    Ok(HttpResponse::Ok().json(QResult::new(purged, None)))
+}
+
+pub fn init_admin_routes(cfg: &mut ServiceConfig) {
+    cfg.app_data(QueryConfig::default().error_handler(json_error_handler));
+    cfg.service(purge);
+}
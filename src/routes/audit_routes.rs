@@ -0,0 +1,93 @@
+use crate::{
+    models::{AuditEvent, PaginatedResult, AUDITED_ENTITIES},
+    repos::{audit_repo, pagination::PaginationDto},
+    routes::DateFilter,
+    utils::{json_error_handler, paginated_response, ApiError, AppData},
+};
+use actix_web::{
+    get,
+    web::{Data, ServiceConfig},
+    HttpRequest, HttpResponse,
+};
+use actix_web_validator::{Query, QueryConfig};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::json;
+use utoipa::{IntoParams, ToSchema};
+use validator::{Validate, ValidationError};
+
+/// Narrows the log down to one entity type, one entity or one actor
+#[derive(Deserialize, Validate, Debug, ToSchema, IntoParams)]
+pub struct AuditFilter {
+    #[validate(custom = "validate_entity")]
+    #[schema(example = "product")]
+    pub entity: Option<String>,
+    #[validate(range(min = 1))]
+    #[schema(example = 1)]
+    pub entity_id: Option<i32>,
+    #[validate(length(min = 1, max = 256))]
+    #[schema(example = "anonymous")]
+    pub actor: Option<String>,
+}
+
+fn validate_entity(entity: &str) -> Result<(), ValidationError> {
+    if AUDITED_ENTITIES.contains(&entity) {
+        return Ok(());
+    }
+    Err(ValidationError::new("unknown entity"))
+}
+
+/// Lists the recorded writes, newest first
+///
+/// `after`/`before` bound the time of the write. Writes are attributed to the
+/// `X-Actor` request header, `anonymous` when it is missing
+#[utoipa::path(
+    get,
+    path = "/audit",
+    params(
+        PaginationDto,
+        AuditFilter,
+        DateFilter,
+    ),
+    responses(
+        (status = 200, description = "Returns a page of audit events", body = PaginatedResult<AuditEvent>, headers(("link" = String, description = "RFC 8288 first/prev/next/last links")), example = json!(PaginatedResult {
+            per_page: 10,
+            page: 1,
+            total: 1,
+            total_pages: 1,
+            result: vec![AuditEvent {
+                id: 1,
+                entity_type: "product".to_owned(),
+                entity_id: 1,
+                action: "update".to_owned(),
+                actor: "anonymous".to_owned(),
+                created_at: Utc::now(),
+                before: Some(json!({"price": "3.50", "version": 1})),
+                after: Some(json!({"price": "4.25", "version": 2})),
+            }]
+        })),
+    )
+)]
+#[get("")]
+async fn get_many(
+    app_data: Data<AppData>,
+    req: HttpRequest,
+    pagination: Query<PaginationDto>,
+    filter: Query<AuditFilter>,
+    date: Query<DateFilter>,
+) -> Result<HttpResponse, ApiError> {
+    let conn = app_data.pg_pool.get()?;
+    let events = audit_repo::get_many(
+        conn,
+        pagination.into_inner().limited(app_data.max_per_page)?,
+        filter.into_inner(),
+        date.into_inner(),
+    )
+    .await?;
+    Ok(paginated_response(&req, events))
+}
+
+pub fn init_audit_routes(cfg: &mut ServiceConfig) {
+    cfg.app_data(QueryConfig::default().error_handler(json_error_handler));
+    cfg.service(get_many);
+}
@@ -1,11 +1,15 @@
 use crate::{
-    models::{CategoryDto, UpdateCategoryDto, Category, PaginatedResult, QResult},
-    repos::{category_repo, pagination::PaginationDto},
+    models::{
+        Category, CategoryDto, CategoryResult, PaginatedResult, PatchCategoryDto, QResult, UpdateCategoryDto,
+    },
+    repos::{category_repo, pagination::PaginationDto, translation_repo},
     routes::OrderBy,
-    utils::{json_error_handler, AppData},
-    
+    utils::{
+        content_language, etag, json_error_handler, paginated_response, versioned_response,
+        AcceptedLocales, Actor, ApiError, AppData, Precondition, DEFAULT_LOCALE,
+    },
 };
-use actix_web::{delete, get, post, put, web, HttpResponse};
+use actix_web::{delete, get, patch, post, put, web, HttpRequest, HttpResponse};
 use actix_web_validator::{Json, JsonConfig, Query, QueryConfig};
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
@@ -20,28 +24,50 @@ pub struct ManyIdsDto {
 }
 
 /// Fetches category with corresponding ID
+///
+/// The name comes in the locale best matching `Accept-Language`, a translated
+/// category is sent without `ETag`
 #[utoipa::path(
     get, 
     path = "/category/{id}",
     params(
-        ("id", description = "Unique id of categories")
+        ("id", description = "Unique id of categories"),
+        ("If-None-Match" = Option<String>, Header, description = "ETag of a cached copy"),
+        ("Accept-Language" = Option<String>, Header, description = "Preferred locales, the default one without")
     ),
     responses(
-        (status = 200, description = "Returns the category with the id", body = QResult<Category>, example = json!(QResult {
-            rows: Category { id: 1, name: "My category".to_owned(), created_at: Utc::now().naive_utc()},
+        (status = 200, description = "Returns the category with the id", body = QResult<Category>, headers(("etag" = String, description = "Version of the category"), ("content-language" = String, description = "Locales of the text")), example = json!(QResult {
+            rows: Category { id: 1, name: "My category".to_owned(), created_at: Utc::now().naive_utc(), version: 1, updated_at: Utc::now(), deleted_at: None},
             error: None
         })),
+        (status = 304, description = "The cached copy is current"),
+        (status = 404, description = "Category not found", body = ErrorJsonPayload),
     )
 )]
 #[get("{id}")]
-async fn get(app_data: web::Data<AppData>, id: web::Path<i32>) -> HttpResponse {
-    match app_data.pg_pool.get() {
-        Ok(conn) => category_repo::get_category(conn, id.into_inner()).await,
-        _ => HttpResponse::InternalServerError().body("Internal Server Error"),
-    }
+async fn get(
+    req: HttpRequest,
+    app_data: web::Data<AppData>,
+    id: web::Path<i32>,
+    locales: AcceptedLocales,
+) -> Result<HttpResponse, ApiError> {
+    let conn = app_data.pg_pool.get()?;
+    let category = category_repo::get_category(conn, id.into_inner()).await?;
+    let conn = app_data.pg_pool.get()?;
+    let (category, locales) = translation_repo::localize(conn, category, locales).await?;
+    let response = match locales == [DEFAULT_LOCALE] {
+        true => versioned_response(&req, category.version, QResult::new(category, None)),
+        false => HttpResponse::Ok().json(QResult::new(category, None)),
+    };
+    Ok(content_language(response, &locales))
 }
 
 /// Fetches categories with corresponding ID
+///
+/// A `cursor` (empty for the first page) walks the categories by keyset instead.
+/// Names are translated after `Accept-Language`. With `fuzzy=true` the `name`
+/// may be misspelled, each category then gets its `similarity` and the closest
+/// come first
 #[utoipa::path(
     get, 
     path = "/category",
@@ -49,15 +75,17 @@ async fn get(app_data: web::Data<AppData>, id: web::Path<i32>) -> HttpResponse {
         PaginationDto,
         OrderBy,
         SearchBy,
+        ("Accept-Language" = Option<String>, Header, description = "Preferred locales, the default one without"),
     ),
     responses(
-        (status = 200, description = "Returns a list of categories", body = PaginatedResult<Category>, example = json!(PaginatedResult {
+        (status = 200, description = "Returns a list of categories", body = PaginatedResult<CategoryResult>, headers(("link" = String, description = "RFC 8288 first/prev/next/last links"), ("content-language" = String, description = "Locales of the text")), example = json!(PaginatedResult {
             per_page: 10,
             page: 1,
+            total: 2,
             total_pages: 1,
             result: vec![
-                Category {id: 1, name: "Category 1".to_owned(), created_at: Utc::now().naive_utc()}, 
-                Category {id: 2, name: "Category 2".to_owned(), created_at: Utc::now().naive_utc()}
+                CategoryResult {category: Category {id: 1, name: "Category 1".to_owned(), created_at: Utc::now().naive_utc(), version: 1, updated_at: Utc::now(), deleted_at: None}, similarity: None}, 
+                CategoryResult {category: Category {id: 2, name: "Category 2".to_owned(), created_at: Utc::now().naive_utc(), version: 1, updated_at: Utc::now(), deleted_at: None}, similarity: None}
             ]
         })),
     )
@@ -65,22 +93,23 @@ async fn get(app_data: web::Data<AppData>, id: web::Path<i32>) -> HttpResponse {
 #[get("")]
 async fn get_many(
     app_data: web::Data<AppData>,
+    req: HttpRequest,
     pagination: Query<PaginationDto>,
     order: Query<OrderBy>,
     search_by: Query<SearchBy>,
-) -> HttpResponse {
-    match app_data.pg_pool.get() {
-        Ok(conn) => {
-            category_repo::get_many(
-                conn,
-                pagination.into_inner(),
-                order.into_inner().option(),
-                search_by.into_inner(),
-            )
-            .await
-        }
-        _ => HttpResponse::InternalServerError().body("Internal Server Error"),
-    }
+    locales: AcceptedLocales,
+) -> Result<HttpResponse, ApiError> {
+    let conn = app_data.pg_pool.get()?;
+    let categories = category_repo::get_many(
+        conn,
+        pagination.into_inner().limited(app_data.max_per_page)?,
+        order.into_inner().option(),
+        search_by.into_inner(),
+    )
+    .await?;
+    let conn = app_data.pg_pool.get()?;
+    let (categories, locales) = translation_repo::localize(conn, categories, locales).await?;
+    Ok(content_language(paginated_response(&req, categories), &locales))
 }
 
 /// Creates a new Category
@@ -90,17 +119,22 @@ async fn get_many(
     request_body = CategoryDto,
     responses(
         (status = 200, body = QResult<Category>, example = json!(QResult {
-            rows: Category {id: 2, name: "Category 2".to_owned(), created_at: Utc::now().naive_utc()},
+            rows: Category {id: 2, name: "Category 2".to_owned(), created_at: Utc::now().naive_utc(), version: 1, updated_at: Utc::now(), deleted_at: None},
             error: None
         })),
     )
 )]
 #[post("")]
-async fn post(app_data: web::Data<AppData>, category: Json<CategoryDto>) -> HttpResponse {
-    match app_data.pg_pool.get() {
-        Ok(conn) => category_repo::add_category(conn, category.into_inner()).await,
-        _ => HttpResponse::InternalServerError().body("Internal Server Error"),
-    }
+async fn post(
+    app_data: web::Data<AppData>,
+    category: Json<CategoryDto>,
+    actor: Actor,
+) -> Result<HttpResponse, ApiError> {
+    let conn = app_data.pg_pool.get()?;
+    let category = category_repo::add_category(conn, category.into_inner(), actor).await?;
+    Ok(HttpResponse::Created()
+        .insert_header(etag(category.version))
+        .json(QResult::new(category, None)))
 }
 
 /// Updates category with corresponding ID
@@ -108,68 +142,168 @@ async fn post(app_data: web::Data<AppData>, category: Json<CategoryDto>) -> Http
     put, 
     path = "/category",
     request_body = UpdateCategoryDto,
+    params(
+        ("If-Match" = String, Header, description = "ETag of the category being replaced, or *")
+    ),
     responses(
         (status = 200, body = QResult<Category>, example = json!(QResult {
-            rows: Category {id: 2, name: "Category 2".to_owned(), created_at: Utc::now().naive_utc()},
+            rows: Category {id: 2, name: "Category 2".to_owned(), created_at: Utc::now().naive_utc(), version: 1, updated_at: Utc::now(), deleted_at: None},
             error: None
         })),
+        (status = 412, description = "The category was modified since", body = ErrorJsonPayload),
+        (status = 428, description = "If-Match is missing", body = ErrorJsonPayload),
     )
 )]
 #[put("{id}")]
 async fn update(
+    req: HttpRequest,
     app_data: web::Data<AppData>,
     category: Json<UpdateCategoryDto>,
     cat_id: web::Path<i32>,
-) -> HttpResponse {
-    match app_data.pg_pool.get() {
-        Ok(conn) => {
-            category_repo::update_category(conn, category.into_inner(), cat_id.into_inner()).await
-        }
-        _ => HttpResponse::InternalServerError().body("Internal Server Error"),
-    }
+    actor: Actor,
+) -> Result<HttpResponse, ApiError> {
+    let precondition = Precondition::from_request(&req)?;
+    let conn = app_data.pg_pool.get()?;
+    let category = category_repo::update_category(
+        conn,
+        category.into_inner().into(),
+        cat_id.into_inner(),
+        precondition,
+        actor,
+    )
+    .await?;
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(category.version))
+        .json(QResult::new(category, None)))
 }
 
-/// Deletes category with corresponding ID
+/// Partially updates category with corresponding ID, JSON Merge Patch semantics
+#[utoipa::path(
+    patch, 
+    path = "/category/{id}",
+    request_body(content = PatchCategoryDto, content_type = "application/merge-patch+json"),
+    params(
+        ("id", description = "Unique id of categories"),
+        ("If-Match" = String, Header, description = "ETag of the category being edited, or *")
+    ),
+    responses(
+        (status = 200, body = QResult<Category>),
+        (status = 404, description = "Category not found", body = ErrorJsonPayload),
+        (status = 412, description = "The category was modified since", body = ErrorJsonPayload),
+        (status = 428, description = "If-Match is missing", body = ErrorJsonPayload),
+    )
+)]
+#[patch("{id}")]
+async fn patch(
+    req: HttpRequest,
+    app_data: web::Data<AppData>,
+    category: Json<PatchCategoryDto>,
+    cat_id: web::Path<i32>,
+    actor: Actor,
+) -> Result<HttpResponse, ApiError> {
+    let precondition = Precondition::from_request(&req)?;
+    let conn = app_data.pg_pool.get()?;
+    let category = category_repo::update_category(
+        conn,
+        category.into_inner(),
+        cat_id.into_inner(),
+        precondition,
+        actor,
+    )
+    .await?;
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(category.version))
+        .json(QResult::new(category, None)))
+}
+
+/// Deletes category with corresponding ID, products filed under it keep the link
 #[utoipa::path(
     delete, 
     path = "/category/{id}",
     params(
-        ("id", description = "Unique id of categories")
+        ("id", description = "Unique id of categories"),
+        ("If-Match" = String, Header, description = "ETag of the category being deleted, or *")
     ),
     responses(
         (status = 200, description = "returns deleted product", body = QResult<Category>, example = json!(QResult {
-            rows: Category {id: 2, name: "Category 2".to_owned(), created_at: Utc::now().naive_utc()},
+            rows: Category {id: 2, name: "Category 2".to_owned(), created_at: Utc::now().naive_utc(), version: 1, updated_at: Utc::now(), deleted_at: None},
             error: None
         })),
+        (status = 412, description = "The category was modified since", body = ErrorJsonPayload),
+        (status = 428, description = "If-Match is missing", body = ErrorJsonPayload),
     )
 )]
 #[delete("{id}")]
-async fn delete(app_data: web::Data<AppData>, cat_id: web::Path<i32>) -> HttpResponse {
-    match app_data.pg_pool.get() {
-        Ok(conn) => category_repo::delete_category(conn, cat_id.into_inner()).await,
-        _ => HttpResponse::InternalServerError().body("Internal Server Error"),
-    }
+async fn delete(
+    req: HttpRequest,
+    app_data: web::Data<AppData>,
+    cat_id: web::Path<i32>,
+    actor: Actor,
+) -> Result<HttpResponse, ApiError> {
+    let precondition = Precondition::from_request(&req)?;
+    let conn = app_data.pg_pool.get()?;
+    let category =
+        category_repo::delete_category(conn, cat_id.into_inner(), precondition, actor).await?;
+    Ok(HttpResponse::Ok().json(QResult::new(category, None)))
 }
 
+/// Restores a deleted category, unless a live one took its name meanwhile
+#[utoipa::path(
+    post,
+    path = "/category/{id}/restore",
+    params(
+        ("id", description = "Unique id of categories")
+    ),
+    responses(
+        (status = 200, description = "Returns the restored category", body = QResult<Category>),
+        (status = 404, description = "Category not found or purged", body = ErrorJsonPayload),
+        (status = 409, description = "The name is used by another category", body = ErrorJsonPayload),
+    )
+)]
+#[post("{id}/restore")]
+async fn restore(
+    app_data: web::Data<AppData>,
+    cat_id: web::Path<i32>,
+    actor: Actor,
+) -> Result<HttpResponse, ApiError> {
+    let conn = app_data.pg_pool.get()?;
+    let category = category_repo::restore_category(conn, cat_id.into_inner(), actor).await?;
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(category.version))
+        .json(QResult::new(category, None)))
+}
 
 /// Deletes cateogories with corresponding IDs
+///
+/// `If-Match` lists the `ETag` of every category deleted, or is `*`
 #[utoipa::path(
     delete, 
     path = "/category",
     request_body = ManyIdsDto,
+    params(
+        ("If-Match" = String, Header, description = "ETags of the categories being deleted, or *")
+    ),
     responses(
         (status = 200, body = QResult<Category>, example = json!(QResult {
-            rows: Category {id: 2, name: "Category 2".to_owned(), created_at: Utc::now().naive_utc()},
+            rows: Category {id: 2, name: "Category 2".to_owned(), created_at: Utc::now().naive_utc(), version: 1, updated_at: Utc::now(), deleted_at: None},
             error: None
         })),
+        (status = 412, description = "A category was modified since", body = ErrorJsonPayload),
+        (status = 428, description = "If-Match is missing", body = ErrorJsonPayload),
     )
 )]
 #[delete("")]
-async fn delete_many(app_data: web::Data<AppData>, ids: web::Json<ManyIdsDto>) -> HttpResponse {
-    match app_data.pg_pool.get() {
-        Ok(conn) => category_repo::delete_many(conn, ids.into_inner().ids).await,
-        _ => HttpResponse::InternalServerError().body("Internal Server Error"),
-    }
+async fn delete_many(
+    req: HttpRequest,
+    app_data: web::Data<AppData>,
+    ids: web::Json<ManyIdsDto>,
+    actor: Actor,
+) -> Result<HttpResponse, ApiError> {
+    let precondition = Precondition::from_request(&req)?;
+    let conn = app_data.pg_pool.get()?;
+    let category =
+        category_repo::delete_many(conn, ids.into_inner().ids, precondition, actor).await?;
+    Ok(HttpResponse::Ok().json(QResult::new(category, None)))
 }
 
 pub fn init_category_routes(cfg: &mut web::ServiceConfig) {
@@ -180,6 +314,8 @@ pub fn init_category_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(get_many).app_data(query_cfg);
     cfg.service(post);
     cfg.service(update);
+    cfg.service(patch);
     cfg.service(delete);
+    cfg.service(restore);
     cfg.service(delete_many);
 }
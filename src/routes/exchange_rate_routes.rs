@@ -0,0 +1,139 @@
+use crate::{
+    models::{ExchangeRate, ExchangeRateDto, QResult, UpdateExchangeRateDto},
+    repos::exchange_rate_repo,
+    utils::{json_error_handler, Actor, ApiError, AppData},
+};
+use actix_web::{
+    delete, get, post, put,
+    web::{self, Data, ServiceConfig},
+    HttpResponse,
+};
+use actix_web_validator::{Json, JsonConfig};
+use bigdecimal::BigDecimal;
+use chrono::Utc;
+
+/// Lists the exchange rates, by currency pair
+#[utoipa::path(
+    get,
+    path = "/exchange-rate",
+    responses(
+        (status = 200, body = QResult<Vec<ExchangeRate>>, example = json!(QResult {
+            rows: vec![ExchangeRate {
+                id: 1,
+                from_currency: "EUR".to_owned(),
+                to_currency: "USD".to_owned(),
+                rate: BigDecimal::new(108_750_000.into(), 8),
+                updated_at: Utc::now(),
+            }],
+            error: None
+        })),
+    )
+)]
+#[get("")]
+async fn get_many(app_data: Data<AppData>) -> Result<HttpResponse, ApiError> {
+    let conn = app_data.pg_pool.get()?;
+    let rates = exchange_rate_repo::get_rates(conn).await?;
+    Ok(HttpResponse::Ok().json(QResult::new(rates, None)))
+}
+
+/// Returns the exchange rate with id=:id
+#[utoipa::path(
+    get,
+    path = "/exchange-rate/{id}",
+    params(
+        ("id", description = "Unique id of exchange rate")
+    ),
+    responses(
+        (status = 200, body = QResult<ExchangeRate>),
+        (status = 404, description = "Exchange rate not found", body = ErrorJsonPayload),
+    )
+)]
+#[get("{id}")]
+async fn get(app_data: Data<AppData>, rate_id: web::Path<i32>) -> Result<HttpResponse, ApiError> {
+    let conn = app_data.pg_pool.get()?;
+    let rate = exchange_rate_repo::get_rate(conn, rate_id.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(QResult::new(rate, None)))
+}
+
+/// Adds the rate of a currency pair, converting the other way uses its inverse
+/// until that pair has its own rate
+#[utoipa::path(
+    post,
+    path = "/exchange-rate",
+    request_body = ExchangeRateDto,
+    responses(
+        (status = 201, body = QResult<ExchangeRate>),
+        (status = 409, description = "The pair already has a rate", body = ErrorJsonPayload),
+        (status = 422, description = "Unknown currency or invalid rate", body = ErrorJsonPayload),
+    )
+)]
+#[post("")]
+async fn post(
+    app_data: Data<AppData>,
+    rate: Json<ExchangeRateDto>,
+    actor: Actor,
+) -> Result<HttpResponse, ApiError> {
+    let conn = app_data.pg_pool.get()?;
+    let rate = exchange_rate_repo::add_rate(conn, rate.into_inner(), actor).await?;
+    Ok(HttpResponse::Created().json(QResult::new(rate, None)))
+}
+
+/// Changes the rate of a currency pair
+#[utoipa::path(
+    put,
+    path = "/exchange-rate/{id}",
+    request_body = UpdateExchangeRateDto,
+    params(
+        ("id", description = "Unique id of exchange rate")
+    ),
+    responses(
+        (status = 200, body = QResult<ExchangeRate>),
+        (status = 404, description = "Exchange rate not found", body = ErrorJsonPayload),
+        (status = 422, description = "Invalid rate", body = ErrorJsonPayload),
+    )
+)]
+#[put("{id}")]
+async fn update(
+    app_data: Data<AppData>,
+    rate_id: web::Path<i32>,
+    rate: Json<UpdateExchangeRateDto>,
+    actor: Actor,
+) -> Result<HttpResponse, ApiError> {
+    let conn = app_data.pg_pool.get()?;
+    let rate =
+        exchange_rate_repo::update_rate(conn, rate_id.into_inner(), rate.into_inner(), actor)
+            .await?;
+    Ok(HttpResponse::Ok().json(QResult::new(rate, None)))
+}
+
+/// Deletes an exchange rate, prices needing it can no longer be converted
+#[utoipa::path(
+    delete,
+    path = "/exchange-rate/{id}",
+    params(
+        ("id", description = "Unique id of exchange rate")
+    ),
+    responses(
+        (status = 200, body = QResult<ExchangeRate>),
+        (status = 404, description = "Exchange rate not found", body = ErrorJsonPayload),
+    )
+)]
+#[delete("{id}")]
+async fn delete(
+    app_data: Data<AppData>,
+    rate_id: web::Path<i32>,
+    actor: Actor,
+) -> Result<HttpResponse, ApiError> {
+    let conn = app_data.pg_pool.get()?;
+    let rate = exchange_rate_repo::delete_rate(conn, rate_id.into_inner(), actor).await?;
+    Ok(HttpResponse::Ok().json(QResult::new(rate, None)))
+}
+
+pub fn init_exchange_rate_routes(cfg: &mut ServiceConfig) {
+    cfg.app_data(JsonConfig::default().error_handler(json_error_handler));
+    cfg.service(get_many);
+    cfg.service(get);
+    cfg.service(post);
+    cfg.service(update);
+    cfg.service(delete);
+}
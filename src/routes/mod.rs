@@ -1,9 +1,32 @@
+pub mod admin_routes;
+pub mod audit_routes;
 pub mod category_routes;
+pub mod exchange_rate_routes;
 pub mod product_routes;
 pub mod store_routes;
+pub mod suggest_routes;
+pub mod translation_routes;
 
 pub use self::{
-    category_routes::{init_category_routes, *},
-    product_routes::*,
-    store_routes::{init_store_routes, DateFilter, StoresOrderBy},
+    admin_routes::{init_admin_routes, PurgeBefore},
+    audit_routes::{init_audit_routes, AuditFilter},
+    category_routes::{init_category_routes, ManyIdsDto},
+    exchange_rate_routes::init_exchange_rate_routes,
+    product_routes::{init_product_routes, validate_order, OrderBy, SearchBy, Stringify},
+    store_routes::{init_store_routes, DateFilter, OpenAt, StatusAt, StoresOrderBy},
+    suggest_routes::{init_suggest_routes, SuggestQuery},
+    translation_routes::{init_translation_routes, TranslationFilter},
 };
+
+use actix_web::web::{scope, ServiceConfig};
+
+pub fn init_routes(cfg: &mut ServiceConfig) {
+    cfg.service(scope("/category").configure(init_category_routes));
+    cfg.service(scope("/product").configure(init_product_routes));
+    cfg.service(scope("/store").configure(init_store_routes));
+    cfg.service(scope("/admin").configure(init_admin_routes));
+    cfg.service(scope("/audit").configure(init_audit_routes));
+    cfg.service(scope("/exchange-rate").configure(init_exchange_rate_routes));
+    cfg.service(scope("/translation").configure(init_translation_routes));
+    cfg.service(scope("/suggest").configure(init_suggest_routes));
+}
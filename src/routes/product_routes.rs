@@ -1,18 +1,26 @@
 use crate::{
-    models::{CategoryId, ProductDto, UpdateProductDto, StoreId, Product, PaginatedResult, QResult, ProductsCategories},
-    repos::{pagination::PaginationDto, product_repo},
-    utils::{json_error_handler, AppData},
+    models::{
+        price::Amount, validate_currency, Category, CategoryId, CurrencyPriceDto, InCurrency, PaginatedResult,
+        PatchProductDto, PriceAt, PriceRange, Product, ProductDetails, ProductDto, ProductExpand,
+        ProductPrice, ProductsCategories, ProductsResult, QResult, StoreId, TextSearch,
+        UpdateProductDto,
+    },
+    repos::{pagination::PaginationDto, product_repo, translation_repo},
+    utils::{
+        content_language, etag, json_error_handler, paginated_response, versioned_response,
+        AcceptedLocales, Actor, ApiError, AppData, Precondition, DEFAULT_LOCALE,
+    },
 };
 use actix_web::{
-    delete, get, post, put,
+    delete, get, patch, post, put,
     web::{self, ServiceConfig},
-    HttpResponse,
+    HttpRequest, HttpResponse,
 };
 use actix_web_validator::{Json, JsonConfig, Query, QueryConfig};
 use bigdecimal::BigDecimal;
-use chrono::Utc;
+use chrono::{Duration, Utc};
 use serde::{Deserialize, Serialize};
-use utoipa::{ToSchema, IntoParams};
+use utoipa::{IntoParams, ToSchema};
 use validator::{Validate, ValidationError};
 
 pub fn validate_order(order: &str) -> Result<(), ValidationError> {
@@ -59,6 +67,7 @@ impl OrderBy {
 }
 
 #[derive(Serialize, Deserialize, Validate, Debug, ToSchema, IntoParams)]
+#[validate(schema(function = "validate_search_by"))]
 pub struct SearchBy {
     #[validate(length(max = 256))]
     #[schema(example = "substring to look for")]
@@ -67,6 +76,16 @@ pub struct SearchBy {
     #[schema(example = "substring to look for")]
     pub description: Option<String>,
     pub in_holiday: Option<bool>,
+    /// Matches `name` by trigram similarity instead, typos, accents and case
+    /// aside. Translated names match too and the results come most similar first
+    pub fuzzy: Option<bool>,
+}
+
+fn validate_search_by(search: &SearchBy) -> Result<(), ValidationError> {
+    if search.fuzzy == Some(true) && search.name.as_deref().unwrap_or_default().is_empty() {
+        return Err(ValidationError::new("fuzzy search needs a name"));
+    }
+    Ok(())
 }
 
 impl SearchBy {
@@ -75,6 +94,7 @@ impl SearchBy {
             name: Some(name),
             description: _,
             in_holiday: _,
+            fuzzy: None | Some(false),
         } = self
         {
             format!("%{}%", name)
@@ -88,6 +108,7 @@ impl SearchBy {
             name: _,
             description: Some(description),
             in_holiday: _,
+            fuzzy: _,
         } = self
         {
             format!("%{}%", description)
@@ -96,11 +117,17 @@ impl SearchBy {
         }
     }
 
+    /// Name to match by similarity, only with `fuzzy`
+    pub fn fuzzy_name(&self) -> Option<&str> {
+        self.name.as_deref().filter(|_| self.fuzzy == Some(true))
+    }
+
     pub fn get_is_holiday(&self) -> &bool {
         if let Self {
             name: _,
             description: _,
             in_holiday: Some(val),
+            fuzzy: _,
         } = self
         {
             val
@@ -114,6 +141,7 @@ impl SearchBy {
             name: _,
             description: _,
             in_holiday: Some(val),
+            fuzzy: _,
         } = self
         {
             val
@@ -125,6 +153,8 @@ impl SearchBy {
 
 pub trait Stringify {
     fn stringify(self) -> String;
+    /// Requested column and whether it is descending, newest first by default
+    fn sorting(&self) -> (&str, bool);
 }
 
 impl Stringify for Option<OrderBy> {
@@ -139,15 +169,23 @@ impl Stringify for Option<OrderBy> {
             "created_at DESC".to_owned()
         }
     }
+
+    fn sorting(&self) -> (&str, bool) {
+        match self {
+            Some(OrderBy {
+                order: Some(order),
+                by: Some(by),
+            }) => (by, order == "DESC"),
+            _ => ("created_at", true),
+        }
+    }
 }
 // {
 // 	"rows": {
 // 		"id": 14,
 // 		"name": "lol",
-// 		"i18n_name": "haha",
 // 		"price": "10.00",
 // 		"description": "lol",
-// 		"i18n_description": "lol",
 // 		"created_at": "2023-02-14T23:04:25.325874",
 // 		"store_id": null
 // 	},
@@ -155,28 +193,225 @@ impl Stringify for Option<OrderBy> {
 // }
 
 /// Returns corresponding product with id=:prodId
+///
+/// Embeds its categories and store unless `expand` narrows the relations.
+/// Those change apart from the product, so responses embedding any carry no
+/// `ETag`, `expand=` gets the bare product with it. A price converted through
+/// `currency` follows the exchange rates, so those responses carry no `ETag`. Names and descriptions come in the locale best
+/// matching `Accept-Language`, translated responses carry no `ETag` either
 #[utoipa::path(
     get, 
     path = "/product/{id}",
     params(
-        ("id", description = "Unique id of products")
+        ("id", description = "Unique id of products"),
+        ("If-None-Match" = Option<String>, Header, description = "ETag of a cached copy"),
+        ("Accept-Language" = Option<String>, Header, description = "Preferred locales, the default one without"),
+        ProductExpand,
+        InCurrency
     ),
     responses(
-        (status = 200, description = "Returns the product with the id", body = QResult<Product>, example = json!(QResult {
-            rows: Product {id: 1, name: "product 1".to_owned(), price: BigDecimal::from(10), i18n_name: Some("i18n".to_owned()), i18n_description: Some("description".to_owned()), description: Some("description".to_owned()), created_at: Utc::now().naive_utc(), store_id: Some(1)},
+        (status = 200, description = "Returns the product with the id", body = QResult<ProductDetails>, headers(("etag" = String, description = "Version of the product row, without embedded relations"), ("content-language" = String, description = "Locales of the text")), example = json!(QResult {
+            rows: ProductDetails {
+                product: Product {id: 1, name: "product 1".to_owned(), price: BigDecimal::from(10), description: Some("description".to_owned()), created_at: Utc::now().naive_utc(), store_id: Some(1), version: 1, updated_at: Utc::now(), deleted_at: None},
+                categories: Some(vec![Category {id: 1, name: "Category 1".to_owned(), created_at: Utc::now().naive_utc(), version: 1, updated_at: Utc::now(), deleted_at: None}]),
+                store: None,
+                currency: None
+            },
             error: None
         })),
+        (status = 304, description = "The cached copy is current"),
+        (status = 404, description = "Product not found", body = ErrorJsonPayload),
+        (status = 422, description = "No exchange rate to the currency", body = ErrorJsonPayload),
     )
 )]
 #[get("{prod_id}")]
-pub async fn get(app_data: web::Data<AppData>, prod_id: web::Path<i32>) -> HttpResponse {
-    match app_data.pg_pool.get() {
-        Ok(conn) => product_repo::get_product(conn, prod_id.into_inner()).await,
-        _ => HttpResponse::InternalServerError().body("Internal Server Error"),
+pub async fn get(
+    req: HttpRequest,
+    app_data: web::Data<AppData>,
+    prod_id: web::Path<i32>,
+    expand: Query<ProductExpand>,
+    currency: Query<InCurrency>,
+    locales: AcceptedLocales,
+) -> Result<HttpResponse, ApiError> {
+    let conn = app_data.pg_pool.get()?;
+    let product = product_repo::get_product(
+        conn,
+        prod_id.into_inner(),
+        expand.into_inner(),
+        currency.into_inner().currency,
+    )
+    .await?;
+    let conn = app_data.pg_pool.get()?;
+    let (product, locales) = translation_repo::localize(conn, product, locales).await?;
+    let embeds = product.categories.is_some() || product.store.is_some();
+    if embeds || product.currency.is_some() || locales != [DEFAULT_LOCALE] {
+        let response = HttpResponse::Ok().json(QResult::new(product, None));
+        return Ok(content_language(response, &locales));
     }
+    let version = product.product.version;
+    let response = versioned_response(&req, version, QResult::new(product, None));
+    Ok(content_language(response, &locales))
+}
+
+/// Returns the price history of the product, oldest first
+///
+/// With `at`, only the price in force at that instant, an empty list when the
+/// product had no price yet
+#[utoipa::path(
+    get, 
+    path = "/product/{id}/prices",
+    params(
+        ("id", description = "Unique id of products"),
+        PriceAt
+    ),
+    responses(
+        (status = 200, body = QResult<Vec<ProductPrice>>, example = json!(QResult {
+            rows: vec![
+                ProductPrice {id: 1, product_id: 1, price: BigDecimal::from(10), effective_from: Utc::now() - Duration::days(30)},
+                ProductPrice {id: 7, product_id: 1, price: BigDecimal::from(12), effective_from: Utc::now()},
+            ],
+            error: None
+        })),
+        (status = 404, description = "Product not found", body = ErrorJsonPayload),
+    )
+)]
+#[get("{prod_id}/prices")]
+async fn get_prices(
+    app_data: web::Data<AppData>,
+    prod_id: web::Path<i32>,
+    at: Query<PriceAt>,
+) -> Result<HttpResponse, ApiError> {
+    let conn = app_data.pg_pool.get()?;
+    let at = at.into_inner().at.map(|at| at.with_timezone(&Utc));
+    let prices = product_repo::get_prices(conn, prod_id.into_inner(), at).await?;
+    Ok(HttpResponse::Ok().json(QResult::new(prices, None)))
+}
+
+/// Lists the prices set by hand in other currencies than the store's
+#[utoipa::path(
+    get, 
+    path = "/product/{id}/currency-prices",
+    params(
+        ("id", description = "Unique id of products")
+    ),
+    responses(
+        (status = 200, body = QResult<Vec<ProductCurrencyPrice>>),
+        (status = 404, description = "Product not found", body = ErrorJsonPayload),
+    )
+)]
+#[get("{prod_id}/currency-prices")]
+async fn get_currency_prices(
+    app_data: web::Data<AppData>,
+    prod_id: web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    let conn = app_data.pg_pool.get()?;
+    let prices = product_repo::get_currency_prices(conn, prod_id.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(QResult::new(prices, None)))
+}
+
+fn currency_path(currency: String) -> Result<String, ApiError> {
+    validate_currency(&currency).map_err(|err| ApiError::Validation {
+        message: err.code.into_owned(),
+        fields: vec!["currency".to_owned()],
+    })?;
+    Ok(currency)
+}
+
+/// Sets the price of the product in a currency other than its store's, used
+/// instead of converting its price
+#[utoipa::path(
+    put, 
+    path = "/product/{id}/currency-prices/{currency}",
+    request_body = CurrencyPriceDto,
+    params(
+        ("id", description = "Unique id of products"),
+        ("currency", description = "ISO 4217 code of the currency"),
+        ("If-Match" = String, Header, description = "ETag of the product, or *")
+    ),
+    responses(
+        (status = 200, body = QResult<ProductCurrencyPrice>),
+        (status = 404, description = "Product not found", body = ErrorJsonPayload),
+        (status = 412, description = "The product was modified since", body = ErrorJsonPayload),
+        (status = 422, description = "Unknown currency, the one of the store or invalid price", body = ErrorJsonPayload),
+        (status = 428, description = "If-Match is missing", body = ErrorJsonPayload),
+    )
+)]
+#[put("{prod_id}/currency-prices/{currency}")]
+async fn set_currency_price(
+    req: HttpRequest,
+    app_data: web::Data<AppData>,
+    path: web::Path<(i32, String)>,
+    price: Json<CurrencyPriceDto>,
+    actor: Actor,
+) -> Result<HttpResponse, ApiError> {
+    let precondition = Precondition::from_request(&req)?;
+    let (prod_id, currency) = path.into_inner();
+    let currency = currency_path(currency)?;
+    let conn = app_data.pg_pool.get()?;
+    let price = product_repo::set_currency_price(
+        conn,
+        prod_id,
+        currency,
+        price.into_inner().price,
+        precondition,
+        actor,
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(QResult::new(price, None)))
+}
+
+/// Removes the price of the product in a currency, its price is converted again
+#[utoipa::path(
+    delete, 
+    path = "/product/{id}/currency-prices/{currency}",
+    params(
+        ("id", description = "Unique id of products"),
+        ("currency", description = "ISO 4217 code of the currency"),
+        ("If-Match" = String, Header, description = "ETag of the product, or *")
+    ),
+    responses(
+        (status = 200, body = QResult<ProductCurrencyPrice>),
+        (status = 404, description = "Product or price not found", body = ErrorJsonPayload),
+        (status = 412, description = "The product was modified since", body = ErrorJsonPayload),
+        (status = 422, description = "Unknown currency", body = ErrorJsonPayload),
+        (status = 428, description = "If-Match is missing", body = ErrorJsonPayload),
+    )
+)]
+#[delete("{prod_id}/currency-prices/{currency}")]
+async fn delete_currency_price(
+    req: HttpRequest,
+    app_data: web::Data<AppData>,
+    path: web::Path<(i32, String)>,
+    actor: Actor,
+) -> Result<HttpResponse, ApiError> {
+    let precondition = Precondition::from_request(&req)?;
+    let (prod_id, currency) = path.into_inner();
+    let currency = currency_path(currency)?;
+    let conn = app_data.pg_pool.get()?;
+    let price =
+        product_repo::delete_currency_price(conn, prod_id, currency, precondition, actor).await?;
+    Ok(HttpResponse::Ok().json(QResult::new(price, None)))
 }
 
 /// Returns a paginated list of products
+///
+/// Sending `cursor` instead of `page` switches to keyset pagination, the
+/// response then carries `next_cursor`/`prev_cursor` instead of the totals.
+/// `min_price`, `max_price` and the price order apply to the prices in the
+/// store currencies, before any `currency` conversion: their amounts are
+/// compared as is across currencies, so 10 JPY comes after 5 EUR. Filter on
+/// `store_id` to keep to a single currency. Text is translated after
+/// `Accept-Language`, `name`, `description` and the name order apply to the
+/// default locale.
+///
+/// `q` searches the text of every locale and puts the best matches first along
+/// with their `rank` and a highlighted `snippet`. Its words are stemmed in the
+/// default locale and in the `Accept-Language` ones. Given `by` and `order` win
+/// over the rank, which cannot back a `cursor`.
+///
+/// `fuzzy=true` matches `name` by trigrams against the names of every locale,
+/// whatever the typos, accents or case. Products get their `similarity` and
+/// come most similar first when neither `q` nor an order is given
 #[utoipa::path(
     get, 
     path = "/product",
@@ -185,60 +420,80 @@ pub async fn get(app_data: web::Data<AppData>, prod_id: web::Path<i32>) -> HttpR
         OrderBy,
         SearchBy,
         CategoryId,
-        StoreId
+        StoreId,
+        PriceRange,
+        InCurrency,
+        TextSearch,
+        ("Accept-Language" = Option<String>, Header, description = "Preferred locales, the default one without")
     ),
     responses(
-        (status = 200, description = "Returns a list of products", body = PaginatedResult<Product>, example = json!(PaginatedResult {
+        (status = 200, description = "Returns a list of products", body = PaginatedResult<Product>, headers(("link" = String, description = "RFC 8288 first/prev/next/last links"), ("content-language" = String, description = "Locales of the text")), example = json!(PaginatedResult {
             per_page: 10,
             page: 1,
+            total: 1,
             total_pages: 1,
-            result: vec![Product {id: 1, name: "product 1".to_owned(), price: BigDecimal::from(10), i18n_name: Some("i18n".to_owned()), i18n_description: Some("description".to_owned()), description: Some("description".to_owned()), created_at: Utc::now().naive_utc(), store_id: Some(1)}]
+            result: vec![Product {id: 1, name: "product 1".to_owned(), price: BigDecimal::from(10), description: Some("description".to_owned()), created_at: Utc::now().naive_utc(), store_id: Some(1), version: 1, updated_at: Utc::now(), deleted_at: None}]
         })),
     )
 )]
 #[get("")]
+#[allow(clippy::too_many_arguments)]
 pub async fn get_many(
     app_data: web::Data<AppData>,
+    req: HttpRequest,
     pagination: Query<PaginationDto>,
     order: Query<OrderBy>,
     search: Query<SearchBy>,
     category_id: Query<CategoryId>,
     store_id: Query<StoreId>,
-) -> HttpResponse {
-    match app_data.pg_pool.get() {
-        Ok(conn) => {
-            product_repo::get_many(
-                conn,
-                pagination.into_inner(),
-                order.into_inner().option(),
-                search.into_inner(),
-                category_id.into_inner().category_id,
-                store_id.into_inner().store_id
-            )
-            .await
-        }
-        _ => HttpResponse::InternalServerError().body("Internal Server Error"),
-    }
+    price: Query<PriceRange>,
+    currency: Query<InCurrency>,
+    text: Query<TextSearch>,
+    locales: AcceptedLocales,
+) -> Result<HttpResponse, ApiError> {
+    let conn = app_data.pg_pool.get()?;
+    let products = product_repo::get_many(
+        conn,
+        pagination.into_inner().limited(app_data.max_per_page)?,
+        order.into_inner().option(),
+        search.into_inner(),
+        category_id.into_inner().category_id,
+        store_id.into_inner().store_id,
+        price.into_inner(),
+        currency.into_inner().currency,
+        text.into_inner().q,
+        locales.clone(),
+    )
+    .await?;
+    let conn = app_data.pg_pool.get()?;
+    let (products, locales) = translation_repo::localize(conn, products, locales).await?;
+    Ok(content_language(paginated_response(&req, products), &locales))
 }
 
 /// Creates a new Product
 #[utoipa::path(
     post, 
     path = "/product",
-    request_body (content = ProductDto, content_type = "application/json", example = json!(ProductDto {  name: "product 1".to_owned(), price: 10.10, i18n_name: Some("i18n".to_owned()), i18n_description: Some("description".to_owned()), description: Some("description".to_owned()), store_id: Some(1), category_id: None })),
+    request_body (content = ProductDto, content_type = "application/json", example = json!(ProductDto {  name: "product 1".to_owned(), price: Amount::Decimal(BigDecimal::new(1010.into(), 2)), description: Some("description".to_owned()), store_id: Some(1), category_ids: vec![1, 3] })),
     responses(
-        (status = 200, body = QResult<Product>, example = json!(QResult {
-            rows: Product {id: 1, name: "product 1".to_owned(), price: BigDecimal::from(10), i18n_name: Some("i18n".to_owned()), i18n_description: Some("description".to_owned()), description: Some("description".to_owned()), created_at: Utc::now().naive_utc(), store_id: Some(1)},
+        (status = 200, body = QResult<ProductsResult>, example = json!(QResult {
+            rows: ProductsResult {id: 1, name: "product 1".to_owned(), price: BigDecimal::from(10), description: Some("description".to_owned()), created_at: Utc::now().naive_utc(), store_id: Some(1), version: 1, updated_at: Utc::now(), categories: vec![Category {id: 1, name: "Category 1".to_owned(), created_at: Utc::now().naive_utc(), version: 1, updated_at: Utc::now(), deleted_at: None}], currency: None, rank: None, snippet: None, similarity: None},
             error: None
         })),
+        (status = 404, description = "Store or category not found", body = ErrorJsonPayload),
     )
 )]
 #[post("")]
-pub async fn post(app_data: web::Data<AppData>, prod: Json<ProductDto>) -> HttpResponse {
-    match app_data.pg_pool.get() {
-        Ok(conn) => product_repo::add_product(conn, prod.into_inner()).await,
-        _ => HttpResponse::InternalServerError().body("Internal Server Error"),
-    }
+pub async fn post(
+    app_data: web::Data<AppData>,
+    prod: Json<ProductDto>,
+    actor: Actor,
+) -> Result<HttpResponse, ApiError> {
+    let conn = app_data.pg_pool.get()?;
+    let product = product_repo::add_product(conn, prod.into_inner(), actor).await?;
+    Ok(HttpResponse::Created()
+        .insert_header(etag(product.version))
+        .json(QResult::new(product, None)))
 }
 
 /// Edits product with corresponding ID
@@ -247,51 +502,136 @@ pub async fn post(app_data: web::Data<AppData>, prod: Json<ProductDto>) -> HttpR
     path = "/product",
     request_body = UpdateProductDto,
     params (
-        ("id", description = "id of product")
+        ("id", description = "id of product"),
+        ("If-Match" = String, Header, description = "ETag of the product being replaced, or *")
     ),
     responses(
-        (status = 200, body = QResult<Product>, example = json!(QResult {
-            rows: Product {id: 1, name: "product 1".to_owned(), price: BigDecimal::from(10), i18n_name: Some("i18n".to_owned()), i18n_description: Some("description".to_owned()), description: Some("description".to_owned()), created_at: Utc::now().naive_utc(), store_id: Some(1)},
+        (status = 200, body = QResult<ProductsResult>, example = json!(QResult {
+            rows: ProductsResult {id: 1, name: "product 1".to_owned(), price: BigDecimal::from(10), description: Some("description".to_owned()), created_at: Utc::now().naive_utc(), store_id: Some(1), version: 1, updated_at: Utc::now(), categories: vec![Category {id: 1, name: "Category 1".to_owned(), created_at: Utc::now().naive_utc(), version: 1, updated_at: Utc::now(), deleted_at: None}], currency: None, rank: None, snippet: None, similarity: None},
             error: None
         })),
+        (status = 404, description = "Product, store or category not found", body = ErrorJsonPayload),
+        (status = 412, description = "The product was modified since", body = ErrorJsonPayload),
+        (status = 428, description = "If-Match is missing", body = ErrorJsonPayload),
     )
 )]
 #[put("{id}")]
 pub async fn update(
+    req: HttpRequest,
     app_data: web::Data<AppData>,
     prod_id: web::Path<i32>,
     prod: Json<UpdateProductDto>,
-) -> HttpResponse {
-    match app_data.pg_pool.get() {
-        Ok(conn) => {
-            product_repo::update_product(conn, prod_id.into_inner(), prod.into_inner()).await
-        }
-        _ => HttpResponse::InternalServerError().body("Internal Server Error"),
-    }
+    actor: Actor,
+) -> Result<HttpResponse, ApiError> {
+    let precondition = Precondition::from_request(&req)?;
+    let conn = app_data.pg_pool.get()?;
+    let product = product_repo::update_product(
+        conn,
+        prod_id.into_inner(),
+        prod.into_inner().into(),
+        precondition,
+        actor,
+    )
+    .await?;
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(product.version))
+        .json(QResult::new(product, None)))
 }
 
-/// Deletes product with id
+/// Partially edits product with corresponding ID, JSON Merge Patch semantics
+#[utoipa::path(
+    patch, 
+    path = "/product/{id}",
+    request_body(content = PatchProductDto, content_type = "application/merge-patch+json"),
+    params (
+        ("id", description = "id of product"),
+        ("If-Match" = String, Header, description = "ETag of the product being edited, or *")
+    ),
+    responses(
+        (status = 200, body = QResult<ProductsResult>),
+        (status = 404, description = "Product, store or category not found", body = ErrorJsonPayload),
+        (status = 412, description = "The product was modified since", body = ErrorJsonPayload),
+        (status = 428, description = "If-Match is missing", body = ErrorJsonPayload),
+    )
+)]
+#[patch("{id}")]
+pub async fn patch(
+    req: HttpRequest,
+    app_data: web::Data<AppData>,
+    prod_id: web::Path<i32>,
+    prod: Json<PatchProductDto>,
+    actor: Actor,
+) -> Result<HttpResponse, ApiError> {
+    let precondition = Precondition::from_request(&req)?;
+    let conn = app_data.pg_pool.get()?;
+    let product = product_repo::update_product(
+        conn,
+        prod_id.into_inner(),
+        prod.into_inner(),
+        precondition,
+        actor,
+    )
+    .await?;
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(product.version))
+        .json(QResult::new(product, None)))
+}
+
+/// Deletes product with id, it stays restorable until purged
 #[utoipa::path(
     delete, 
     path = "/product/{id}",
     params(
-        ("id", description = "Unique id of products")
+        ("id", description = "Unique id of products"),
+        ("If-Match" = String, Header, description = "ETag of the product being deleted, or *")
     ),
     responses(
         (status = 200, description = "Returns deleted product", body = QResult<Product>, example = json!(QResult {
-            rows: Product {id: 1, name: "product 1".to_owned(), price: BigDecimal::from(10), i18n_name: Some("i18n".to_owned()), i18n_description: Some("description".to_owned()), description: Some("description".to_owned()), created_at: Utc::now().naive_utc(), store_id: Some(1)},
+            rows: Product {id: 1, name: "product 1".to_owned(), price: BigDecimal::from(10), description: Some("description".to_owned()), created_at: Utc::now().naive_utc(), store_id: Some(1), version: 1, updated_at: Utc::now(), deleted_at: None},
             error: None
         })),
+        (status = 412, description = "The product was modified since", body = ErrorJsonPayload),
+        (status = 428, description = "If-Match is missing", body = ErrorJsonPayload),
     )
 )]
 #[delete("{id}")]
-pub async fn delete(app_data: web::Data<AppData>, id: web::Path<i32>) -> HttpResponse {
-    match app_data.pg_pool.get() {
-        Ok(conn) => product_repo::delete_product(conn, id.into_inner()).await,
-        _ => HttpResponse::InternalServerError().body("Internal Server Error"),
-    }
+pub async fn delete(
+    req: HttpRequest,
+    app_data: web::Data<AppData>,
+    id: web::Path<i32>,
+    actor: Actor,
+) -> Result<HttpResponse, ApiError> {
+    let precondition = Precondition::from_request(&req)?;
+    let conn = app_data.pg_pool.get()?;
+    let product =
+        product_repo::delete_product(conn, id.into_inner(), precondition, actor).await?;
+    Ok(HttpResponse::Ok().json(QResult::new(product, None)))
 }
 
+/// Restores a deleted product
+#[utoipa::path(
+    post,
+    path = "/product/{id}/restore",
+    params(
+        ("id", description = "Unique id of products")
+    ),
+    responses(
+        (status = 200, description = "Returns the restored product", body = QResult<ProductsResult>),
+        (status = 404, description = "Product not found or purged", body = ErrorJsonPayload),
+    )
+)]
+#[post("{id}/restore")]
+pub async fn restore(
+    app_data: web::Data<AppData>,
+    id: web::Path<i32>,
+    actor: Actor,
+) -> Result<HttpResponse, ApiError> {
+    let conn = app_data.pg_pool.get()?;
+    let product = product_repo::restore_product(conn, id.into_inner(), actor).await?;
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(product.version))
+        .json(QResult::new(product, None)))
+}
 
 /// Attach category to product
 #[utoipa::path(
@@ -300,24 +640,30 @@ pub async fn delete(app_data: web::Data<AppData>, id: web::Path<i32>) -> HttpRes
     params(
         ("prod_id", description = "Unique id of products"),
         ("cat_id", description = "Unique id of category"),
+        ("If-Match" = String, Header, description = "ETag of the product, or *")
     ),
     responses(
         (status = 200, body = QResult<ProductsCategories>, example = json!(QResult {
             rows: ProductsCategories { id: 1, category_id: 3, product_id: 5 },
             error: None
         })),
+        (status = 404, description = "Product or category not found", body = ErrorJsonPayload),
+        (status = 412, description = "The product was modified since", body = ErrorJsonPayload),
+        (status = 428, description = "If-Match is missing", body = ErrorJsonPayload),
     )
 )]
 #[put("{prod_id}/category/{cat_id}")]
 pub async fn attach_category(
+    req: HttpRequest,
     app_data: web::Data<AppData>,
     path: web::Path<(i32, i32)>,
-) -> HttpResponse {
-    println!("inside attach");
-    match app_data.pg_pool.get() {
-        Ok(conn) => product_repo::attach_category(conn, path.0, path.1).await,
-        _ => HttpResponse::InternalServerError().body("Internal Server Error"),
-    }
+    actor: Actor,
+) -> Result<HttpResponse, ApiError> {
+    let precondition = Precondition::from_request(&req)?;
+    let conn = app_data.pg_pool.get()?;
+    let product_category =
+        product_repo::attach_category(conn, path.0, path.1, precondition, actor).await?;
+    Ok(HttpResponse::Ok().json(QResult::new(product_category, None)))
 }
 
 /// Attach store to product
@@ -327,20 +673,32 @@ pub async fn attach_category(
     params(
         ("prod_id", description = "Unique id of products"),
         ("store_id", description = "Unique id of stores"),
+        ("If-Match" = String, Header, description = "ETag of the product, or *")
     ),
     responses(
         (status = 200, description = "Returns a list of products", body = QResult<Product>, example = json!(QResult {
-            rows: Product {id: 1, name: "product 1".to_owned(), price: BigDecimal::from(10), i18n_name: Some("i18n".to_owned()), i18n_description: Some("description".to_owned()), description: Some("description".to_owned()), created_at: Utc::now().naive_utc(), store_id: Some(1)},
+            rows: Product {id: 1, name: "product 1".to_owned(), price: BigDecimal::from(10), description: Some("description".to_owned()), created_at: Utc::now().naive_utc(), store_id: Some(1), version: 1, updated_at: Utc::now(), deleted_at: None},
             error: None
         })),
+        (status = 404, description = "Product or store not found", body = ErrorJsonPayload),
+        (status = 422, description = "The store prices in another currency", body = ErrorJsonPayload),
+        (status = 412, description = "The product was modified since", body = ErrorJsonPayload),
+        (status = 428, description = "If-Match is missing", body = ErrorJsonPayload),
     )
 )]
 #[put("{prod_id}/store/{store_id}")]
-pub async fn attach_store(app_data: web::Data<AppData>, path: web::Path<(i32, i32)>) -> HttpResponse {
-    match app_data.pg_pool.get() {
-        Ok(conn) => product_repo::attach_store(conn, path.0, path.1).await,
-        _ => HttpResponse::InternalServerError().body("Internal Server Error"),
-    }
+pub async fn attach_store(
+    req: HttpRequest,
+    app_data: web::Data<AppData>,
+    path: web::Path<(i32, i32)>,
+    actor: Actor,
+) -> Result<HttpResponse, ApiError> {
+    let precondition = Precondition::from_request(&req)?;
+    let conn = app_data.pg_pool.get()?;
+    let product = product_repo::attach_store(conn, path.0, path.1, precondition, actor).await?;
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(product.version))
+        .json(QResult::new(product, None)))
 }
 
 /// Dettach category from product
@@ -350,33 +708,45 @@ pub async fn attach_store(app_data: web::Data<AppData>, path: web::Path<(i32, i3
     params(
         ("prod_id", description = "Unique id of products"),
         ("cat_id", description = "Unique id of category"),
+        ("If-Match" = String, Header, description = "ETag of the product, or *")
     ),
     responses(
         (status = 200, description = "Returns a list of products", body = QResult<ProductsCategories>, example = json!(QResult {
             rows: ProductsCategories { id: 1, category_id: 3, product_id: 5 },
             error: None
         })),
+        (status = 412, description = "The product was modified since", body = ErrorJsonPayload),
+        (status = 428, description = "If-Match is missing", body = ErrorJsonPayload),
     )
 )]
 #[delete("{prod_id}/category/{cat_id}")]
 pub async fn dettach_category(
+    req: HttpRequest,
     app_data: web::Data<AppData>,
     path: web::Path<(i32, i32)>,
-) -> HttpResponse {
-    match app_data.pg_pool.get() {
-        Ok(conn) => product_repo::dettach_category(conn, path.0, path.1).await,
-        _ => HttpResponse::InternalServerError().body("Internal Server Error"),
-    }
+    actor: Actor,
+) -> Result<HttpResponse, ApiError> {
+    let precondition = Precondition::from_request(&req)?;
+    let conn = app_data.pg_pool.get()?;
+    let product_category =
+        product_repo::dettach_category(conn, path.0, path.1, precondition, actor).await?;
+    Ok(HttpResponse::Ok().json(QResult::new(product_category, None)))
 }
 
 pub fn init_product_routes(cfg: &mut ServiceConfig) {
     cfg.app_data(JsonConfig::default().error_handler(json_error_handler));
     cfg.app_data(QueryConfig::default().error_handler(json_error_handler));
     cfg.service(get);
+    cfg.service(get_prices);
+    cfg.service(get_currency_prices);
+    cfg.service(set_currency_price);
+    cfg.service(delete_currency_price);
     cfg.service(get_many);
     cfg.service(post);
     cfg.service(update);
+    cfg.service(patch);
     cfg.service(delete);
+    cfg.service(restore);
     cfg.service(attach_category);
     cfg.service(dettach_category);
     cfg.service(attach_store);
@@ -1,28 +1,48 @@
 use crate::{
-    models::{CreateStoreDto, UpdateStoreDto, Store, QResult, PaginatedResult},
-    repos::{pagination::PaginationDto, store_repo},
+    models::{
+        ClosureDto, CreateStoreDto, PaginatedResult, PatchStoreDto, QResult, Store, StoreResult, UpdateStoreDto,
+    },
+    repos::{pagination::PaginationDto, store_repo, translation_repo},
     routes::{validate_order, SearchBy},
-    utils::{json_error_handler, AppData},
+    utils::{
+        content_language, etag, json_error_handler, paginated_response,
+        AcceptedLocales, Actor, ApiError, AppData, Precondition,
+    },
 };
 use actix_web::{
-    get, post, put, delete,
+    delete, get, patch, post, put,
     web::{self, Data, ServiceConfig},
-    HttpResponse
+    HttpRequest, HttpResponse,
 };
 use actix_web_validator::{Json, JsonConfig, Query, QueryConfig};
-use chrono::{DateTime, Local, NaiveDateTime, Utc};
+use chrono::{DateTime, FixedOffset, Utc};
 use serde::Deserialize;
-use utoipa::{ToSchema, IntoParams};
+use utoipa::{IntoParams, ToSchema};
 use validator::{Validate, ValidationError};
 
 use super::Stringify;
 
+/// Creation date bounds, RFC 3339 with an explicit offset
 #[derive(Deserialize, Validate, Debug, ToSchema, IntoParams)]
 pub struct DateFilter {
     #[schema(example = json!(Utc::now()))]
-    pub before: Option<DateTime<Local>>,
+    pub before: Option<DateTime<FixedOffset>>,
     #[schema(example = json!(Utc::now()))]
-    pub after: Option<DateTime<Local>>,
+    pub after: Option<DateTime<FixedOffset>>,
+}
+
+/// Keeps the stores open at that instant, each store in its own time zone
+#[derive(Deserialize, Validate, Debug, ToSchema, IntoParams)]
+pub struct OpenAt {
+    #[schema(example = json!(Utc::now()))]
+    pub open_at: Option<DateTime<FixedOffset>>,
+}
+
+#[derive(Deserialize, Validate, Debug, ToSchema, IntoParams)]
+pub struct StatusAt {
+    /// Defaults to now
+    #[schema(example = json!(Utc::now()))]
+    pub at: Option<DateTime<FixedOffset>>,
 }
 
 #[derive(Deserialize, Validate, Debug, ToSchema, IntoParams)]
@@ -62,53 +82,80 @@ impl Stringify for Option<StoresOrderBy> {
             "created_at DESC".to_owned()
         }
     }
+
+    fn sorting(&self) -> (&str, bool) {
+        match self {
+            Some(StoresOrderBy {
+                order: Some(order),
+                by: Some(by),
+            }) => (by, order == "DESC"),
+            _ => ("created_at", true),
+        }
+    }
 }
 
 impl DateFilter {
-    pub fn get_before(&self) -> NaiveDateTime {
+    pub fn get_before(&self) -> DateTime<Utc> {
         match self {
             DateFilter {
                 before: Some(before_date),
                 after: _,
-            } => before_date.naive_utc(),
-            _ => Utc::now().naive_utc(),
+            } => before_date.with_timezone(&Utc),
+            _ => Utc::now(),
         }
     }
 
-    pub fn get_after(&self) -> NaiveDateTime {
+    pub fn get_after(&self) -> DateTime<Utc> {
         match self {
             DateFilter {
                 after: Some(after_date),
                 before: _,
-            } => after_date.naive_utc(),
-            _ => DateTime::<Local>::default().naive_utc(),
+            } => after_date.with_timezone(&Utc),
+            _ => DateTime::<Utc>::default(),
         }
     }
 }
 
 /// Returns corresponding store with id=:store_id
+///
+/// Store and product text is translated after `Accept-Language`. Its products
+/// and opening status change apart from the store, so the response carries no
+/// `ETag`, write responses do
 #[utoipa::path(
     get, 
     path = "/store/{store_id}",
     params(
-        ("store_id", description = "Unique id of stores")
+        ("store_id", description = "Unique id of stores"),
+        ("Accept-Language" = Option<String>, Header, description = "Preferred locales, the default one without")
     ),
     responses(
-        (status = 200, description = "Returns the store with the corresponding id", body = QResult<Store>, example = json!(QResult {
-            rows: Store { id: 1, name: "Store 1".to_owned(), is_holiday: false, created_at: Utc::now().naive_utc(), prod_count: 2 },
+        (status = 200, description = "Returns the store with the corresponding id", body = QResult<Store>, headers(("content-language" = String, description = "Locales of the text")), example = json!(QResult {
+            rows: Store { id: 1, name: "Store 1".to_owned(), is_holiday: false, created_at: Utc::now(), prod_count: 2, timezone: "Europe/Paris".to_owned(), currency: "EUR".to_owned(), version: 1, updated_at: Utc::now(), deleted_at: None },
             error: None
         })),
+        (status = 404, description = "Store not found", body = ErrorJsonPayload),
     )
 )]
 #[get("{store_id}")]
-async fn get(app_data: Data<AppData>, store_id: web::Path<i32>) -> HttpResponse {
-    match app_data.pg_pool.get() {
-        Ok(conn) => store_repo::get_store(conn, store_id.into_inner()).await,
-        _ => HttpResponse::InternalServerError().body("Internal Server Error"),
-    }
+async fn get(
+    app_data: Data<AppData>,
+    store_id: web::Path<i32>,
+    locales: AcceptedLocales,
+) -> Result<HttpResponse, ApiError> {
+    let conn = app_data.pg_pool.get()?;
+    let store = store_repo::get_store(conn, store_id.into_inner()).await?;
+    let conn = app_data.pg_pool.get()?;
+    let (store, locales) = translation_repo::localize(conn, store, locales).await?;
+    let response = HttpResponse::Ok().json(QResult::new(store, None));
+    Ok(content_language(response, &locales))
 }
 
 /// Returns a paginated list of stores
+///
+/// Takes `cursor` instead of `page` for keyset pagination, ordered stores
+/// keep a stable position while new ones are created. Names are translated
+/// after `Accept-Language`. A `fuzzy=true` search tolerates typos in `name`,
+/// the stores come with their `similarity`, most similar first
 #[utoipa::path(
     get, 
     path = "/store",
@@ -116,41 +163,51 @@ async fn get(app_data: Data<AppData>, store_id: web::Path<i32>) -> HttpResponse
         PaginationDto,
         StoresOrderBy,
         SearchBy,
-        DateFilter
+        DateFilter,
+        OpenAt,
+        ("Accept-Language" = Option<String>, Header, description = "Preferred locales, the default one without")
     ),
     responses(
-        (status = 200, description = "Returns a list of products", body = PaginatedResult<Product>, example = json!(PaginatedResult {
+        (status = 200, description = "Returns a list of products", body = PaginatedResult<Product>, headers(("link" = String, description = "RFC 8288 first/prev/next/last links"), ("content-language" = String, description = "Locales of the text")), example = json!(PaginatedResult {
             per_page: 10,
             page: 1,
+            total: 2,
             total_pages: 1,
             result: vec![
-                Store { id: 1, name: "Store 1".to_owned(), is_holiday: false, created_at: Utc::now().naive_utc(), prod_count: 2 },
-                Store { id: 2, name: "Store 2".to_owned(), is_holiday: false, created_at: Utc::now().naive_utc(), prod_count: 0 }
+                Store { id: 1, name: "Store 1".to_owned(), is_holiday: false, created_at: Utc::now(), prod_count: 2, timezone: "Europe/Paris".to_owned(), currency: "EUR".to_owned(), version: 1, updated_at: Utc::now(), deleted_at: None },
+                Store { id: 2, name: "Store 2".to_owned(), is_holiday: false, created_at: Utc::now(), prod_count: 0, timezone: "Europe/Paris".to_owned(), currency: "EUR".to_owned(), version: 1, updated_at: Utc::now(), deleted_at: None }
             ]
         })),
     )
 )]
 #[get("")]
+#[allow(clippy::too_many_arguments)]
 async fn get_many(
     app_data: Data<AppData>,
+    req: HttpRequest,
     order: Query<StoresOrderBy>,
     pagination: Query<PaginationDto>,
     search_by: Query<SearchBy>,
     date: Query<DateFilter>,
-) -> HttpResponse {
-    match app_data.pg_pool.get() {
-        Ok(conn) => {
-            store_repo::get_many(
-                conn,
-                pagination.into_inner(),
-                order.into_inner().option(),
-                search_by.into_inner(),
-                date.into_inner(),
-            )
-            .await
-        }
-        _ => HttpResponse::InternalServerError().body("Internal Server Error"),
-    }
+    open_at: Query<OpenAt>,
+    locales: AcceptedLocales,
+) -> Result<HttpResponse, ApiError> {
+    let conn = app_data.pg_pool.get()?;
+    let stores = store_repo::get_many(
+        conn,
+        pagination.into_inner().limited(app_data.max_per_page)?,
+        order.into_inner().option(),
+        search_by.into_inner(),
+        date.into_inner(),
+        open_at
+            .into_inner()
+            .open_at
+            .map(|at| at.with_timezone(&Utc)),
+    )
+    .await?;
+    let conn = app_data.pg_pool.get()?;
+    let (stores, locales) = translation_repo::localize(conn, stores, locales).await?;
+    Ok(content_language(paginated_response(&req, stores), &locales))
 }
 
 /// Creates a new store
@@ -159,18 +216,23 @@ async fn get_many(
     path = "/store",
     request_body = CreateStoreDto,
     responses(
-        (status = 200, body = QResult<Store>, example = json!(QResult {
-            rows: Store { id: 2, name: "Store 2".to_owned(), is_holiday: false, created_at: Utc::now().naive_utc(), prod_count: 0 },
+        (status = 200, body = QResult<StoreResult>, example = json!(QResult {
+            rows: StoreResult { id: 2, name: "Store 2".to_owned(), is_holiday: false, created_at: Utc::now(), prod_count: 0, timezone: "Europe/Paris".to_owned(), currency: "EUR".to_owned(), version: 1, updated_at: Utc::now(), worktimes: vec![], closures: vec![], open_now: false, next_change_at: None, similarity: None },
             error: None
         })),
     )
 )]
 #[post("")]
-async fn post(app_data: Data<AppData>, store: Json<CreateStoreDto>) -> HttpResponse {
-    match app_data.pg_pool.get() {
-        Ok(conn) => store_repo::create_store(conn, store.into_inner()).await,
-        Err(_) => HttpResponse::InternalServerError().body("Internal Server Error"),
-    }
+async fn post(
+    app_data: Data<AppData>,
+    store: Json<CreateStoreDto>,
+    actor: Actor,
+) -> Result<HttpResponse, ApiError> {
+    let conn = app_data.pg_pool.get()?;
+    let store = store_repo::create_store(conn, store.into_inner(), actor).await?;
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(store.version))
+        .json(QResult::new(store, None)))
 }
 
 /// Edits store with corresponding ID
@@ -179,47 +241,134 @@ async fn post(app_data: Data<AppData>, store: Json<CreateStoreDto>) -> HttpRespo
     path = "/store/{id}",
     request_body = UpdateStoreDto,
     params (
-        ("id", description = "id of store")
+        ("id", description = "id of store"),
+        ("If-Match" = String, Header, description = "ETag of the store being replaced, or *")
     ),
     responses(
-        (status = 200, body = QResult<Store>, example = json!(QResult {
-            rows: Store { id: 2, name: "Store 2".to_owned(), is_holiday: false, created_at: Utc::now().naive_utc(), prod_count: 0 },
+        (status = 200, body = QResult<StoreResult>, example = json!(QResult {
+            rows: StoreResult { id: 2, name: "Store 2".to_owned(), is_holiday: false, created_at: Utc::now(), prod_count: 0, timezone: "Europe/Paris".to_owned(), currency: "EUR".to_owned(), version: 1, updated_at: Utc::now(), worktimes: vec![], closures: vec![], open_now: false, next_change_at: None, similarity: None },
             error: None
         })),
+        (status = 412, description = "The store was modified since", body = ErrorJsonPayload),
+        (status = 428, description = "If-Match is missing", body = ErrorJsonPayload),
     )
 )]
 #[put("{id}")]
 async fn update(
+    req: HttpRequest,
     app_data: Data<AppData>,
     store_id: web::Path<i32>,
     store: Json<UpdateStoreDto>,
-) -> HttpResponse {
-    match app_data.pg_pool.get() {
-        Ok(conn) => store_repo::update_store(conn, store_id.into_inner(), store.into_inner()).await,
-        _ => HttpResponse::InternalServerError().body("InternalServerError"),
-    }
+    actor: Actor,
+) -> Result<HttpResponse, ApiError> {
+    let precondition = Precondition::from_request(&req)?;
+    let conn = app_data.pg_pool.get()?;
+    let store = store_repo::update_store(
+        conn,
+        store_id.into_inner(),
+        store.into_inner().into(),
+        precondition,
+        actor,
+    )
+    .await?;
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(store.version))
+        .json(QResult::new(store, None)))
 }
 
-/// Deletes store with corresponding id
+/// Partially edits store with corresponding ID, JSON Merge Patch semantics
+#[utoipa::path(
+    patch, 
+    path = "/store/{id}",
+    request_body(content = PatchStoreDto, content_type = "application/merge-patch+json"),
+    params (
+        ("id", description = "id of store"),
+        ("If-Match" = String, Header, description = "ETag of the store being edited, or *")
+    ),
+    responses(
+        (status = 200, body = QResult<StoreResult>),
+        (status = 404, description = "Store not found", body = ErrorJsonPayload),
+        (status = 412, description = "The store was modified since", body = ErrorJsonPayload),
+        (status = 428, description = "If-Match is missing", body = ErrorJsonPayload),
+    )
+)]
+#[patch("{id}")]
+async fn patch(
+    req: HttpRequest,
+    app_data: Data<AppData>,
+    store_id: web::Path<i32>,
+    store: Json<PatchStoreDto>,
+    actor: Actor,
+) -> Result<HttpResponse, ApiError> {
+    let precondition = Precondition::from_request(&req)?;
+    let conn = app_data.pg_pool.get()?;
+    let store = store_repo::update_store(
+        conn,
+        store_id.into_inner(),
+        store.into_inner(),
+        precondition,
+        actor,
+    )
+    .await?;
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(store.version))
+        .json(QResult::new(store, None)))
+}
+
+/// Deletes store with corresponding id, its worktimes and closures are kept for a restore
 #[utoipa::path(
     delete, 
     path = "/store/{id}",
     params(
-        ("id", description = "Unique id of store")
+        ("id", description = "Unique id of store"),
+        ("If-Match" = String, Header, description = "ETag of the store being deleted, or *")
     ),
     responses(
         (status = 200, description = "return deleted store", body = QResult<Store>, example = json!(QResult {
-            rows: Store { id: 2, name: "Store 2".to_owned(), is_holiday: false, created_at: Utc::now().naive_utc(), prod_count: 0 },
+            rows: Store { id: 2, name: "Store 2".to_owned(), is_holiday: false, created_at: Utc::now(), prod_count: 0, timezone: "Europe/Paris".to_owned(), currency: "EUR".to_owned(), version: 1, updated_at: Utc::now(), deleted_at: None },
             error: None
         })),
+        (status = 412, description = "The store was modified since", body = ErrorJsonPayload),
+        (status = 428, description = "If-Match is missing", body = ErrorJsonPayload),
     )
 )]
 #[delete("{store_id}")]
-async fn delete(app_data: Data<AppData>, store_id: web::Path<i32>) -> HttpResponse {
-    match app_data.pg_pool.get() {
-        Ok(conn) => store_repo::delete_store(conn, store_id.into_inner()).await,
-        _ => HttpResponse::InternalServerError().body("Internal Server Error"),
-    }
+async fn delete(
+    req: HttpRequest,
+    app_data: Data<AppData>,
+    store_id: web::Path<i32>,
+    actor: Actor,
+) -> Result<HttpResponse, ApiError> {
+    let precondition = Precondition::from_request(&req)?;
+    let conn = app_data.pg_pool.get()?;
+    let store =
+        store_repo::delete_store(conn, store_id.into_inner(), precondition, actor).await?;
+    Ok(HttpResponse::Ok().json(QResult::new(store, None)))
+}
+
+/// Restores a deleted store
+#[utoipa::path(
+    post,
+    path = "/store/{id}/restore",
+    params(
+        ("id", description = "Unique id of stores")
+    ),
+    responses(
+        (status = 200, description = "Returns the restored store", body = QResult<StoreResult>),
+        (status = 404, description = "Store not found or purged", body = ErrorJsonPayload),
+    )
+)]
+#[post("{id}/restore")]
+async fn restore(
+    app_data: Data<AppData>,
+    store_id: web::Path<i32>,
+    actor: Actor,
+) -> Result<HttpResponse, ApiError> {
+    let conn = app_data.pg_pool.get()?;
+    let store = store_repo::restore_store(conn, store_id.into_inner(), actor).await?;
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(store.version))
+        .json(QResult::new(store, None)))
 }
 
 /// get stores product count (never used in application)
@@ -237,11 +386,200 @@ async fn delete(app_data: Data<AppData>, store_id: web::Path<i32>) -> HttpRespon
     )
 )]
 #[get("{store_id}/count")]
-async fn product_count(app_data: Data<AppData>, store_id: web::Path<i32>) -> HttpResponse {
-    match app_data.pg_pool.get() {
-        Ok(conn) => store_repo::product_count(conn, store_id.into_inner()).await,
-        _ => HttpResponse::InternalServerError().body("Internal Server Error"),
-    }
+async fn product_count(
+    app_data: Data<AppData>,
+    store_id: web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    let conn = app_data.pg_pool.get()?;
+    let count = store_repo::product_count(conn, store_id.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(QResult::new(count, None)))
+}
+
+/// Tells whether the store is open at `at` and when that changes next
+#[utoipa::path(
+    get, 
+    path = "/store/{id}/status",
+    params(
+        ("id", description = "Unique id of store"),
+        StatusAt
+    ),
+    responses(
+        (status = 200, body = QResult<StoreStatus>),
+        (status = 404, description = "Store not found", body = ErrorJsonPayload),
+    )
+)]
+#[get("{store_id}/status")]
+async fn status(
+    app_data: Data<AppData>,
+    store_id: web::Path<i32>,
+    at: Query<StatusAt>,
+) -> Result<HttpResponse, ApiError> {
+    let conn = app_data.pg_pool.get()?;
+    let at = at
+        .into_inner()
+        .at
+        .map_or_else(Utc::now, |at| at.with_timezone(&Utc));
+    let status = store_repo::store_status(conn, store_id.into_inner(), at).await?;
+    Ok(HttpResponse::Ok().json(QResult::new(status, None)))
+}
+
+/// Recomputes every store product count from products, returns how many were off
+#[utoipa::path(
+    post, 
+    path = "/store/recount",
+    responses(
+        (status = 200, description = "Number of stores whose count was corrected", body = QResult<i32>, example = json!(QResult {
+            rows: 1,
+            error: None
+        })),
+    )
+)]
+#[post("recount")]
+async fn recount(app_data: Data<AppData>, actor: Actor) -> Result<HttpResponse, ApiError> {
+    let conn = app_data.pg_pool.get()?;
+    let count = store_repo::recount_products(conn, actor).await?;
+    Ok(HttpResponse::Ok().json(QResult::new(count, None)))
+}
+
+/// Lists every closure of the store, past ones included
+#[utoipa::path(
+    get, 
+    path = "/store/{id}/closures",
+    params(
+        ("id", description = "Unique id of store")
+    ),
+    responses(
+        (status = 200, body = QResult<Vec<StoreClosure>>),
+        (status = 404, description = "Store not found", body = ErrorJsonPayload),
+    )
+)]
+#[get("{store_id}/closures")]
+async fn get_closures(
+    app_data: Data<AppData>,
+    store_id: web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    let conn = app_data.pg_pool.get()?;
+    let closures = store_repo::get_closures(conn, store_id.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(QResult::new(closures, None)))
+}
+
+/// Returns the closure with id=:closure_id of the store
+#[utoipa::path(
+    get, 
+    path = "/store/{id}/closures/{closure_id}",
+    params(
+        ("id", description = "Unique id of store"),
+        ("closure_id", description = "Unique id of closure"),
+    ),
+    responses(
+        (status = 200, body = QResult<StoreClosure>),
+        (status = 404, description = "Closure not found", body = ErrorJsonPayload),
+    )
+)]
+#[get("{store_id}/closures/{closure_id}")]
+async fn get_closure(
+    app_data: Data<AppData>,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse, ApiError> {
+    let conn = app_data.pg_pool.get()?;
+    let closure = store_repo::get_closure(conn, path.0, path.1).await?;
+    Ok(HttpResponse::Ok().json(QResult::new(closure, None)))
+}
+
+/// Closes the store or sets special hours on a range of dates
+#[utoipa::path(
+    post, 
+    path = "/store/{id}/closures",
+    request_body = ClosureDto,
+    params(
+        ("id", description = "Unique id of store")
+    ),
+    responses(
+        (status = 200, body = QResult<StoreClosure>),
+        (status = 404, description = "Store not found", body = ErrorJsonPayload),
+        (status = 422, description = "Invalid hours or dates overlapping another closure", body = ErrorJsonPayload),
+    )
+)]
+#[post("{store_id}/closures")]
+async fn post_closure(
+    app_data: Data<AppData>,
+    store_id: web::Path<i32>,
+    closure: Json<ClosureDto>,
+    actor: Actor,
+) -> Result<HttpResponse, ApiError> {
+    let conn = app_data.pg_pool.get()?;
+    let closure =
+        store_repo::add_closure(conn, store_id.into_inner(), closure.into_inner(), actor).await?;
+    Ok(HttpResponse::Ok().json(QResult::new(closure, None)))
+}
+
+/// Replaces the dates and hours of a closure
+#[utoipa::path(
+    put, 
+    path = "/store/{id}/closures/{closure_id}",
+    request_body = ClosureDto,
+    params(
+        ("id", description = "Unique id of store"),
+        ("closure_id", description = "Unique id of closure"),
+        ("If-Match" = String, Header, description = "ETag of the store, or *")
+    ),
+    responses(
+        (status = 200, body = QResult<StoreClosure>),
+        (status = 404, description = "Closure not found", body = ErrorJsonPayload),
+        (status = 412, description = "The store was modified since", body = ErrorJsonPayload),
+        (status = 422, description = "Invalid hours or dates overlapping another closure", body = ErrorJsonPayload),
+        (status = 428, description = "If-Match is missing", body = ErrorJsonPayload),
+    )
+)]
+#[put("{store_id}/closures/{closure_id}")]
+async fn update_closure(
+    req: HttpRequest,
+    app_data: Data<AppData>,
+    path: web::Path<(i32, i32)>,
+    closure: Json<ClosureDto>,
+    actor: Actor,
+) -> Result<HttpResponse, ApiError> {
+    let precondition = Precondition::from_request(&req)?;
+    let conn = app_data.pg_pool.get()?;
+    let closure = store_repo::update_closure(
+        conn,
+        path.0,
+        path.1,
+        closure.into_inner(),
+        precondition,
+        actor,
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(QResult::new(closure, None)))
+}
+
+/// Deletes a closure, the store follows its worktimes again on those dates
+#[utoipa::path(
+    delete, 
+    path = "/store/{id}/closures/{closure_id}",
+    params(
+        ("id", description = "Unique id of store"),
+        ("closure_id", description = "Unique id of closure"),
+        ("If-Match" = String, Header, description = "ETag of the store, or *")
+    ),
+    responses(
+        (status = 200, description = "return deleted closure", body = QResult<StoreClosure>),
+        (status = 404, description = "Closure not found", body = ErrorJsonPayload),
+        (status = 412, description = "The store was modified since", body = ErrorJsonPayload),
+        (status = 428, description = "If-Match is missing", body = ErrorJsonPayload),
+    )
+)]
+#[delete("{store_id}/closures/{closure_id}")]
+async fn delete_closure(
+    req: HttpRequest,
+    app_data: Data<AppData>,
+    path: web::Path<(i32, i32)>,
+    actor: Actor,
+) -> Result<HttpResponse, ApiError> {
+    let precondition = Precondition::from_request(&req)?;
+    let conn = app_data.pg_pool.get()?;
+    let closure = store_repo::delete_closure(conn, path.0, path.1, precondition, actor).await?;
+    Ok(HttpResponse::Ok().json(QResult::new(closure, None)))
 }
 
 pub fn init_store_routes(cfg: &mut ServiceConfig) {
@@ -251,8 +589,17 @@ pub fn init_store_routes(cfg: &mut ServiceConfig) {
     cfg.service(get_many);
     cfg.service(post);
     cfg.service(update);
+    cfg.service(patch);
     cfg.service(delete);
+    cfg.service(restore);
     cfg.service(product_count);
+    cfg.service(status);
+    cfg.service(recount);
+    cfg.service(get_closures);
+    cfg.service(get_closure);
+    cfg.service(post_closure);
+    cfg.service(update_closure);
+    cfg.service(delete_closure);
 }
 
 fn validate_order_by(order_by: &str) -> Result<(), ValidationError> {
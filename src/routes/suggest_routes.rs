@@ -0,0 +1,66 @@
+use crate::{
+    models::{QResult, Suggestion},
+    repos::suggest_repo,
+    utils::{json_error_handler, ApiError, AppData},
+};
+use actix_web::{
+    get,
+    web::{Data, ServiceConfig},
+    HttpResponse,
+};
+use actix_web_validator::{Query, QueryConfig};
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+
+/// Suggestions sent when `limit` is not given
+const DEFAULT_LIMIT: i64 = 10;
+
+#[derive(Deserialize, Validate, Debug, ToSchema, IntoParams)]
+pub struct SuggestQuery {
+    /// Start of the name typed so far
+    #[validate(length(min = 1, max = 256))]
+    #[schema(example = "écom")]
+    pub q: String,
+    #[validate(range(min = 1, max = 50))]
+    #[schema(example = 10)]
+    pub limit: Option<i64>,
+}
+
+/// Suggests products, categories and stores as a name is typed
+///
+/// Names starting with `q`, accents and case aside, whatever their type. An
+/// exact name comes first, then the shortest ones
+#[utoipa::path(
+    get,
+    path = "/suggest",
+    params(
+        SuggestQuery,
+    ),
+    responses(
+        (status = 200, body = QResult<Vec<Suggestion>>, example = json!(QResult {
+            rows: vec![
+                Suggestion { entity_type: "category".to_owned(), id: 3, name: "Écologie".to_owned() },
+                Suggestion { entity_type: "product".to_owned(), id: 12, name: "Écoméditique bio".to_owned() },
+                Suggestion { entity_type: "store".to_owned(), id: 2, name: "Écoméditique Lyon".to_owned() },
+            ],
+            error: None
+        })),
+        (status = 422, description = "Missing or too long `q`, `limit` out of range", body = ErrorJsonPayload),
+    )
+)]
+#[get("")]
+async fn get(
+    app_data: Data<AppData>,
+    query: Query<SuggestQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let SuggestQuery { q, limit } = query.into_inner();
+    let conn = app_data.pg_pool.get()?;
+    let suggestions = suggest_repo::suggest(conn, q, limit.unwrap_or(DEFAULT_LIMIT)).await?;
+    Ok(HttpResponse::Ok().json(QResult::new(suggestions, None)))
+}
+
+pub fn init_suggest_routes(cfg: &mut ServiceConfig) {
+    cfg.app_data(QueryConfig::default().error_handler(json_error_handler));
+    cfg.service(get);
+}
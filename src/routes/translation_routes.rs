@@ -0,0 +1,213 @@
+use crate::{
+    models::{
+        validate_locale, PaginatedResult, QResult, Translation, TranslationDto,
+        UpdateTranslationDto, TRANSLATED_ENTITIES,
+    },
+    repos::{pagination::PaginationDto, translation_repo},
+    utils::{json_error_handler, paginated_response, Actor, ApiError, AppData, Precondition},
+};
+use actix_web::{
+    delete, get, post, put,
+    web::{self, Data, ServiceConfig},
+    HttpRequest, HttpResponse,
+};
+use actix_web_validator::{Json, JsonConfig, Query, QueryConfig};
+use chrono::Utc;
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+use validator::{Validate, ValidationError};
+
+/// Narrows the translations down to one entity type, one entity or one locale
+#[derive(Deserialize, Validate, Debug, ToSchema, IntoParams)]
+pub struct TranslationFilter {
+    #[validate(custom = "validate_entity")]
+    #[schema(example = "product")]
+    pub entity: Option<String>,
+    #[validate(range(min = 1))]
+    #[schema(example = 1)]
+    pub entity_id: Option<i32>,
+    #[validate(custom = "validate_locale")]
+    #[schema(example = "fr-CA")]
+    pub locale: Option<String>,
+}
+
+fn validate_entity(entity: &str) -> Result<(), ValidationError> {
+    if TRANSLATED_ENTITIES.contains(&entity) {
+        return Ok(());
+    }
+    Err(ValidationError::new("unknown entity"))
+}
+
+/// Lists the translations, oldest first
+#[utoipa::path(
+    get,
+    path = "/translation",
+    params(
+        PaginationDto,
+        TranslationFilter,
+    ),
+    responses(
+        (status = 200, description = "Returns a page of translations", body = PaginatedResult<Translation>, headers(("link" = String, description = "RFC 8288 first/prev/next/last links")), example = json!(PaginatedResult {
+            per_page: 10,
+            page: 1,
+            total: 1,
+            total_pages: 1,
+            result: vec![Translation {
+                id: 1,
+                entity_type: "product".to_owned(),
+                entity_id: 1,
+                field: "name".to_owned(),
+                locale: "fr-CA".to_owned(),
+                value: "Pomme".to_owned(),
+                updated_at: Utc::now(),
+            }]
+        })),
+    )
+)]
+#[get("")]
+async fn get_many(
+    app_data: Data<AppData>,
+    req: HttpRequest,
+    pagination: Query<PaginationDto>,
+    filter: Query<TranslationFilter>,
+) -> Result<HttpResponse, ApiError> {
+    let conn = app_data.pg_pool.get()?;
+    let translations = translation_repo::get_many(
+        conn,
+        pagination.into_inner().limited(app_data.max_per_page)?,
+        filter.into_inner(),
+    )
+    .await?;
+    Ok(paginated_response(&req, translations))
+}
+
+/// Returns the translation with id=:id
+#[utoipa::path(
+    get,
+    path = "/translation/{id}",
+    params(
+        ("id", description = "Unique id of translation")
+    ),
+    responses(
+        (status = 200, body = QResult<Translation>),
+        (status = 404, description = "Translation not found", body = ErrorJsonPayload),
+    )
+)]
+#[get("{id}")]
+async fn get(
+    app_data: Data<AppData>,
+    translation_id: web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    let conn = app_data.pg_pool.get()?;
+    let translation = translation_repo::get_translation(conn, translation_id.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(QResult::new(translation, None)))
+}
+
+/// Translates a field of a product, category or store
+///
+/// Product names and descriptions, category and store names can be translated.
+/// Reads of the entity pick it up through `Accept-Language`
+#[utoipa::path(
+    post,
+    path = "/translation",
+    request_body = TranslationDto,
+    responses(
+        (status = 201, body = QResult<Translation>),
+        (status = 404, description = "No such entity to translate", body = ErrorJsonPayload),
+        (status = 409, description = "The field already has a translation in the locale", body = ErrorJsonPayload),
+        (status = 422, description = "Untranslatable field, invalid locale or value", body = ErrorJsonPayload),
+    )
+)]
+#[post("")]
+async fn post(
+    app_data: Data<AppData>,
+    translation: Json<TranslationDto>,
+    actor: Actor,
+) -> Result<HttpResponse, ApiError> {
+    let conn = app_data.pg_pool.get()?;
+    let translation =
+        translation_repo::add_translation(conn, translation.into_inner(), actor).await?;
+    Ok(HttpResponse::Created().json(QResult::new(translation, None)))
+}
+
+/// Changes the text of a translation
+#[utoipa::path(
+    put,
+    path = "/translation/{id}",
+    request_body = UpdateTranslationDto,
+    params(
+        ("id", description = "Unique id of translation"),
+        ("If-Match" = String, Header, description = "ETag of the translated entity, or *")
+    ),
+    responses(
+        (status = 200, body = QResult<Translation>),
+        (status = 404, description = "Translation not found", body = ErrorJsonPayload),
+        (status = 412, description = "The translated entity was modified since", body = ErrorJsonPayload),
+        (status = 422, description = "Value empty or too long for the field", body = ErrorJsonPayload),
+        (status = 428, description = "If-Match is missing", body = ErrorJsonPayload),
+    )
+)]
+#[put("{id}")]
+async fn update(
+    req: HttpRequest,
+    app_data: Data<AppData>,
+    translation_id: web::Path<i32>,
+    translation: Json<UpdateTranslationDto>,
+    actor: Actor,
+) -> Result<HttpResponse, ApiError> {
+    let precondition = Precondition::from_request(&req)?;
+    let conn = app_data.pg_pool.get()?;
+    let translation = translation_repo::update_translation(
+        conn,
+        translation_id.into_inner(),
+        translation.into_inner(),
+        precondition,
+        actor,
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(QResult::new(translation, None)))
+}
+
+/// Deletes a translation, the field falls back on the default locale
+#[utoipa::path(
+    delete,
+    path = "/translation/{id}",
+    params(
+        ("id", description = "Unique id of translation"),
+        ("If-Match" = String, Header, description = "ETag of the translated entity, or *")
+    ),
+    responses(
+        (status = 200, body = QResult<Translation>),
+        (status = 404, description = "Translation not found", body = ErrorJsonPayload),
+        (status = 412, description = "The translated entity was modified since", body = ErrorJsonPayload),
+        (status = 428, description = "If-Match is missing", body = ErrorJsonPayload),
+    )
+)]
+#[delete("{id}")]
+async fn delete(
+    req: HttpRequest,
+    app_data: Data<AppData>,
+    translation_id: web::Path<i32>,
+    actor: Actor,
+) -> Result<HttpResponse, ApiError> {
+    let precondition = Precondition::from_request(&req)?;
+    let conn = app_data.pg_pool.get()?;
+    let translation = translation_repo::delete_translation(
+        conn,
+        translation_id.into_inner(),
+        precondition,
+        actor,
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(QResult::new(translation, None)))
+}
+
+pub fn init_translation_routes(cfg: &mut ServiceConfig) {
+    cfg.app_data(JsonConfig::default().error_handler(json_error_handler));
+    cfg.app_data(QueryConfig::default().error_handler(json_error_handler));
+    cfg.service(get_many);
+    cfg.service(get);
+    cfg.service(post);
+    cfg.service(update);
+    cfg.service(delete);
+}
@@ -1,23 +1,78 @@
 // @generated automatically by Diesel CLI.
 
+pub mod sql_types {
+    #[derive(diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "tsvector", schema = "pg_catalog"))]
+    pub struct Tsvector;
+}
+
+diesel::table! {
+    audit_events (id) {
+        id -> Int4,
+        entity_type -> Varchar,
+        entity_id -> Int4,
+        action -> Varchar,
+        actor -> Varchar,
+        created_at -> Timestamptz,
+        before -> Nullable<Jsonb>,
+        after -> Nullable<Jsonb>,
+    }
+}
+
 diesel::table! {
     categories (id) {
         id -> Int4,
         name -> Varchar,
         created_at -> Timestamp,
+        version -> Int4,
+        updated_at -> Timestamptz,
+        deleted_at -> Nullable<Timestamptz>,
     }
 }
 
 diesel::table! {
+    exchange_rates (id) {
+        id -> Int4,
+        from_currency -> Varchar,
+        to_currency -> Varchar,
+        rate -> Numeric,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::Tsvector;
+
     products (id) {
         id -> Int4,
         name -> Varchar,
-        i18n_name -> Nullable<Varchar>,
         price -> Numeric,
         description -> Nullable<Text>,
-        i18n_description -> Nullable<Text>,
         created_at -> Timestamp,
         store_id -> Nullable<Int4>,
+        version -> Int4,
+        updated_at -> Timestamptz,
+        deleted_at -> Nullable<Timestamptz>,
+        search_vector -> Tsvector,
+    }
+}
+
+diesel::table! {
+    product_currency_prices (product_id, currency) {
+        product_id -> Int4,
+        currency -> Varchar,
+        price -> Numeric,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    product_prices (id) {
+        id -> Int4,
+        product_id -> Int4,
+        price -> Numeric,
+        effective_from -> Timestamptz,
     }
 }
 
@@ -29,13 +84,45 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    store_closures (id) {
+        id -> Int4,
+        store_id -> Int4,
+        starts_on -> Date,
+        ends_on -> Date,
+        am_open -> Nullable<Time>,
+        am_close -> Nullable<Time>,
+        pm_open -> Nullable<Time>,
+        pm_close -> Nullable<Time>,
+        reason -> Nullable<Varchar>,
+        created_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     stores (id) {
         id -> Int4,
         name -> Varchar,
         is_holiday -> Bool,
-        created_at -> Timestamp,
+        created_at -> Timestamptz,
         prod_count -> Int4,
+        timezone -> Varchar,
+        version -> Int4,
+        updated_at -> Timestamptz,
+        deleted_at -> Nullable<Timestamptz>,
+        currency -> Varchar,
+    }
+}
+
+diesel::table! {
+    translations (id) {
+        id -> Int4,
+        entity_type -> Varchar,
+        entity_id -> Int4,
+        field -> Varchar,
+        locale -> Varchar,
+        value -> Text,
+        updated_at -> Timestamptz,
     }
 }
 
@@ -44,22 +131,31 @@ diesel::table! {
         id -> Int4,
         day_id -> Int4,
         store_id -> Int4,
-        am_open -> Nullable<Varchar>,
-        am_close -> Nullable<Varchar>,
-        pm_open -> Nullable<Varchar>,
-        pm_close -> Nullable<Varchar>,
+        am_open -> Nullable<Time>,
+        am_close -> Nullable<Time>,
+        pm_open -> Nullable<Time>,
+        pm_close -> Nullable<Time>,
     }
 }
 
 diesel::joinable!(products -> stores (store_id));
 diesel::joinable!(products_categories -> categories (category_id));
 diesel::joinable!(products_categories -> products (product_id));
+diesel::joinable!(product_currency_prices -> products (product_id));
+diesel::joinable!(product_prices -> products (product_id));
+diesel::joinable!(store_closures -> stores (store_id));
 diesel::joinable!(worktimes -> stores (store_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    audit_events,
     categories,
+    exchange_rates,
+    product_currency_prices,
+    product_prices,
     products,
     products_categories,
+    store_closures,
     stores,
+    translations,
     worktimes,
 );
@@ -0,0 +1,29 @@
+use super::ApiError;
+use actix_web::{dev::Payload, FromRequest, HttpRequest};
+use std::future::{ready, Ready};
+
+/// Header naming who performs a write, set by whatever sits in front of the API
+pub const ACTOR_HEADER: &str = "X-Actor";
+
+/// Author of a write as recorded in the audit log, `anonymous` without the header
+#[derive(Debug, Clone)]
+pub struct Actor(pub String);
+
+impl FromRequest for Actor {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, ApiError>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let actor = match req.headers().get(ACTOR_HEADER) {
+            None => Ok(Actor("anonymous".to_owned())),
+            Some(value) => match value.to_str().map(str::trim) {
+                Ok(name) if !name.is_empty() && name.len() <= 256 => Ok(Actor(name.to_owned())),
+                _ => Err(ApiError::Validation {
+                    message: "Invalid X-Actor header".to_owned(),
+                    fields: vec![ACTOR_HEADER.to_owned()],
+                }),
+            },
+        };
+        ready(actor)
+    }
+}
@@ -9,6 +9,8 @@ pub type Connection = PooledConnection<ConnectionManager<PgConnection>>;
 #[derive(Clone)]
 pub struct AppData {
     pub pg_pool: Pool<ConnectionManager<PgConnection>>,
+    /// Largest `per_page` a listing accepts
+    pub max_per_page: i64,
 }
 
 pub fn create_conn_pool(config: &Config) -> AppData {
@@ -18,5 +20,8 @@ pub fn create_conn_pool(config: &Config) -> AppData {
         .max_size(*config.get_pool_size())
         .build(manager)
         .expect("Failed to connect to database!");
-    AppData { pg_pool: pool }
+    AppData {
+        pg_pool: pool,
+        max_per_page: *config.get_max_per_page(),
+    }
 }
@@ -1,61 +1,168 @@
 use actix_web::{
-    error::InternalError, http::StatusCode, HttpRequest, HttpResponse, HttpResponseBuilder,
+    error::{BlockingError, JsonPayloadError},
+    http::StatusCode,
+    HttpRequest, HttpResponse, HttpResponseBuilder,
 };
 use actix_web_validator::Error;
+use derive_more::Display;
+use diesel::{
+    r2d2::PoolError,
+    result::{DatabaseErrorKind, Error as DieselError},
+};
 use serde::Serialize;
-use std::fmt::Display;
 use utoipa::ToSchema;
 
+/// JSON envelope returned for every failed request
 #[derive(Serialize, ToSchema)]
-pub struct ValidationErrorJsonPayload {
-    #[schema(example = "validation error")]
+pub struct ErrorJsonPayload {
+    #[schema(example = "validation_error")]
+    pub code: String,
+    #[schema(example = "Validation error")]
     pub message: String,
     #[schema(example = json!(vec!["name", "price"]))]
     pub fields: Vec<String>,
 }
 
-impl From<&validator::ValidationErrors> for ValidationErrorJsonPayload {
-    fn from(error: &validator::ValidationErrors) -> Self {
-        ValidationErrorJsonPayload {
-            message: "Validation error".to_owned(),
-            fields: error
-                .field_errors()
-                .iter()
-                .map(|(field, _)| field.to_string())
-                .collect(),
+#[derive(Debug, Display)]
+pub enum ApiError {
+    #[display(fmt = "{}", _0)]
+    BadRequest(String),
+    #[display(fmt = "{}", _0)]
+    NotFound(String),
+    #[display(fmt = "{}", _0)]
+    UniqueViolation(String),
+    #[display(fmt = "{}", _0)]
+    ForeignKeyViolation(String),
+    #[display(fmt = "{}", message)]
+    Validation {
+        message: String,
+        fields: Vec<String>,
+    },
+    #[display(fmt = "{}", _0)]
+    PreconditionFailed(String),
+    #[display(fmt = "If-Match header is required")]
+    PreconditionRequired,
+    #[display(fmt = "Database connection pool exhausted")]
+    PoolExhausted,
+    #[display(fmt = "Internal server error")]
+    Internal(String),
+}
+
+impl ApiError {
+    /// Stable machine-readable code the frontend can branch on
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::UniqueViolation(_) => "unique_violation",
+            ApiError::ForeignKeyViolation(_) => "foreign_key_violation",
+            ApiError::Validation { .. } => "validation_error",
+            ApiError::PreconditionFailed(_) => "precondition_failed",
+            ApiError::PreconditionRequired => "precondition_required",
+            ApiError::PoolExhausted => "pool_exhausted",
+            ApiError::Internal(_) => "internal_error",
         }
     }
-}
 
-pub fn json_error_handler<'r>(errors: Error, _req: &'r HttpRequest) -> actix_web::Error {
-    let json_error = match &errors {
-        Error::Validate(error) => ValidationErrorJsonPayload::from(error),
-        _ => ValidationErrorJsonPayload {
-            message: errors.to_string(),
+    pub fn validation(message: &str) -> Self {
+        ApiError::Validation {
+            message: message.to_owned(),
             fields: Vec::new(),
-        },
-    };
-    InternalError::from_response(errors, HttpResponse::BadRequest().json(json_error)).into()
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl actix_web::error::ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::UniqueViolation(_) | ApiError::ForeignKeyViolation(_) => StatusCode::CONFLICT,
+            ApiError::Validation { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            ApiError::PreconditionRequired => StatusCode::PRECONDITION_REQUIRED,
+            ApiError::PoolExhausted => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        // The detail stays out of the response, the logs are the only trace of it
+        if let ApiError::Internal(detail) = self {
+            log::error!("{}", detail);
+        }
+        let fields = match self {
+            ApiError::Validation { fields, .. } => fields.clone(),
+            _ => Vec::new(),
+        };
+        HttpResponseBuilder::new(self.status_code()).json(ErrorJsonPayload {
+            code: self.code().to_owned(),
+            message: self.to_string(),
+            fields,
+        })
+    }
 }
 
-#[derive(Debug, Serialize)]
-pub struct NotFoundError {
-    error: String,
+impl From<DieselError> for ApiError {
+    fn from(error: DieselError) -> Self {
+        match error {
+            DieselError::NotFound => ApiError::NotFound("Resource not found".to_owned()),
+            DieselError::DatabaseError(kind, info) => match kind {
+                DatabaseErrorKind::UniqueViolation => {
+                    ApiError::UniqueViolation(info.message().to_owned())
+                }
+                DatabaseErrorKind::ForeignKeyViolation => {
+                    ApiError::ForeignKeyViolation(info.message().to_owned())
+                }
+                DatabaseErrorKind::NotNullViolation | DatabaseErrorKind::CheckViolation => {
+                    ApiError::Validation {
+                        message: info.message().to_owned(),
+                        fields: info.column_name().into_iter().map(str::to_owned).collect(),
+                    }
+                }
+                _ => ApiError::Internal(info.message().to_owned()),
+            },
+            err => ApiError::Internal(err.to_string()),
+        }
+    }
 }
 
-impl Display for NotFoundError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.error)
+impl From<BlockingError> for ApiError {
+    fn from(error: BlockingError) -> Self {
+        ApiError::Internal(error.to_string())
     }
 }
 
-impl std::error::Error for NotFoundError {}
-impl actix_web::error::ResponseError for NotFoundError {
-    fn status_code(&self) -> StatusCode {
-        StatusCode::BAD_REQUEST
+impl From<PoolError> for ApiError {
+    fn from(_: PoolError) -> Self {
+        ApiError::PoolExhausted
     }
+}
 
-    fn error_response(&self) -> HttpResponse {
-        HttpResponseBuilder::new(StatusCode::BAD_REQUEST).json(self)
+impl From<&validator::ValidationErrors> for ApiError {
+    fn from(error: &validator::ValidationErrors) -> Self {
+        ApiError::Validation {
+            message: "Validation error".to_owned(),
+            fields: error
+                .field_errors()
+                .keys()
+                .map(|field| field.to_string())
+                .collect(),
+        }
+    }
+}
+
+/// A body that is not JSON at all is a 400, one that does not fit the DTO a 422
+pub fn json_error_handler(errors: Error, _req: &HttpRequest) -> actix_web::Error {
+    match &errors {
+        Error::Validate(error) => ApiError::from(error),
+        Error::JsonPayloadError(JsonPayloadError::Deserialize(error)) if error.is_data() => {
+            ApiError::validation(&errors.to_string())
+        }
+        Error::JsonPayloadError(_) => ApiError::BadRequest(errors.to_string()),
+        _ => ApiError::validation(&errors.to_string()),
     }
+    .into()
 }
@@ -0,0 +1,70 @@
+use super::ApiError;
+use actix_web::{
+    http::header::{self, ETag, EntityTag, Header, IfMatch, IfNoneMatch},
+    HttpRequest, HttpResponse,
+};
+use serde::Serialize;
+
+/// Strong `ETag` of a row version
+pub fn etag(version: i32) -> ETag {
+    ETag(EntityTag::new_strong(version.to_string()))
+}
+
+/// `If-Match` condition of a write to a versioned row
+#[derive(Debug, Clone)]
+pub enum Precondition {
+    Any,
+    Versions(Vec<i32>),
+}
+
+impl Precondition {
+    /// The header is mandatory, writes without it get a 428
+    pub fn from_request(req: &HttpRequest) -> Result<Self, ApiError> {
+        if !req.headers().contains_key(header::IF_MATCH) {
+            return Err(ApiError::PreconditionRequired);
+        }
+        match IfMatch::parse(req) {
+            Ok(IfMatch::Any) => Ok(Precondition::Any),
+            // If-Match uses the strong comparison, weak tags never match
+            Ok(IfMatch::Items(tags)) => Ok(Precondition::Versions(
+                tags.iter()
+                    .filter(|tag| !tag.weak)
+                    .filter_map(|tag| tag.tag().parse().ok())
+                    .collect(),
+            )),
+            Err(_) => Err(ApiError::Validation {
+                message: "Invalid If-Match header".to_owned(),
+                fields: vec!["If-Match".to_owned()],
+            }),
+        }
+    }
+
+    /// Fails with a 412 unless the current `version` of `resource` is expected
+    pub fn check(&self, resource: &str, version: i32) -> Result<(), ApiError> {
+        match self {
+            Precondition::Versions(versions) if !versions.contains(&version) => {
+                Err(ApiError::PreconditionFailed(format!(
+                    "{} was modified, current version is {}",
+                    resource, version
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Read response carrying the `ETag`, a 304 when `If-None-Match` names it
+pub fn versioned_response<T: Serialize>(req: &HttpRequest, version: i32, body: T) -> HttpResponse {
+    let current = EntityTag::new_strong(version.to_string());
+    let fresh = match IfNoneMatch::parse(req) {
+        Ok(IfNoneMatch::Any) => true,
+        Ok(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&current)),
+        Err(_) => false,
+    };
+    match fresh {
+        true => HttpResponse::NotModified()
+            .insert_header(ETag(current))
+            .finish(),
+        false => HttpResponse::Ok().insert_header(ETag(current)).json(body),
+    }
+}
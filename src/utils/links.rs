@@ -0,0 +1,50 @@
+use crate::models::Page;
+use actix_web::{http::header::LINK, HttpRequest, HttpResponse};
+use serde::Serialize;
+
+/// Same request with `page`/`cursor` swapped for the given one
+fn link(req: &HttpRequest, key: &str, value: &str, rel: &str) -> String {
+    let mut query: Vec<(String, String)> =
+        serde_urlencoded::from_str(req.query_string()).unwrap_or_default();
+    query.retain(|(name, _)| name != "page" && name != "cursor");
+    query.push((key.to_owned(), value.to_owned()));
+    format!(
+        "<{}?{}>; rel=\"{}\"",
+        req.uri().path(),
+        serde_urlencoded::to_string(&query).unwrap_or_default(),
+        rel
+    )
+}
+
+/// Sends a listing page along with its RFC 8288 `Link` header (first/prev/next/last)
+pub fn paginated_response<T: Serialize>(req: &HttpRequest, page: Page<T>) -> HttpResponse {
+    let links = match &page {
+        Page::Offset(page) => {
+            let last = page.total_pages.max(1);
+            let mut links = vec![link(req, "page", "1", "first")];
+            if page.page > 1 {
+                let prev = (page.page - 1).min(last).to_string();
+                links.push(link(req, "page", &prev, "prev"));
+            }
+            if page.page < page.total_pages {
+                links.push(link(req, "page", &(page.page + 1).to_string(), "next"));
+            }
+            links.push(link(req, "page", &last.to_string(), "last"));
+            links
+        }
+        Page::Cursor(page) => {
+            let mut links = vec![link(req, "cursor", &page.first_cursor, "first")];
+            if let Some(prev) = &page.prev_cursor {
+                links.push(link(req, "cursor", prev, "prev"));
+            }
+            if let Some(next) = &page.next_cursor {
+                links.push(link(req, "cursor", next, "next"));
+            }
+            links.push(link(req, "cursor", &page.last_cursor, "last"));
+            links
+        }
+    };
+    HttpResponse::Ok()
+        .insert_header((LINK, links.join(", ")))
+        .json(page)
+}
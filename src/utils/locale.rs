@@ -0,0 +1,170 @@
+use super::ApiError;
+use actix_web::{
+    dev::Payload,
+    http::header::{
+        AcceptLanguage, Header, HeaderValue, Preference, Quality, CONTENT_LANGUAGE, VARY,
+    },
+    FromRequest, HttpRequest, HttpResponse,
+};
+use std::{
+    cmp::Reverse,
+    future::{ready, Ready},
+};
+
+/// Locale of the text stored on the entities themselves
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// Language ranges of the `Accept-Language` header, most preferred first and
+/// lowercased. Ranges with `q=0` are dropped, a malformed header counts as no
+/// preference at all
+#[derive(Debug, Clone, Default)]
+pub struct AcceptedLocales(pub Vec<String>);
+
+impl FromRequest for AcceptedLocales {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, ApiError>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let ranges = match AcceptLanguage::parse(req) {
+            Ok(AcceptLanguage(mut items)) => {
+                items.retain(|item| item.quality > Quality::ZERO);
+                // stable, ranges of equal weight keep the order they were listed in
+                items.sort_by_key(|item| Reverse(item.quality));
+                items
+                    .into_iter()
+                    .map(|item| match item.item {
+                        Preference::Any => "*".to_owned(),
+                        Preference::Specific(tag) => tag.to_string().to_ascii_lowercase(),
+                    })
+                    .collect()
+            }
+            Err(_) => vec![],
+        };
+        ready(Ok(AcceptedLocales(ranges)))
+    }
+}
+
+impl AcceptedLocales {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Best of the `available` locales, `None` when the default locale wins.
+    ///
+    /// Each range is looked up as in RFC 4647, dropping subtags from the end
+    /// until a locale matches, then falls back on a more specific locale of the
+    /// same range (`fr` takes `fr-CA`) before the next range is tried
+    pub fn negotiate<'a>(&self, available: &[&'a str]) -> Option<&'a str> {
+        let find = |tag: &str| {
+            available
+                .iter()
+                .find(|locale| locale.eq_ignore_ascii_case(tag))
+                .copied()
+        };
+        for range in &self.0 {
+            if range == "*" {
+                return None;
+            }
+            let mut tag = range.as_str();
+            loop {
+                if let Some(locale) = find(tag) {
+                    return Some(locale);
+                }
+                if tag == DEFAULT_LOCALE {
+                    return None;
+                }
+                match tag.rfind('-') {
+                    Some(end) => tag = &tag[..end],
+                    None => break,
+                }
+                // a singleton never ends a tag, it goes along with its extension
+                if let Some(end) = tag.rfind('-').filter(|end| tag.len() - end == 2) {
+                    tag = &tag[..end];
+                }
+            }
+            let prefix = format!("{}-", range);
+            if let Some(locale) = available
+                .iter()
+                .find(|locale| locale.to_ascii_lowercase().starts_with(&prefix))
+            {
+                return Some(locale);
+            }
+        }
+        None
+    }
+}
+
+/// Marks a response as negotiated on `Accept-Language`, `locales` being the
+/// ones its text is in
+pub fn content_language(mut response: HttpResponse, locales: &[String]) -> HttpResponse {
+    let headers = response.headers_mut();
+    headers.insert(VARY, HeaderValue::from_static("Accept-Language"));
+    if let Ok(value) = HeaderValue::from_str(&locales.join(", ")) {
+        headers.insert(CONTENT_LANGUAGE, value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    fn accepted(header: &str) -> AcceptedLocales {
+        let req = TestRequest::default()
+            .insert_header(("accept-language", header))
+            .to_http_request();
+        AcceptedLocales::from_request(&req, &mut Payload::None)
+            .into_inner()
+            .unwrap()
+    }
+
+    fn locales(ranges: &[&str]) -> AcceptedLocales {
+        AcceptedLocales(ranges.iter().map(|range| range.to_string()).collect())
+    }
+
+    #[test]
+    fn ranges_are_sorted_by_quality() {
+        let ranges = accepted("fr;q=0.5, de, en-GB;q=0.8, es;q=0, *;q=0.1").0;
+        assert_eq!(ranges, ["de", "en-gb", "fr", "*"]);
+        // Equal weights keep the order they were listed in
+        assert_eq!(accepted("it, pt, nl;q=1").0, ["it", "pt", "nl"]);
+    }
+
+    #[test]
+    fn malformed_header_means_no_preference() {
+        assert!(accepted("fr;q=high").is_empty());
+        assert!(AcceptedLocales::from_request(
+            &TestRequest::default().to_http_request(),
+            &mut Payload::None
+        )
+        .into_inner()
+        .unwrap()
+        .is_empty());
+    }
+
+    #[test]
+    fn ranges_fall_back_on_shorter_then_longer_tags() {
+        assert_eq!(locales(&["fr-ca"]).negotiate(&["de", "fr"]), Some("fr"));
+        assert_eq!(locales(&["fr"]).negotiate(&["de", "fr-CA"]), Some("fr-CA"));
+        assert_eq!(
+            locales(&["de-ch-x-zurich"]).negotiate(&["de", "de-CH"]),
+            Some("de-CH")
+        );
+        // The first range with a match wins over an exact match further down
+        assert_eq!(
+            locales(&["pt", "fr"]).negotiate(&["fr", "pt-BR"]),
+            Some("pt-BR")
+        );
+        assert_eq!(locales(&["pt", "fr"]).negotiate(&["fr"]), Some("fr"));
+    }
+
+    #[test]
+    fn default_locale_and_wildcard_stop_the_search() {
+        // The text on the entity is in the default locale already
+        assert_eq!(locales(&["en-us", "fr"]).negotiate(&["fr"]), None);
+        assert_eq!(locales(&["*", "fr"]).negotiate(&["fr"]), None);
+        assert_eq!(locales(&["it"]).negotiate(&["fr"]), None);
+        assert_eq!(locales(&[]).negotiate(&["fr"]), None);
+    }
+}
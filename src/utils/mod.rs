@@ -1,5 +1,10 @@
+mod actor;
 mod db;
 mod error_handlers;
+mod etag;
+mod links;
+mod locale;
+#[allow(clippy::module_inception)]
 mod utils;
 
-pub use self::{db::*, error_handlers::*, utils::*};
+pub use self::{actor::*, db::*, error_handlers::*, etag::*, links::*, locale::*, utils::*};
@@ -1,7 +1,7 @@
 use crate::config::Config;
 
 pub fn server_running(config: &Config) {
-    println!("");
+    println!();
     println!("//////////////////////////////////////");
     println!(
         "// Server running on {}:{} //",
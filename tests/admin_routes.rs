@@ -0,0 +1,60 @@
+mod common;
+
+use actix_web::{http::StatusCode, test};
+use chrono::{TimeZone, Utc};
+use diesel::prelude::*;
+use fs_store::schema::{products, stores};
+use serde_json::{json, Value};
+
+#[actix_web::test]
+async fn purge_removes_rows_deleted_before_the_cutoff() {
+    let app_data = common::app_data();
+    let mut conn = app_data.pg_pool.get().unwrap();
+    let long_ago = Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap();
+    let store_id: i32 = diesel::insert_into(stores::table)
+        .values((
+            stores::name.eq("purged store"),
+            stores::is_holiday.eq(false),
+            stores::deleted_at.eq(long_ago),
+        ))
+        .returning(stores::id)
+        .get_result(&mut conn)
+        .unwrap();
+    let insert = |conn: &mut PgConnection, name: &str| -> i32 {
+        diesel::insert_into(products::table)
+            .values((
+                products::name.eq(name),
+                products::price.eq(bigdecimal::BigDecimal::from(1)),
+                products::store_id.eq(store_id),
+            ))
+            .returning(products::id)
+            .get_result(conn)
+            .unwrap()
+    };
+    let (kept, purged) = (insert(&mut conn, "kept"), insert(&mut conn, "purged"));
+    diesel::update(products::table.find(purged))
+        .set(products::deleted_at.eq(long_ago))
+        .execute(&mut conn)
+        .unwrap();
+
+    let req = test::TestRequest::post().uri("/admin/purge?before=2000-01-02T00:00:00Z");
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(res).await;
+    assert!(body["rows"]["products"].as_i64().unwrap() >= 1);
+    assert!(body["rows"]["stores"].as_i64().unwrap() >= 1);
+
+    let req = test::TestRequest::post().uri(&format!("/product/{}/restore", purged));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    let req = test::TestRequest::get().uri(&format!("/product/{}", kept));
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    assert_eq!(body["rows"]["store_id"], json!(null));
+
+    let req = test::TestRequest::post().uri("/admin/purge?before=yesterday");
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    diesel::delete(products::table.find(kept))
+        .execute(&mut conn)
+        .unwrap();
+}
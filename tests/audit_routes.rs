@@ -0,0 +1,68 @@
+mod common;
+
+use actix_web::{http::StatusCode, test};
+use serde_json::{json, Value};
+
+#[actix_web::test]
+async fn writes_are_recorded_with_their_actor_and_changes() {
+    let app_data = common::app_data();
+    let req = test::TestRequest::post()
+        .uri("/product")
+        .insert_header(("x-actor", "alice"))
+        .set_json(json!({ "name": "audited product", "price": "3.5" }));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let body: Value = test::read_body_json(res).await;
+    let id = body["rows"]["id"].as_i64().unwrap();
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/product/{}", id))
+        .insert_header(("x-actor", "bob"))
+        .insert_header(("if-match", "*"))
+        .set_json(json!({ "price": "4.25" }));
+    assert_eq!(common::send(&app_data, req).await.status(), StatusCode::OK);
+    let req = test::TestRequest::delete()
+        .uri(&format!("/product/{}", id))
+        .insert_header(("if-match", "*"));
+    assert_eq!(common::send(&app_data, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get().uri(&format!("/audit?entity=product&entity_id={}", id));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(res).await;
+    let events = body["result"].as_array().unwrap();
+    let actions: Vec<_> = events
+        .iter()
+        .map(|e| e["action"].as_str().unwrap())
+        .collect();
+    assert_eq!(actions, ["delete", "update", "create"]);
+    let actors: Vec<_> = events
+        .iter()
+        .map(|e| e["actor"].as_str().unwrap())
+        .collect();
+    assert_eq!(actors, ["anonymous", "bob", "alice"]);
+    assert_eq!(events[1]["before"]["price"], json!("3.50"));
+    assert_eq!(events[1]["after"]["price"], json!("4.25"));
+    assert!(events[1]["before"].get("name").is_none());
+    assert_eq!(events[2]["before"], json!(null));
+
+    let req = test::TestRequest::get().uri("/audit?actor=bob&entity=product");
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    let events = body["result"].as_array().unwrap();
+    assert!(events.iter().all(|e| e["actor"] == json!("bob")));
+}
+
+#[actix_web::test]
+async fn rejects_unknown_entities_and_bad_actors() {
+    let app_data = common::app_data();
+    let req = test::TestRequest::get().uri("/audit?entity=worktime");
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let req = test::TestRequest::post()
+        .uri("/category")
+        .insert_header(("x-actor", ""))
+        .set_json(json!({ "name": "never stored" }));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use actix_web::{http::StatusCode, test};
+use serde_json::{json, Value};
+
+#[actix_web::test]
+async fn patch_category_renames_or_keeps_it() {
+    let app_data = common::app_data();
+    let req = test::TestRequest::post()
+        .uri("/category")
+        .set_json(json!({ "name": "patchable" }));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let body: Value = test::read_body_json(res).await;
+    let uri = format!("/category/{}", body["rows"]["id"]);
+
+    let req = test::TestRequest::patch()
+        .uri(&uri)
+        .insert_header(("if-match", "*"))
+        .set_json(json!({}));
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    assert_eq!(body["rows"]["name"], "patchable");
+
+    let req = test::TestRequest::patch()
+        .uri(&uri)
+        .insert_header(("if-match", "*"))
+        .insert_header(("content-type", "application/merge-patch+json"))
+        .set_payload(json!({ "name": "patched" }).to_string());
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    assert_eq!(body["rows"]["name"], "patched");
+
+    let req = test::TestRequest::patch()
+        .uri(&uri)
+        .insert_header(("if-match", "*"))
+        .set_json(json!({ "name": null }));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let req = test::TestRequest::patch()
+        .uri("/category/9999")
+        .insert_header(("if-match", "*"))
+        .set_json(json!({ "name": "nowhere" }));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::delete()
+        .uri(&uri)
+        .insert_header(("if-match", "*"));
+    common::send(&app_data, req).await;
+}
+
+#[actix_web::test]
+async fn deleted_category_frees_its_name() {
+    let app_data = common::app_data();
+    let create = || {
+        test::TestRequest::post()
+            .uri("/category")
+            .set_json(json!({ "name": "trashed" }))
+    };
+    let body: Value = test::read_body_json(common::send(&app_data, create()).await).await;
+    let old_id = body["rows"]["id"].clone();
+    let req = test::TestRequest::post()
+        .uri("/product")
+        .set_json(json!({ "name": "filed product", "price": "1", "category_ids": [old_id] }));
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    let product_uri = format!("/product/{}", body["rows"]["id"]);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/category/{}", old_id))
+        .insert_header(("if-match", "*"));
+    assert_eq!(common::send(&app_data, req).await.status(), StatusCode::OK);
+    let req = test::TestRequest::get().uri(&product_uri);
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    assert_eq!(body["rows"]["categories"], json!([]));
+
+    let res = common::send(&app_data, create()).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let body: Value = test::read_body_json(res).await;
+    let new_uri = format!("/category/{}", body["rows"]["id"]);
+    let restore = || test::TestRequest::post().uri(&format!("/category/{}/restore", old_id));
+    let res = common::send(&app_data, restore()).await;
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+
+    let req = test::TestRequest::delete()
+        .uri(&new_uri)
+        .insert_header(("if-match", "*"));
+    common::send(&app_data, req).await;
+    let res = common::send(&app_data, restore()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let req = test::TestRequest::get().uri(&product_uri);
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    assert_eq!(body["rows"]["categories"][0]["id"], old_id);
+
+    for uri in [format!("/category/{}", old_id), product_uri] {
+        let req = test::TestRequest::delete()
+            .uri(&uri)
+            .insert_header(("if-match", "*"));
+        common::send(&app_data, req).await;
+    }
+}
+
+#[actix_web::test]
+async fn fuzzy_search_scores_category_names() {
+    let app_data = common::app_data();
+    let req = test::TestRequest::post()
+        .uri("/category")
+        .set_json(json!({ "name": "Écoplume" }));
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    let id = body["rows"]["id"].clone();
+
+    let req = test::TestRequest::get().uri("/category?name=ecoplum&fuzzy=true");
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["result"][0]["id"], id);
+    assert_eq!(body["result"][0]["name"], "Écoplume");
+    assert!(body["result"][0]["similarity"].as_f64().unwrap() > 0.4);
+    let req = test::TestRequest::get().uri("/category?name=ecoplum");
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    assert_eq!(body["total"], 0);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/category/{}", id))
+        .insert_header(("if-match", "*"));
+    common::send(&app_data, req).await;
+}
@@ -0,0 +1,35 @@
+use actix_web::{
+    dev::ServiceResponse,
+    test::{call_service, init_service, TestRequest},
+    web, App,
+};
+use diesel::{
+    r2d2::{ConnectionManager, Pool},
+    PgConnection,
+};
+use fs_store::{routes::init_routes, utils::AppData};
+
+/// Pool on the migrated database named by `TEST_DATABASE_URL`, which the tests
+/// cannot run without
+pub fn app_data() -> AppData {
+    let db_url = std::env::var("TEST_DATABASE_URL")
+        .expect("TEST_DATABASE_URL must name a migrated database to run the route tests");
+    let pool = Pool::builder()
+        .max_size(2)
+        .build(ConnectionManager::<PgConnection>::new(db_url))
+        .expect("Failed to connect to test database!");
+    AppData {
+        pg_pool: pool,
+        max_per_page: 100,
+    }
+}
+
+pub async fn send(app_data: &AppData, req: TestRequest) -> ServiceResponse {
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(app_data.clone()))
+            .configure(init_routes),
+    )
+    .await;
+    call_service(&app, req.to_request()).await
+}
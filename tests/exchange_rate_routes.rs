@@ -0,0 +1,268 @@
+mod common;
+
+use actix_web::{http::StatusCode, test};
+use diesel::prelude::*;
+use fs_store::schema::{products, stores};
+use serde_json::{json, Value};
+
+#[actix_web::test]
+async fn prices_are_converted_through_the_rates() {
+    let app_data = common::app_data();
+    let mut conn = app_data.pg_pool.get().unwrap();
+    let store_id: i32 = diesel::insert_into(stores::table)
+        .values((
+            stores::name.eq("krona store"),
+            stores::is_holiday.eq(false),
+            stores::currency.eq("ISK"),
+        ))
+        .returning(stores::id)
+        .get_result(&mut conn)
+        .unwrap();
+    let product_id: i32 = diesel::insert_into(products::table)
+        .values((
+            products::name.eq("krona product"),
+            products::price.eq(bigdecimal::BigDecimal::from(100)),
+            products::store_id.eq(store_id),
+        ))
+        .returning(products::id)
+        .get_result(&mut conn)
+        .unwrap();
+    let uri = format!("/product/{}", product_id);
+    let price_in =
+        |currency: &str| test::TestRequest::get().uri(&format!("{}?currency={}", uri, currency));
+    let add_rate = |from: &str, to: &str, rate: &str| {
+        test::TestRequest::post()
+            .uri("/exchange-rate")
+            .set_json(json!({
+                "from_currency": from,
+                "to_currency": to,
+                "rate": rate
+            }))
+    };
+
+    let res = common::send(&app_data, price_in("NOK")).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let res = common::send(&app_data, add_rate("ISK", "NOK", "0.0789")).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let body: Value = test::read_body_json(res).await;
+    let direct = body["rows"]["id"].as_i64().unwrap();
+    let res = common::send(&app_data, add_rate("ISK", "NOK", "0.08")).await;
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+    for (from, to, rate) in [
+        ("ISK", "ISK", "1"),
+        ("ISK", "XYZ", "1"),
+        ("ISK", "SEK", "0"),
+    ] {
+        let res = common::send(&app_data, add_rate(from, to, rate)).await;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    let res = common::send(&app_data, price_in("NOK")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(res.headers().get("etag").is_none());
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["rows"]["price"], "7.89");
+    assert_eq!(body["rows"]["currency"], "NOK");
+    let req = test::TestRequest::get().uri(&format!("/product?store_id={}&currency=NOK", store_id));
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    assert_eq!(body["result"][0]["price"], "7.89");
+    assert_eq!(body["result"][0]["currency"], "NOK");
+    let res = common::send(&app_data, price_in("nok")).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    // The reverse pair is used through its inverse
+    let res = common::send(&app_data, add_rate("SEK", "ISK", "12.5")).await;
+    let body: Value = test::read_body_json(res).await;
+    let reverse = body["rows"]["id"].as_i64().unwrap();
+    let body: Value = test::read_body_json(common::send(&app_data, price_in("SEK")).await).await;
+    assert_eq!(body["rows"]["price"], "8.00");
+
+    // Conversions round to the minor unit of the currency
+    let mut rounded = vec![reverse];
+    for (to, rate, price) in [("JPY", "1.2345", "123.00"), ("BHD", "0.002915", "0.292")] {
+        let body: Value =
+            test::read_body_json(common::send(&app_data, add_rate("ISK", to, rate)).await).await;
+        rounded.push(body["rows"]["id"].as_i64().unwrap());
+        let body: Value = test::read_body_json(common::send(&app_data, price_in(to)).await).await;
+        assert_eq!(body["rows"]["price"], price);
+    }
+
+    let prices = format!("{}/currency-prices", uri);
+    let req = test::TestRequest::put()
+        .uri(&format!("{}/ISK", prices))
+        .insert_header(("if-match", "*"))
+        .set_json(json!({ "price": "90" }));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let req = test::TestRequest::delete()
+        .uri(&format!("{}/nok", prices))
+        .insert_header(("if-match", "*"));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let req = test::TestRequest::put()
+        .uri(&format!("{}/NOK", prices))
+        .set_json(json!({ "price": "8.5" }));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::PRECONDITION_REQUIRED);
+    let req = test::TestRequest::put()
+        .uri(&format!("{}/NOK", prices))
+        .insert_header(("if-match", "*"))
+        .set_json(json!({ "price": "8.5" }));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(common::send(&app_data, price_in("NOK")).await).await;
+    assert_eq!(body["rows"]["price"], "8.50");
+    let body: Value =
+        test::read_body_json(common::send(&app_data, test::TestRequest::get().uri(&prices)).await)
+            .await;
+    assert_eq!(body["rows"][0]["currency"], "NOK");
+    let req = test::TestRequest::delete()
+        .uri(&format!("{}/NOK", prices))
+        .insert_header(("if-match", "\"1\""));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+    let req = test::TestRequest::delete()
+        .uri(&format!("{}/NOK", prices))
+        .insert_header(("if-match", "*"));
+    assert_eq!(common::send(&app_data, req).await.status(), StatusCode::OK);
+    let req = test::TestRequest::delete()
+        .uri(&format!("{}/NOK", prices))
+        .insert_header(("if-match", "*"));
+    assert_eq!(
+        common::send(&app_data, req).await.status(),
+        StatusCode::NOT_FOUND
+    );
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/exchange-rate/{}", direct))
+        .set_json(json!({ "rate": "0.1" }));
+    assert_eq!(common::send(&app_data, req).await.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(common::send(&app_data, price_in("NOK")).await).await;
+    assert_eq!(body["rows"]["price"], "10.00");
+
+    for id in std::iter::once(direct).chain(rounded) {
+        let req = test::TestRequest::delete().uri(&format!("/exchange-rate/{}", id));
+        assert_eq!(common::send(&app_data, req).await.status(), StatusCode::OK);
+    }
+    let req = test::TestRequest::get().uri(&format!("/exchange-rate/{}", direct));
+    assert_eq!(
+        common::send(&app_data, req).await.status(),
+        StatusCode::NOT_FOUND
+    );
+    diesel::delete(products::table.find(product_id))
+        .execute(&mut conn)
+        .unwrap();
+    diesel::delete(stores::table.find(store_id))
+        .execute(&mut conn)
+        .unwrap();
+}
+
+#[actix_web::test]
+async fn store_currency_is_an_iso_4217_code() {
+    let app_data = common::app_data();
+    let mut conn = app_data.pg_pool.get().unwrap();
+    let store_id: i32 = diesel::insert_into(stores::table)
+        .values((
+            stores::name.eq("priced store"),
+            stores::is_holiday.eq(false),
+        ))
+        .returning(stores::id)
+        .get_result(&mut conn)
+        .unwrap();
+    let patch = |currency: &str| {
+        test::TestRequest::patch()
+            .uri(&format!("/store/{}", store_id))
+            .insert_header(("if-match", "*"))
+            .set_json(json!({ "currency": currency }))
+    };
+
+    let req = test::TestRequest::get().uri(&format!("/store/{}", store_id));
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    assert_eq!(body["rows"]["currency"], "EUR");
+    let res = common::send(&app_data, patch("XYZ")).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let res = common::send(&app_data, patch("CHF")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["rows"]["currency"], "CHF");
+
+    diesel::delete(stores::table.find(store_id))
+        .execute(&mut conn)
+        .unwrap();
+}
+
+#[actix_web::test]
+async fn integer_prices_count_minor_units_of_the_currency() {
+    let app_data = common::app_data();
+    let mut conn = app_data.pg_pool.get().unwrap();
+    let store_id: i32 = diesel::insert_into(stores::table)
+        .values((
+            stores::name.eq("yen store"),
+            stores::is_holiday.eq(false),
+            stores::currency.eq("JPY"),
+        ))
+        .returning(stores::id)
+        .get_result(&mut conn)
+        .unwrap();
+    let req = test::TestRequest::post()
+        .uri("/product")
+        .set_json(json!({ "name": "yen product", "price": 1500, "store_id": store_id }));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["rows"]["price"], "1500.00");
+    let product_id = body["rows"]["id"].as_i64().unwrap() as i32;
+    let uri = format!("/product/{}", product_id);
+
+    let req = test::TestRequest::patch()
+        .uri(&uri)
+        .insert_header(("if-match", "*"))
+        .set_json(json!({ "price": 250 }));
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    assert_eq!(body["rows"]["price"], "250.00");
+    // Neither the product nor its store can leave the yen alone
+    let req = test::TestRequest::patch()
+        .uri(&uri)
+        .insert_header(("if-match", "*"))
+        .set_json(json!({ "store_id": null }));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let req = test::TestRequest::patch()
+        .uri(&format!("/store/{}", store_id))
+        .insert_header(("if-match", "*"))
+        .set_json(json!({ "currency": "EUR" }));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["fields"], json!(["currency"]));
+    // Leaving for a euro store, the cents are read in euros
+    let req = test::TestRequest::patch()
+        .uri(&uri)
+        .insert_header(("if-match", "*"))
+        .set_json(json!({ "price": 250, "store_id": null }));
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    assert_eq!(body["rows"]["price"], "2.50");
+
+    let set_price = |currency: &str, price: Value| {
+        test::TestRequest::put()
+            .uri(&format!("{}/currency-prices/{}", uri, currency))
+            .insert_header(("if-match", "*"))
+            .set_json(json!({ "price": price }))
+    };
+    let body: Value =
+        test::read_body_json(common::send(&app_data, set_price("KWD", json!(1250))).await).await;
+    assert_eq!(body["rows"]["price"], "1.25");
+    let res = common::send(&app_data, set_price("KWD", json!(1255))).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["fields"], json!(["price"]));
+    let res = common::send(&app_data, set_price("JPY", json!(-1))).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    diesel::delete(products::table.find(product_id))
+        .execute(&mut conn)
+        .unwrap();
+    diesel::delete(stores::table.find(store_id))
+        .execute(&mut conn)
+        .unwrap();
+}
@@ -0,0 +1,865 @@
+mod common;
+
+use actix_web::{http::StatusCode, test};
+use diesel::prelude::*;
+use fs_store::schema::{categories, products, stores};
+use serde_json::{json, Value};
+
+#[actix_web::test]
+async fn get_missing_product_returns_404() {
+    let app_data = common::app_data();
+    let res = common::send(&app_data, test::TestRequest::get().uri("/product/9999")).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["code"], "not_found");
+}
+
+#[actix_web::test]
+async fn attach_missing_category_returns_404() {
+    let app_data = common::app_data();
+    let res = common::send(
+        &app_data,
+        test::TestRequest::put()
+            .uri("/product/1/category/9999")
+            .insert_header(("if-match", "*")),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["code"], "not_found");
+}
+
+#[actix_web::test]
+async fn malformed_json_returns_400() {
+    let app_data = common::app_data();
+    for (payload, status) in [
+        ("{\"name\": \"half", StatusCode::BAD_REQUEST),
+        ("name=widget", StatusCode::BAD_REQUEST),
+        ("{\"name\": 1}", StatusCode::UNPROCESSABLE_ENTITY),
+    ] {
+        let req = test::TestRequest::post()
+            .uri("/product")
+            .insert_header(("content-type", "application/json"))
+            .set_payload(payload);
+        let res = common::send(&app_data, req).await;
+        assert_eq!(res.status(), status, "{}", payload);
+    }
+    let req = test::TestRequest::post()
+        .uri("/product")
+        .insert_header(("content-type", "application/json"))
+        .set_payload("[");
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    assert_eq!(body["code"], "bad_request");
+}
+
+#[actix_web::test]
+async fn deleted_category_or_store_returns_404() {
+    let app_data = common::app_data();
+    let mut conn = app_data.pg_pool.get().unwrap();
+    let category_id: i32 = diesel::insert_into(categories::table)
+        .values((
+            categories::name.eq("trashed"),
+            categories::deleted_at.eq(diesel::dsl::now),
+        ))
+        .returning(categories::id)
+        .get_result(&mut conn)
+        .unwrap();
+    let store_id: i32 = diesel::insert_into(stores::table)
+        .values((
+            stores::name.eq("trashed store"),
+            stores::is_holiday.eq(false),
+            stores::deleted_at.eq(diesel::dsl::now),
+        ))
+        .returning(stores::id)
+        .get_result(&mut conn)
+        .unwrap();
+
+    for (uri, message) in [
+        (
+            format!("/product/1/category/{}", category_id),
+            format!("Category {} not found", category_id),
+        ),
+        (
+            format!("/product/1/store/{}", store_id),
+            format!("Store {} not found", store_id),
+        ),
+    ] {
+        let req = test::TestRequest::put()
+            .uri(&uri)
+            .insert_header(("if-match", "*"));
+        let res = common::send(&app_data, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["message"], message);
+    }
+
+    let product = |fields: Value| {
+        let mut product = json!({ "name": "orphan product", "price": "1" });
+        product
+            .as_object_mut()
+            .unwrap()
+            .extend(fields.as_object().unwrap().clone());
+        product
+    };
+    for fields in [
+        json!({ "store_id": store_id }),
+        json!({ "category_ids": [1, category_id] }),
+    ] {
+        let req = test::TestRequest::post()
+            .uri("/product")
+            .set_json(product(fields.clone()));
+        let res = common::send(&app_data, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let req = test::TestRequest::put()
+            .uri("/product/1")
+            .insert_header(("if-match", "*"))
+            .set_json(product(fields.clone()));
+        let res = common::send(&app_data, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let res = common::send(&app_data, merge_patch("/product/1", fields)).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+    let orphans = products::table
+        .filter(products::name.eq("orphan product"))
+        .count()
+        .get_result::<i64>(&mut conn)
+        .unwrap();
+    assert_eq!(orphans, 0);
+}
+
+#[actix_web::test]
+async fn attaching_checks_and_bumps_the_product_version() {
+    let app_data = common::app_data();
+    let req = test::TestRequest::post()
+        .uri("/category")
+        .set_json(json!({ "name": "attached" }));
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    let category_id = body["rows"]["id"].clone();
+    let req = test::TestRequest::post()
+        .uri("/product")
+        .set_json(json!({ "name": "attached product", "price": "1" }));
+    let res = common::send(&app_data, req).await;
+    let created = res.headers().get("etag").cloned().unwrap();
+    let body: Value = test::read_body_json(res).await;
+    let product_uri = format!("/product/{}", body["rows"]["id"]);
+    let category_uri = format!("{}/category/{}", product_uri, category_id);
+
+    let res = common::send(&app_data, test::TestRequest::put().uri(&category_uri)).await;
+    assert_eq!(res.status(), StatusCode::PRECONDITION_REQUIRED);
+    let req = test::TestRequest::put()
+        .uri(&category_uri)
+        .insert_header(("if-match", created.clone()));
+    assert_eq!(common::send(&app_data, req).await.status(), StatusCode::OK);
+    // The categories belong to the product version
+    let req = test::TestRequest::delete()
+        .uri(&category_uri)
+        .insert_header(("if-match", created));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+    let bare = format!("{}?expand=", product_uri);
+    let res = common::send(&app_data, test::TestRequest::get().uri(&bare)).await;
+    let current = res.headers().get("etag").cloned().unwrap();
+    let req = test::TestRequest::delete()
+        .uri(&category_uri)
+        .insert_header(("if-match", current));
+    assert_eq!(common::send(&app_data, req).await.status(), StatusCode::OK);
+
+    for uri in [product_uri, format!("/category/{}", category_id)] {
+        let req = test::TestRequest::delete()
+            .uri(&uri)
+            .insert_header(("if-match", "*"));
+        common::send(&app_data, req).await;
+    }
+}
+
+#[actix_web::test]
+async fn get_many_composes_filters() {
+    let app_data = common::app_data();
+    let req = test::TestRequest::get()
+        .uri("/product?category_id=9&min_price=1&max_price=500&order=ASC&by=price&per_page=50");
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(res).await;
+    let prices: Vec<f64> = body["result"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|product| {
+            assert!(product["categories"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .any(|category| category["id"] == 9));
+            product["price"].as_str().unwrap().parse().unwrap()
+        })
+        .collect();
+    assert!(prices.iter().all(|price| (1.0..=500.0).contains(price)));
+    assert!(prices.windows(2).all(|pair| pair[0] <= pair[1]));
+}
+
+#[actix_web::test]
+async fn get_many_rejects_unknown_order_column() {
+    let app_data = common::app_data();
+    let req = test::TestRequest::get().uri("/product?order=ASC&by=price%3Bdrop");
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[actix_web::test]
+async fn get_many_reports_true_totals() {
+    let app_data = common::app_data();
+    let expected: i64 = {
+        let mut conn = app_data.pg_pool.get().unwrap();
+        products::table
+            .filter(products::store_id.eq(1))
+            .count()
+            .get_result(&mut conn)
+            .unwrap()
+    };
+    let req = test::TestRequest::get().uri("/product?store_id=1&per_page=2");
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    assert_eq!(body["total"], expected);
+    assert_eq!(body["total_pages"], (expected + 1) / 2);
+}
+
+fn ids(body: &Value) -> Vec<i64> {
+    body["result"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|product| product["id"].as_i64().unwrap())
+        .collect()
+}
+
+#[actix_web::test]
+async fn out_of_range_pages_return_422() {
+    let app_data = common::app_data();
+    for uri in [
+        "/product?page=9223372036854775807",
+        "/store?page=9223372036854775807",
+        "/category?page=9223372036854775807",
+    ] {
+        let res = common::send(&app_data, test::TestRequest::get().uri(uri)).await;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY, "{}", uri);
+    }
+}
+
+#[actix_web::test]
+async fn cursor_pages_match_offset_pages() {
+    let app_data = common::app_data();
+    let req = test::TestRequest::get().uri("/product?order=ASC&by=price&per_page=6");
+    let offset: Value = test::read_body_json(common::send(&app_data, req).await).await;
+
+    let req = test::TestRequest::get().uri("/product?order=ASC&by=price&per_page=3&cursor=");
+    let first: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    assert!(first["prev_cursor"].is_null());
+    let uri = format!(
+        "/product?per_page=3&cursor={}",
+        first["next_cursor"].as_str().unwrap()
+    );
+    let second: Value =
+        test::read_body_json(common::send(&app_data, test::TestRequest::get().uri(&uri)).await)
+            .await;
+    assert_eq!([ids(&first), ids(&second)].concat(), ids(&offset));
+
+    let uri = format!(
+        "/product?per_page=3&cursor={}",
+        second["prev_cursor"].as_str().unwrap()
+    );
+    let back: Value =
+        test::read_body_json(common::send(&app_data, test::TestRequest::get().uri(&uri)).await)
+            .await;
+    assert_eq!(ids(&back), ids(&first));
+    assert!(back["prev_cursor"].is_null());
+
+    let req = test::TestRequest::get().uri("/product?order=ASC&by=price&per_page=3&cursor=");
+    let res = common::send(&app_data, req).await;
+    let link = res
+        .headers()
+        .get("link")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_owned();
+    let last = link
+        .split(", ")
+        .find(|link| link.ends_with("rel=\"last\""))
+        .and_then(|link| link.split(['<', '>']).nth(1))
+        .unwrap()
+        .to_owned();
+    let req = test::TestRequest::get().uri(&last);
+    let last: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    assert!(last["next_cursor"].is_null());
+    assert!(last["prev_cursor"].is_string());
+    assert_eq!(ids(&last).len(), 3);
+}
+
+#[actix_web::test]
+async fn cursor_rejects_page_and_forged_tokens() {
+    let app_data = common::app_data();
+    for uri in [
+        "/product?page=2&cursor=",
+        "/product?cursor=not-a-cursor",
+        "/product?order=ASC&by=description&cursor=",
+    ] {
+        let res = common::send(&app_data, test::TestRequest::get().uri(uri)).await;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY, "{}", uri);
+    }
+}
+
+#[actix_web::test]
+async fn get_many_rejects_oversized_pages() {
+    let app_data = common::app_data();
+    let req = test::TestRequest::get().uri("/product?per_page=10000000");
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["fields"], serde_json::json!(["per_page"]));
+}
+
+#[actix_web::test]
+async fn get_many_sends_link_header() {
+    let app_data = common::app_data();
+    let req = test::TestRequest::get().uri("/product?store_id=1&per_page=1&page=2");
+    let res = common::send(&app_data, req).await;
+    let link = res
+        .headers()
+        .get("link")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_owned();
+    let body: Value = test::read_body_json(res).await;
+    let last = body["total_pages"].as_i64().unwrap();
+    assert!(link.contains("</product?store_id=1&per_page=1&page=1>; rel=\"first\""));
+    assert!(link.contains("</product?store_id=1&per_page=1&page=1>; rel=\"prev\""));
+    assert!(link.contains(&format!("page={}>; rel=\"last\"", last)));
+    assert_eq!(link.contains("rel=\"next\""), last > 2);
+}
+
+fn category_ids(body: &Value) -> Vec<i64> {
+    body["rows"]["categories"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|category| category["id"].as_i64().unwrap())
+        .collect()
+}
+
+#[actix_web::test]
+async fn create_and_update_assign_categories() {
+    let app_data = common::app_data();
+    let req = test::TestRequest::post()
+        .uri("/product")
+        .set_json(json!({ "name": "categorized", "price": "3.5", "category_ids": [2, 1, 2] }));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let created: Value = test::read_body_json(res).await;
+    assert_eq!(category_ids(&created), vec![1, 2]);
+    let id = created["rows"]["id"].as_i64().unwrap();
+
+    let uri = format!("/product/{}", id);
+    let req = test::TestRequest::put()
+        .uri(&uri)
+        .insert_header(("if-match", "*"))
+        .set_json(json!({ "name": "categorized", "price": "3.5", "category_ids": [3] }));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let updated: Value = test::read_body_json(res).await;
+    assert_eq!(category_ids(&updated), vec![3]);
+
+    let req = test::TestRequest::put()
+        .uri(&uri)
+        .insert_header(("if-match", "*"))
+        .set_json(json!({ "name": "renamed", "price": "3.5" }));
+    let untouched: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    assert_eq!(category_ids(&untouched), vec![3]);
+
+    let req = test::TestRequest::delete()
+        .uri(&uri)
+        .insert_header(("if-match", "*"));
+    common::send(&app_data, req).await;
+}
+
+#[actix_web::test]
+async fn create_with_missing_category_rolls_back() {
+    let app_data = common::app_data();
+    let name = "orphan categorized";
+    let req = test::TestRequest::post()
+        .uri("/product")
+        .set_json(json!({ "name": name, "price": "1", "category_ids": [1, 9999] }));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    let mut conn = app_data.pg_pool.get().unwrap();
+    let count: i64 = products::table
+        .filter(products::name.eq(name))
+        .count()
+        .get_result(&mut conn)
+        .unwrap();
+    assert_eq!(count, 0);
+}
+
+#[actix_web::test]
+async fn get_product_expands_relations() {
+    let app_data = common::app_data();
+    let (id, store_id): (i32, Option<i32>) = {
+        let mut conn = app_data.pg_pool.get().unwrap();
+        products::table
+            .filter(products::store_id.is_not_null())
+            .select((products::id, products::store_id))
+            .first(&mut conn)
+            .unwrap()
+    };
+    let uri = format!("/product/{}", id);
+    let body: Value =
+        test::read_body_json(common::send(&app_data, test::TestRequest::get().uri(&uri)).await)
+            .await;
+    assert_eq!(body["rows"]["id"], id);
+    assert!(body["rows"]["categories"].is_array());
+    assert_eq!(body["rows"]["store"]["id"], store_id.unwrap());
+    assert!(body["rows"]["store"]["worktimes"].is_array());
+
+    let uri = format!("/product/{}?expand=categories", id);
+    let body: Value =
+        test::read_body_json(common::send(&app_data, test::TestRequest::get().uri(&uri)).await)
+            .await;
+    assert!(body["rows"]["categories"].is_array());
+    assert!(body["rows"].get("store").is_none());
+
+    let uri = format!("/product/{}?expand=owner", id);
+    let res = common::send(&app_data, test::TestRequest::get().uri(&uri)).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+fn merge_patch(uri: &str, patch: Value) -> test::TestRequest {
+    test::TestRequest::patch()
+        .uri(uri)
+        .insert_header(("if-match", "*"))
+        .insert_header(("content-type", "application/merge-patch+json"))
+        .set_payload(patch.to_string())
+}
+
+#[actix_web::test]
+async fn patch_follows_merge_patch_semantics() {
+    let app_data = common::app_data();
+    let req = test::TestRequest::post().uri("/product").set_json(json!({
+        "name": "patched",
+        "price": "3.5",
+        "description": "kept around",
+        "category_ids": [1, 2]
+    }));
+    let created: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    let uri = format!("/product/{}", created["rows"]["id"]);
+
+    let req = merge_patch(&uri, json!({ "price": "4.25" }));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["rows"]["price"], "4.25");
+    assert_eq!(body["rows"]["description"], "kept around");
+    assert_eq!(category_ids(&body), vec![1, 2]);
+
+    let req = merge_patch(&uri, json!({ "description": null, "category_ids": null }));
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    assert!(body["rows"]["description"].is_null());
+    assert_eq!(body["rows"]["price"], "4.25");
+    assert_eq!(category_ids(&body), Vec::<i64>::new());
+
+    let req = merge_patch(&uri, json!({}));
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    assert_eq!(body["rows"]["name"], "patched");
+
+    for patch in [json!({ "name": null }), json!({ "name": "ab" })] {
+        let res = common::send(&app_data, merge_patch(&uri, patch)).await;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    let res = common::send(&app_data, merge_patch("/product/9999", json!({}))).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::delete()
+        .uri(&uri)
+        .insert_header(("if-match", "*"));
+    common::send(&app_data, req).await;
+}
+
+#[actix_web::test]
+async fn writes_are_conditional_on_the_etag() {
+    let app_data = common::app_data();
+    let req = test::TestRequest::post()
+        .uri("/product")
+        .set_json(json!({ "name": "versioned", "price": "1.5" }));
+    let res = common::send(&app_data, req).await;
+    let created = res
+        .headers()
+        .get("etag")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_owned();
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(created, "\"1\"");
+    assert_eq!(body["rows"]["version"], 1);
+    let uri = format!("/product/{}", body["rows"]["id"]);
+    let bare = format!("{}?expand=", uri);
+
+    let res = common::send(&app_data, test::TestRequest::get().uri(&uri)).await;
+    assert!(res.headers().get("etag").is_none());
+    let res = common::send(&app_data, test::TestRequest::get().uri(&bare)).await;
+    assert_eq!(res.headers().get("etag").unwrap(), created.as_str());
+    let req = test::TestRequest::get()
+        .uri(&bare)
+        .insert_header(("if-none-match", created.as_str()));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+
+    let res = common::send(
+        &app_data,
+        merge_patch(&uri, json!({ "price": "2" })).insert_header(("if-match", created.as_str())),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers().get("etag").unwrap(), "\"2\"");
+
+    let req = test::TestRequest::patch()
+        .uri(&uri)
+        .insert_header(("if-match", created.as_str()))
+        .set_json(json!({ "price": "3" }));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+    let req = test::TestRequest::delete().uri(&uri);
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::PRECONDITION_REQUIRED);
+
+    let req = test::TestRequest::get()
+        .uri(&bare)
+        .insert_header(("if-none-match", created.as_str()));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["rows"]["price"], "2.00");
+
+    let req = test::TestRequest::delete()
+        .uri(&uri)
+        .insert_header(("if-match", "\"2\""));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn deleted_product_is_hidden_until_restored() {
+    let app_data = common::app_data();
+    let req = test::TestRequest::post()
+        .uri("/product")
+        .set_json(json!({ "name": "trashed product", "price": "2", "category_ids": [1, 2] }));
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    let id = body["rows"]["id"].as_i64().unwrap();
+    let uri = format!("/product/{}", id);
+    let delete = || {
+        test::TestRequest::delete()
+            .uri(&uri)
+            .insert_header(("if-match", "*"))
+    };
+
+    let res = common::send(&app_data, delete()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = common::send(&app_data, test::TestRequest::get().uri(&uri)).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    let req = test::TestRequest::get().uri("/product?name=trashed%20product&per_page=100");
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    assert!(!ids(&body).contains(&id));
+    let res = common::send(&app_data, delete()).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    let res = common::send(&app_data, merge_patch(&uri, json!({}))).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::post().uri(&format!("{}/restore", uri));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(category_ids(&body), vec![1, 2]);
+    let res = common::send(&app_data, test::TestRequest::get().uri(&uri)).await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    common::send(&app_data, delete()).await;
+}
+
+#[actix_web::test]
+async fn price_changes_are_kept_as_history() {
+    let app_data = common::app_data();
+    let req = test::TestRequest::post()
+        .uri("/product")
+        .set_json(json!({ "name": "priced product", "price": "3.5" }));
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    let uri = format!("/product/{}", body["rows"]["id"]);
+    for patch in [
+        json!({ "price": 400 }),
+        json!({ "name": "renamed" }),
+        json!({ "price": "5" }),
+    ] {
+        let res = common::send(&app_data, merge_patch(&uri, patch)).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    let req = test::TestRequest::get().uri(&format!("{}/prices", uri));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(res).await;
+    let history = body["rows"].as_array().unwrap();
+    let prices: Vec<_> = history
+        .iter()
+        .map(|p| p["price"].as_str().unwrap())
+        .collect();
+    assert_eq!(prices, ["3.50", "4.00", "5.00"]);
+
+    let at = history[1]["effective_from"].as_str().unwrap();
+    let req = test::TestRequest::get().uri(&format!("{}/prices?at={}", uri, at));
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    assert_eq!(body["rows"], json!([history[1]]));
+    let req = test::TestRequest::get().uri(&format!("{}/prices?at=2000-01-01T00:00:00Z", uri));
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    assert_eq!(body["rows"], json!([]));
+    let req = test::TestRequest::get().uri(&format!("{}/prices?at=yesterday", uri));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let req = test::TestRequest::delete()
+        .uri(&uri)
+        .insert_header(("if-match", "*"));
+    common::send(&app_data, req).await;
+    let req = test::TestRequest::get().uri(&format!("{}/prices", uri));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn prices_are_exact_decimals() {
+    let app_data = common::app_data();
+    let create = |price: Value| {
+        test::TestRequest::post()
+            .uri("/product")
+            .set_json(json!({ "name": "exact price", "price": price }))
+    };
+    for (price, expected) in [
+        (json!("10.1"), "10.10"),
+        (json!(1010), "10.10"),
+        (json!("0.30"), "0.30"),
+    ] {
+        let res = common::send(&app_data, create(price)).await;
+        assert_eq!(res.status(), StatusCode::CREATED);
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["rows"]["price"], expected);
+        let req = test::TestRequest::delete()
+            .uri(&format!("/product/{}", body["rows"]["id"]))
+            .insert_header(("if-match", "*"));
+        common::send(&app_data, req).await;
+    }
+
+    for price in [
+        json!(10.1),
+        json!("10.123"),
+        json!("1e3"),
+        json!("NaN"),
+        json!("-1"),
+        json!("1000000.01"),
+        json!(null),
+    ] {
+        let res = common::send(&app_data, create(price.clone())).await;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY, "{}", price);
+    }
+    let res = common::send(
+        &app_data,
+        merge_patch("/product/1", json!({ "price": null })),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[actix_web::test]
+async fn text_search_ranks_matches() {
+    let app_data = common::app_data();
+    let mut ids = vec![];
+    for (name, description) in [
+        ("zorblax widget", "a zorblax in every home"),
+        ("plain widget", "mentions a zorblax once"),
+        ("other thing", "nothing to see"),
+    ] {
+        let req = test::TestRequest::post().uri("/product").set_json(json!({
+            "name": name,
+            "price": "1",
+            "description": description
+        }));
+        let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+        ids.push(body["rows"]["id"].as_i64().unwrap());
+    }
+    let req = test::TestRequest::post()
+        .uri("/translation")
+        .set_json(json!({
+            "entity_type": "product",
+            "entity_id": ids[2],
+            "field": "name",
+            "locale": "fr",
+            "value": "chose zorblax"
+        }));
+    assert_eq!(
+        common::send(&app_data, req).await.status(),
+        StatusCode::CREATED
+    );
+    let search = |query: &str| test::TestRequest::get().uri(&format!("/product?{}", query));
+    let found = |body: &Value| -> Vec<i64> {
+        body["result"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| p["id"].as_i64().unwrap())
+            .collect()
+    };
+
+    let res = common::send(&app_data, search("q=zorblax")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(found(&body), vec![ids[0], ids[2], ids[1]]);
+    assert!(
+        body["result"][0]["rank"].as_f64().unwrap() > body["result"][2]["rank"].as_f64().unwrap()
+    );
+    assert!(body["result"][0]["snippet"]
+        .as_str()
+        .unwrap()
+        .contains("<mark>zorblax</mark>"));
+    // Words are stemmed, all of them have to match
+    let body: Value =
+        test::read_body_json(common::send(&app_data, search("q=zorblax+widgets")).await).await;
+    assert_eq!(found(&body), vec![ids[0], ids[1]]);
+    let body: Value = test::read_body_json(
+        common::send(&app_data, search("q=zorblax+-widget&order=ASC&by=name")).await,
+    )
+    .await;
+    assert_eq!(found(&body), vec![ids[2]]);
+    assert!(body["result"][0]["rank"].is_number());
+    let body: Value =
+        test::read_body_json(common::send(&app_data, search("per_page=1")).await).await;
+    assert!(body["result"][0].get("snippet").is_none());
+
+    let res = common::send(&app_data, search("q=zorblax&cursor=")).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let res = common::send(&app_data, search("q=")).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    for id in ids {
+        let req = test::TestRequest::delete()
+            .uri(&format!("/product/{}", id))
+            .insert_header(("if-match", "*"));
+        common::send(&app_data, req).await;
+    }
+}
+
+#[actix_web::test]
+async fn snippets_escape_the_product_text() {
+    let app_data = common::app_data();
+    let req = test::TestRequest::post().uri("/product").set_json(json!({
+        "name": "<b>quuxbar</b> & co",
+        "price": "1",
+        "description": "<img src=x onerror=alert(1)>"
+    }));
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    let id = body["rows"]["id"].as_i64().unwrap();
+
+    let req = test::TestRequest::get()
+        .uri("/product?q=quuxbar")
+        .insert_header(("accept-language", "fr, *"));
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    let snippet = body["result"][0]["snippet"].as_str().unwrap();
+    assert!(snippet.contains("<mark>quuxbar</mark>&lt;/b&gt; &amp; co"));
+    assert!(snippet.contains("&lt;img src=x"));
+    assert!(!snippet.contains("<img") && !snippet.contains("</b>"));
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/product/{}", id))
+        .insert_header(("if-match", "*"));
+    common::send(&app_data, req).await;
+}
+
+#[actix_web::test]
+async fn fuzzy_search_tolerates_typos_and_accents() {
+    let app_data = common::app_data();
+    let mut ids = vec![];
+    for name in ["Écoméditique bio", "carnet kraft", "éco-méditique"] {
+        let req = test::TestRequest::post()
+            .uri("/product")
+            .set_json(json!({ "name": name, "price": "1" }));
+        let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+        ids.push(body["rows"]["id"].as_i64().unwrap());
+    }
+    let req = test::TestRequest::post()
+        .uri("/translation")
+        .set_json(json!({
+            "entity_type": "product",
+            "entity_id": ids[1],
+            "field": "name",
+            "locale": "fr",
+            "value": "cahier écoméditique"
+        }));
+    assert_eq!(
+        common::send(&app_data, req).await.status(),
+        StatusCode::CREATED
+    );
+    let search = |query: &str| test::TestRequest::get().uri(&format!("/product?{}", query));
+    let found = |body: &Value| -> Vec<i64> {
+        body["result"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| p["id"].as_i64().unwrap())
+            .filter(|id| ids.contains(id))
+            .collect()
+    };
+
+    let res = common::send(&app_data, search("name=ECOMEDITQUE&fuzzy=true&per_page=50")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(res).await;
+    // The translated name matches as well as the default ones
+    let mut matched = found(&body);
+    matched.sort();
+    assert_eq!(matched, ids);
+    let similarities: Vec<f64> = body["result"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p["similarity"].as_f64().unwrap())
+        .collect();
+    assert!(similarities.windows(2).all(|pair| pair[0] >= pair[1]));
+    // Without fuzzy the name is a plain substring
+    let body: Value =
+        test::read_body_json(common::send(&app_data, search("name=ecomeditque")).await).await;
+    assert!(found(&body).is_empty());
+    assert!(body["result"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|p| p.get("similarity").is_none()));
+    let body: Value = test::read_body_json(
+        common::send(
+            &app_data,
+            search("name=ecomeditque&fuzzy=true&order=DESC&by=id&per_page=50"),
+        )
+        .await,
+    )
+    .await;
+    assert_eq!(found(&body), vec![ids[2], ids[1], ids[0]]);
+    assert!(body["result"][0]["similarity"].is_number());
+
+    for query in [
+        "fuzzy=true",
+        "name=&fuzzy=true",
+        "name=ecomeditque&fuzzy=true&cursor=",
+    ] {
+        let res = common::send(&app_data, search(query)).await;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    for id in ids {
+        let req = test::TestRequest::delete()
+            .uri(&format!("/product/{}", id))
+            .insert_header(("if-match", "*"));
+        common::send(&app_data, req).await;
+    }
+}
@@ -0,0 +1,848 @@
+mod common;
+
+use actix_web::{http::StatusCode, test};
+use chrono::{DateTime, Duration, SecondsFormat, TimeZone, Timelike, Utc};
+use chrono_tz::{America::New_York, Tz};
+use diesel::prelude::*;
+use fs_store::{
+    schema::{products, stores},
+    utils::AppData,
+};
+use serde_json::{json, Value};
+
+#[actix_web::test]
+async fn get_missing_store_returns_404() {
+    let app_data = common::app_data();
+    let res = common::send(&app_data, test::TestRequest::get().uri("/store/9999")).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["code"], "not_found");
+}
+
+#[actix_web::test]
+async fn missing_store_product_count_returns_404() {
+    let app_data = common::app_data();
+    let res = common::send(&app_data, test::TestRequest::get().uri("/store/9999/count")).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn delete_missing_store_returns_404() {
+    let app_data = common::app_data();
+    let req = test::TestRequest::delete()
+        .uri("/store/9999")
+        .insert_header(("if-match", "*"));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+fn store_payload(name: &str, days: [i32; 7]) -> Value {
+    let worktimes: Vec<Value> = days
+        .iter()
+        .map(|day| {
+            json!({
+                "day_id": day,
+                "am_open": "08:00",
+                "am_close": "12:00",
+                "pm_open": "14:00",
+                "pm_close": "18:00"
+            })
+        })
+        .collect();
+    json!({ "name": name, "is_holiday": false, "worktimes": worktimes })
+}
+
+fn count_stores_named(app_data: &AppData, store_name: &str) -> i64 {
+    let mut conn = app_data.pg_pool.get().unwrap();
+    stores::table
+        .filter(stores::name.eq(store_name))
+        .count()
+        .get_result(&mut conn)
+        .unwrap()
+}
+
+#[actix_web::test]
+async fn create_store_returns_store_with_worktimes() {
+    let app_data = common::app_data();
+    let req = test::TestRequest::post()
+        .uri("/store")
+        .set_json(store_payload("created with hours", [1, 2, 3, 4, 5, 6, 7]));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["rows"]["name"], "created with hours");
+    assert_eq!(body["rows"]["worktimes"].as_array().unwrap().len(), 7);
+}
+
+#[actix_web::test]
+async fn create_store_rolls_back_on_worktime_constraint_error() {
+    let app_data = common::app_data();
+    let req = test::TestRequest::post()
+        .uri("/store")
+        .set_json(store_payload("duplicated day store", [1, 1, 3, 4, 5, 6, 7]));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+    assert_eq!(count_stores_named(&app_data, "duplicated day store"), 0);
+}
+
+#[actix_web::test]
+async fn create_store_rejects_invalid_worktime() {
+    let app_data = common::app_data();
+    let mut payload = store_payload("invalid hours store", [1, 2, 3, 4, 5, 6, 7]);
+    payload["worktimes"][0]["am_open"] = json!("25:99");
+    let req = test::TestRequest::post().uri("/store").set_json(payload);
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(count_stores_named(&app_data, "invalid hours store"), 0);
+}
+
+#[actix_web::test]
+async fn update_store_rolls_back_on_foreign_worktime() {
+    let app_data = common::app_data();
+    let req = test::TestRequest::post()
+        .uri("/store")
+        .set_json(store_payload("store before update", [1, 2, 3, 4, 5, 6, 7]));
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    let foreign_worktime = body["rows"]["worktimes"][0]["id"].as_i64().unwrap();
+
+    let req = test::TestRequest::post()
+        .uri("/store")
+        .set_json(store_payload("other store", [1, 2, 3, 4, 5, 6, 7]));
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    let other_id = body["rows"]["id"].as_i64().unwrap();
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/store/{}", other_id))
+        .insert_header(("if-match", "*"))
+        .set_json(json!({
+            "name": "store after update",
+            "is_holiday": true,
+            "worktimes": [{ "id": foreign_worktime, "am_open": "09:00" }]
+        }));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    assert_eq!(count_stores_named(&app_data, "store after update"), 0);
+}
+
+fn new_store(conn: &mut PgConnection, store_name: &str) -> i32 {
+    diesel::insert_into(stores::table)
+        .values((stores::name.eq(store_name), stores::is_holiday.eq(false)))
+        .returning(stores::id)
+        .get_result(conn)
+        .unwrap()
+}
+
+fn prod_count(conn: &mut PgConnection, store_id: i32) -> i32 {
+    stores::table
+        .find(store_id)
+        .select(stores::prod_count)
+        .first(conn)
+        .unwrap()
+}
+
+fn move_product(conn: &mut PgConnection, prod_id: i32, store_id: Option<i32>) {
+    diesel::update(products::table.find(prod_id))
+        .set(products::store_id.eq(store_id))
+        .execute(conn)
+        .unwrap();
+}
+
+#[actix_web::test]
+async fn prod_count_follows_every_transition() {
+    let app_data = common::app_data();
+    let mut conn = app_data.pg_pool.get().unwrap();
+    let (a, b) = (
+        new_store(&mut conn, "count a"),
+        new_store(&mut conn, "count b"),
+    );
+    let insert = |conn: &mut PgConnection, store_id: Option<i32>| -> i32 {
+        diesel::insert_into(products::table)
+            .values((
+                products::name.eq("counted"),
+                products::price.eq(bigdecimal::BigDecimal::from(1)),
+                products::store_id.eq(store_id),
+            ))
+            .returning(products::id)
+            .get_result(conn)
+            .unwrap()
+    };
+    let counts = |conn: &mut PgConnection| (prod_count(conn, a), prod_count(conn, b));
+
+    // insert with and without a store
+    let first = insert(&mut conn, Some(a));
+    let second = insert(&mut conn, None);
+    assert_eq!(counts(&mut conn), (1, 0));
+    // no store -> store
+    move_product(&mut conn, second, Some(a));
+    assert_eq!(counts(&mut conn), (2, 0));
+    // same store and unrelated column updates are no-ops
+    move_product(&mut conn, second, Some(a));
+    diesel::update(products::table.find(first))
+        .set(products::name.eq("renamed"))
+        .execute(&mut conn)
+        .unwrap();
+    assert_eq!(counts(&mut conn), (2, 0));
+    // store -> other store
+    move_product(&mut conn, second, Some(b));
+    assert_eq!(counts(&mut conn), (1, 1));
+    // store -> no store
+    move_product(&mut conn, second, None);
+    assert_eq!(counts(&mut conn), (1, 0));
+    // soft delete and restore
+    let trash = |conn: &mut PgConnection, deleted_at: Option<DateTime<Utc>>| {
+        diesel::update(products::table.find(first))
+            .set(products::deleted_at.eq(deleted_at))
+            .execute(conn)
+            .unwrap();
+    };
+    trash(&mut conn, Some(Utc::now()));
+    assert_eq!(counts(&mut conn), (0, 0));
+    trash(&mut conn, None);
+    assert_eq!(counts(&mut conn), (1, 0));
+    // delete without and with a store
+    diesel::delete(products::table.find(second))
+        .execute(&mut conn)
+        .unwrap();
+    assert_eq!(counts(&mut conn), (1, 0));
+    diesel::delete(products::table.find(first))
+        .execute(&mut conn)
+        .unwrap();
+    assert_eq!(counts(&mut conn), (0, 0));
+}
+
+#[actix_web::test]
+async fn recount_fixes_drifted_counts() {
+    let app_data = common::app_data();
+    let store_id = {
+        let mut conn = app_data.pg_pool.get().unwrap();
+        let store_id = new_store(&mut conn, "drifted count");
+        diesel::update(stores::table.find(store_id))
+            .set(stores::prod_count.eq(42))
+            .execute(&mut conn)
+            .unwrap();
+        store_id
+    };
+    let res = common::send(&app_data, test::TestRequest::post().uri("/store/recount")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(res).await;
+    assert!(body["rows"]["count"].as_i64().unwrap() >= 1);
+    let mut conn = app_data.pg_pool.get().unwrap();
+    assert_eq!(prod_count(&mut conn, store_id), 0);
+
+    let req = test::TestRequest::get().uri(&format!("/audit?entity=store&entity_id={}", store_id));
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    assert_eq!(body["result"][0]["action"], "recount");
+    assert_eq!(body["result"][0]["before"], json!({ "prod_count": 42 }));
+    assert_eq!(body["result"][0]["after"], json!({ "prod_count": 0 }));
+}
+
+#[actix_web::test]
+async fn create_store_accepts_24_hour_and_overnight_hours() {
+    let app_data = common::app_data();
+    let mut payload = store_payload("late hours store", [1, 2, 3, 4, 5, 6, 7]);
+    payload["worktimes"][0]["pm_open"] = json!("19:00");
+    payload["worktimes"][0]["pm_close"] = json!("02:00");
+    payload["timezone"] = json!("America/New_York");
+    let req = test::TestRequest::post().uri("/store").set_json(payload);
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(res).await;
+    let monday = &body["rows"]["worktimes"][0];
+    assert_eq!(monday["pm_open"], "19:00");
+    assert_eq!(monday["pm_close"], "02:00");
+}
+
+#[actix_web::test]
+async fn create_store_rejects_inconsistent_slots() {
+    let app_data = common::app_data();
+    let cases = [
+        // open == close
+        json!({ "am_open": "08:00", "am_close": "08:00" }),
+        // slots overlap
+        json!({ "am_close": "15:00" }),
+        // only the last slot can cross midnight
+        json!({ "am_open": "23:00", "am_close": "01:00" }),
+        // half open slot
+        json!({ "pm_close": null }),
+        // overnight slot runs into tuesday morning
+        json!({ "pm_open": "20:00", "pm_close": "09:00" }),
+    ];
+    for case in cases {
+        let mut payload = store_payload("inconsistent hours store", [1, 2, 3, 4, 5, 6, 7]);
+        for (key, value) in case.as_object().unwrap() {
+            payload["worktimes"][0][key] = value.clone();
+        }
+        let req = test::TestRequest::post().uri("/store").set_json(payload);
+        let res = common::send(&app_data, req).await;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY, "{}", case);
+    }
+    assert_eq!(count_stores_named(&app_data, "inconsistent hours store"), 0);
+}
+
+#[actix_web::test]
+async fn update_store_rejects_overlapping_slots() {
+    let app_data = common::app_data();
+    let req = test::TestRequest::post()
+        .uri("/store")
+        .set_json(store_payload(
+            "overlap before update",
+            [1, 2, 3, 4, 5, 6, 7],
+        ));
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    let store_id = body["rows"]["id"].as_i64().unwrap();
+    let worktime = body["rows"]["worktimes"][0]["id"].as_i64().unwrap();
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/store/{}", store_id))
+        .insert_header(("if-match", "*"))
+        .set_json(json!({
+            "name": "overlap after update",
+            "is_holiday": false,
+            "worktimes": [{ "id": worktime, "pm_open": "11:00" }]
+        }));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(count_stores_named(&app_data, "overlap after update"), 0);
+}
+
+/// Instant of 2023-02-13 (a Monday) plus `days` in `tz`, encoded for a query string
+fn monday_at(tz: Tz, days: u32, hour: u32) -> String {
+    tz.with_ymd_and_hms(2023, 2, 13 + days, hour, 0, 0)
+        .unwrap()
+        .to_rfc3339_opts(SecondsFormat::Secs, true)
+        .replace('+', "%2B")
+}
+
+/// Hour of a date sent back in the store zone
+fn hour_of(at: &Value) -> u32 {
+    DateTime::parse_from_rfc3339(at.as_str().unwrap())
+        .unwrap()
+        .hour()
+}
+
+#[actix_web::test]
+async fn status_follows_worktimes_and_overnight_slots() {
+    let app_data = common::app_data();
+    let mut payload = store_payload("late monday store", [1, 2, 3, 4, 5, 6, 7]);
+    payload["worktimes"][0]["pm_open"] = json!("20:00");
+    payload["worktimes"][0]["pm_close"] = json!("02:00");
+    payload["timezone"] = json!("America/New_York");
+    let req = test::TestRequest::post().uri("/store").set_json(payload);
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    let store_id = body["rows"]["id"].as_i64().unwrap();
+    assert!(body["rows"]["open_now"].is_boolean());
+
+    for (days, hour, open, next_hour) in [
+        (0, 10, true, 12),
+        (0, 13, false, 20),
+        (1, 1, true, 2),
+        (1, 3, false, 8),
+    ] {
+        let uri = format!(
+            "/store/{}/status?at={}",
+            store_id,
+            monday_at(New_York, days, hour)
+        );
+        let res = common::send(&app_data, test::TestRequest::get().uri(&uri)).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["rows"]["open"], open, "{}", uri);
+        assert_eq!(
+            hour_of(&body["rows"]["next_change_at"]),
+            next_hour,
+            "{}",
+            uri
+        );
+
+        let uri = format!(
+            "/store?name=late%20monday%20store&open_at={}",
+            monday_at(New_York, days, hour)
+        );
+        let body: Value =
+            test::read_body_json(common::send(&app_data, test::TestRequest::get().uri(&uri)).await)
+                .await;
+        let listed = body["result"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|store| store["id"] == store_id);
+        assert_eq!(listed, open, "{}", uri);
+    }
+}
+
+#[actix_web::test]
+async fn status_of_store_on_holiday_is_closed() {
+    let app_data = common::app_data();
+    let mut payload = store_payload("holiday status store", [1, 2, 3, 4, 5, 6, 7]);
+    payload["is_holiday"] = json!(true);
+    let req = test::TestRequest::post().uri("/store").set_json(payload);
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    let uri = format!(
+        "/store/{}/status?at={}",
+        body["rows"]["id"],
+        monday_at(New_York, 0, 10)
+    );
+    let body: Value =
+        test::read_body_json(common::send(&app_data, test::TestRequest::get().uri(&uri)).await)
+            .await;
+    assert_eq!(body["rows"]["open"], false);
+    assert!(body["rows"]["next_change_at"].is_null());
+
+    let res = common::send(
+        &app_data,
+        test::TestRequest::get().uri("/store/9999/status"),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn worktimes_are_read_in_the_store_zone() {
+    let app_data = common::app_data();
+    let mut tokyo = store_payload("zoned store", [1, 2, 3, 4, 5, 6, 7]);
+    tokyo["timezone"] = json!("Asia/Tokyo");
+    let req = test::TestRequest::post().uri("/store").set_json(tokyo);
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    assert_eq!(body["rows"]["timezone"], "Asia/Tokyo");
+    let tokyo_id = body["rows"]["id"].clone();
+    let req = test::TestRequest::post()
+        .uri("/store")
+        .set_json(store_payload("zoned store", [1, 2, 3, 4, 5, 6, 7]));
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    assert_eq!(body["rows"]["timezone"], "UTC");
+    let utc_id = body["rows"]["id"].clone();
+
+    // 01:00 UTC is 10:00 in Tokyo
+    let at = monday_at(Tz::UTC, 0, 1);
+    let uri = format!("/store/{}/status?at={}", tokyo_id, at);
+    let body: Value =
+        test::read_body_json(common::send(&app_data, test::TestRequest::get().uri(&uri)).await)
+            .await;
+    assert_eq!(body["rows"]["open"], true);
+    assert_eq!(body["rows"]["at"], "2023-02-13T10:00:00+09:00");
+    assert_eq!(body["rows"]["next_change_at"], "2023-02-13T12:00:00+09:00");
+
+    let uri = format!("/store?name=zoned%20store&open_at={}", at);
+    let body: Value =
+        test::read_body_json(common::send(&app_data, test::TestRequest::get().uri(&uri)).await)
+            .await;
+    let listed: Vec<Value> = body["result"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|store| store["id"].clone())
+        .collect();
+    assert!(listed.contains(&tokyo_id));
+    assert!(!listed.contains(&utc_id));
+
+    let uri = format!("/store/{}", tokyo_id);
+    let req = test::TestRequest::put()
+        .uri(&uri)
+        .insert_header(("if-match", "*"))
+        .set_json(json!({
+            "name": "zoned store",
+            "is_holiday": false,
+            "timezone": "UTC",
+            "worktimes": []
+        }));
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    assert_eq!(body["rows"]["timezone"], "UTC");
+    assert!(body["rows"]["open_now"].is_boolean());
+}
+
+#[actix_web::test]
+async fn store_timezone_must_be_known() {
+    let app_data = common::app_data();
+    let mut payload = store_payload("unknown zone store", [1, 2, 3, 4, 5, 6, 7]);
+    payload["timezone"] = json!("Mars/Olympus_Mons");
+    let req = test::TestRequest::post().uri("/store").set_json(payload);
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(count_stores_named(&app_data, "unknown zone store"), 0);
+}
+
+#[actix_web::test]
+async fn date_filter_honours_offsets() {
+    let app_data = common::app_data();
+    let req = test::TestRequest::post()
+        .uri("/store")
+        .set_json(store_payload("dated store", [1, 2, 3, 4, 5, 6, 7]));
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    let store_id = body["rows"]["id"].clone();
+    let created_at = DateTime::parse_from_rfc3339(body["rows"]["created_at"].as_str().unwrap())
+        .unwrap()
+        .with_timezone(&Utc);
+
+    for (after, listed) in [
+        (created_at - Duration::seconds(1), true),
+        (created_at + Duration::seconds(1), false),
+    ] {
+        // Same instant written in another zone
+        let after = after
+            .with_timezone(&New_York)
+            .to_rfc3339_opts(SecondsFormat::Micros, true)
+            .replace('+', "%2B");
+        let uri = format!("/store?name=dated%20store&after={}", after);
+        let body: Value =
+            test::read_body_json(common::send(&app_data, test::TestRequest::get().uri(&uri)).await)
+                .await;
+        let found = body["result"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|store| store["id"] == store_id);
+        assert_eq!(found, listed, "{}", uri);
+    }
+}
+
+fn closure_uri(store_id: &Value, closure_id: Option<&Value>) -> String {
+    match closure_id {
+        Some(closure_id) => format!("/store/{}/closures/{}", store_id, closure_id),
+        None => format!("/store/{}/closures", store_id),
+    }
+}
+
+#[actix_web::test]
+async fn closures_crud() {
+    let app_data = common::app_data();
+    let req = test::TestRequest::post()
+        .uri("/store")
+        .set_json(store_payload("closing store", [1, 2, 3, 4, 5, 6, 7]));
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    let store_id = body["rows"]["id"].clone();
+    assert_eq!(body["rows"]["closures"], json!([]));
+
+    let starts_on = Utc::now().date_naive() + Duration::days(30);
+    let req = test::TestRequest::post()
+        .uri(&closure_uri(&store_id, None))
+        .set_json(json!({
+            "starts_on": starts_on,
+            "ends_on": starts_on + Duration::days(2),
+            "reason": "inventory"
+        }));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(res).await;
+    let closure_id = body["rows"]["id"].clone();
+    assert!(body["rows"]["am_open"].is_null());
+
+    let body: Value = test::read_body_json(
+        common::send(
+            &app_data,
+            test::TestRequest::get().uri(&format!("/store/{}", store_id)),
+        )
+        .await,
+    )
+    .await;
+    assert_eq!(body["rows"]["closures"][0]["id"], closure_id);
+
+    for (payload, field) in [
+        // Overlaps the last day of the first closure
+        (
+            json!({ "starts_on": starts_on + Duration::days(2), "ends_on": starts_on + Duration::days(3) }),
+            "starts_on",
+        ),
+        (
+            json!({ "starts_on": starts_on + Duration::days(5), "ends_on": starts_on + Duration::days(4) }),
+            "__all__",
+        ),
+        (
+            json!({ "starts_on": starts_on + Duration::days(5), "ends_on": starts_on + Duration::days(5), "am_open": "09:00" }),
+            "__all__",
+        ),
+    ] {
+        let req = test::TestRequest::post()
+            .uri(&closure_uri(&store_id, None))
+            .set_json(payload);
+        let res = common::send(&app_data, req).await;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body: Value = test::read_body_json(res).await;
+        assert!(
+            body["fields"].as_array().unwrap().contains(&json!(field)),
+            "{}",
+            body
+        );
+    }
+
+    let req = test::TestRequest::put()
+        .uri(&closure_uri(&store_id, Some(&closure_id)))
+        .insert_header(("if-match", "*"))
+        .set_json(json!({
+            "starts_on": starts_on,
+            "ends_on": starts_on,
+            "am_open": "09:00",
+            "am_close": "13:00"
+        }));
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    assert_eq!(body["rows"]["am_close"], "13:00");
+    assert!(body["rows"]["reason"].is_null());
+
+    let req = test::TestRequest::get().uri(&closure_uri(&store_id, None));
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    assert_eq!(body["rows"].as_array().unwrap().len(), 1);
+
+    let req = test::TestRequest::delete()
+        .uri(&closure_uri(&store_id, Some(&closure_id)))
+        .insert_header(("if-match", "*"));
+    assert_eq!(common::send(&app_data, req).await.status(), StatusCode::OK);
+    let req = test::TestRequest::get().uri(&closure_uri(&store_id, Some(&closure_id)));
+    assert_eq!(
+        common::send(&app_data, req).await.status(),
+        StatusCode::NOT_FOUND
+    );
+    let req = test::TestRequest::get().uri(&closure_uri(&json!(9999), None));
+    assert_eq!(
+        common::send(&app_data, req).await.status(),
+        StatusCode::NOT_FOUND
+    );
+}
+
+#[actix_web::test]
+async fn closures_override_worktimes() {
+    let app_data = common::app_data();
+    let mut payload = store_payload("christmas store", [1, 2, 3, 4, 5, 6, 7]);
+    payload["timezone"] = json!("America/New_York");
+    let req = test::TestRequest::post().uri("/store").set_json(payload);
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    let store_id = body["rows"]["id"].clone();
+    for closure in [
+        json!({ "starts_on": "2023-02-13", "ends_on": "2023-02-13" }),
+        json!({ "starts_on": "2023-02-14", "ends_on": "2023-02-14", "am_open": "10:00", "am_close": "13:00" }),
+    ] {
+        let req = test::TestRequest::post()
+            .uri(&closure_uri(&store_id, None))
+            .set_json(closure);
+        assert_eq!(common::send(&app_data, req).await.status(), StatusCode::OK);
+    }
+
+    for (days, hour, open, next_hour) in [
+        (0, 10, false, 10),
+        (1, 9, false, 10),
+        (1, 11, true, 13),
+        (1, 14, false, 8),
+        (2, 15, true, 18),
+    ] {
+        let uri = format!(
+            "/store/{}/status?at={}",
+            store_id,
+            monday_at(New_York, days, hour)
+        );
+        let body: Value =
+            test::read_body_json(common::send(&app_data, test::TestRequest::get().uri(&uri)).await)
+                .await;
+        assert_eq!(body["rows"]["open"], open, "{}", uri);
+        assert_eq!(
+            hour_of(&body["rows"]["next_change_at"]),
+            next_hour,
+            "{}",
+            uri
+        );
+
+        let uri = format!(
+            "/store?name=christmas%20store&open_at={}",
+            monday_at(New_York, days, hour)
+        );
+        let body: Value =
+            test::read_body_json(common::send(&app_data, test::TestRequest::get().uri(&uri)).await)
+                .await;
+        let listed = body["result"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|store| store["id"] == store_id);
+        assert_eq!(listed, open, "{}", uri);
+    }
+}
+
+#[actix_web::test]
+async fn patch_store_changes_only_given_fields() {
+    let app_data = common::app_data();
+    let mut payload = store_payload("patched store", [1, 2, 3, 4, 5, 6, 7]);
+    payload["timezone"] = json!("Europe/Paris");
+    let req = test::TestRequest::post().uri("/store").set_json(payload);
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    let uri = format!("/store/{}", body["rows"]["id"]);
+    let monday = body["rows"]["worktimes"][0]["id"].clone();
+
+    let req = test::TestRequest::patch()
+        .uri(&uri)
+        .insert_header(("if-match", "*"))
+        .insert_header(("content-type", "application/merge-patch+json"))
+        .set_payload(
+            json!({
+                "is_holiday": true,
+                "worktimes": [{ "id": monday, "pm_open": null, "pm_close": null }]
+            })
+            .to_string(),
+        );
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["rows"]["name"], "patched store");
+    assert_eq!(body["rows"]["timezone"], "Europe/Paris");
+    assert_eq!(body["rows"]["is_holiday"], true);
+    let worktime = body["rows"]["worktimes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|worktime| worktime["id"] == monday)
+        .unwrap();
+    assert_eq!(worktime["am_close"], "12:00");
+    assert!(worktime["pm_open"].is_null());
+
+    let req = test::TestRequest::patch()
+        .uri(&uri)
+        .insert_header(("if-match", "*"))
+        .set_json(json!({ "is_holiday": null }));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let req = test::TestRequest::patch()
+        .uri("/store/9999")
+        .insert_header(("if-match", "*"))
+        .set_json(json!({ "name": "nowhere" }));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn closures_bump_the_store_version() {
+    let app_data = common::app_data();
+    let req = test::TestRequest::post()
+        .uri("/store")
+        .set_json(store_payload("versioned store", [1, 2, 3, 4, 5, 6, 7]));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.headers().get("etag").unwrap(), "\"1\"");
+    let body: Value = test::read_body_json(res).await;
+    let store_id = body["rows"]["id"].clone();
+    let uri = format!("/store/{}", store_id);
+
+    let req = test::TestRequest::post()
+        .uri(&closure_uri(&store_id, None))
+        .set_json(json!({ "starts_on": "2023-12-24", "ends_on": "2023-12-25" }));
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    let closure = closure_uri(&store_id, Some(&body["rows"]["id"]));
+    for (if_match, status) in [
+        (None, StatusCode::PRECONDITION_REQUIRED),
+        (Some("\"1\""), StatusCode::PRECONDITION_FAILED),
+        (Some("\"2\""), StatusCode::OK),
+    ] {
+        let mut req = test::TestRequest::put()
+            .uri(&closure)
+            .set_json(json!({ "starts_on": "2023-12-24", "ends_on": "2023-12-24" }));
+        if let Some(if_match) = if_match {
+            req = req.insert_header(("if-match", if_match));
+        }
+        assert_eq!(common::send(&app_data, req).await.status(), status);
+    }
+    let req = test::TestRequest::delete()
+        .uri(&closure)
+        .insert_header(("if-match", "\"2\""));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+    let req = test::TestRequest::delete()
+        .uri(&closure)
+        .insert_header(("if-match", "\"3\""));
+    assert_eq!(common::send(&app_data, req).await.status(), StatusCode::OK);
+
+    // Products and opening status are embedded, reads carry no ETag
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header(("if-none-match", "\"1\""));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(res.headers().get("etag").is_none());
+
+    let req = test::TestRequest::delete()
+        .uri(&uri)
+        .insert_header(("if-match", "\"3\""));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+    let req = test::TestRequest::delete()
+        .uri(&uri)
+        .insert_header(("if-match", "W/\"4\", \"4\""));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn deleted_store_keeps_worktimes_for_restore() {
+    let app_data = common::app_data();
+    let req = test::TestRequest::post()
+        .uri("/store")
+        .set_json(store_payload("trashed store", [1, 2, 3, 4, 5, 6, 7]));
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    let store_id = body["rows"]["id"].clone();
+    let uri = format!("/store/{}", store_id);
+
+    let req = test::TestRequest::delete()
+        .uri(&uri)
+        .insert_header(("if-match", "*"));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(res).await;
+    assert!(body["rows"]["deleted_at"].is_string());
+    for uri in [
+        uri.clone(),
+        format!("{}/status", uri),
+        closure_uri(&store_id, None),
+    ] {
+        let res = common::send(&app_data, test::TestRequest::get().uri(&uri)).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+    let req = test::TestRequest::get().uri("/store?name=trashed%20store&per_page=100");
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    assert!(!body["result"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|store| store["id"] == store_id));
+
+    let req = test::TestRequest::post().uri(&format!("{}/restore", uri));
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(res).await;
+    assert!(body["rows"]["deleted_at"].is_null());
+    assert_eq!(body["rows"]["worktimes"].as_array().unwrap().len(), 7);
+    let res = common::send(&app_data, test::TestRequest::get().uri(&uri)).await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let req = test::TestRequest::post().uri("/store/9999/restore");
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn fuzzy_search_scores_store_names() {
+    let app_data = common::app_data();
+    let mut ids = vec![];
+    for name in ["Écoméditique Lyon", "Boulangerie Quimper"] {
+        let req = test::TestRequest::post()
+            .uri("/store")
+            .set_json(store_payload(name, [1, 2, 3, 4, 5, 6, 7]));
+        let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+        ids.push(body["rows"]["id"].as_i64().unwrap());
+    }
+    let req = test::TestRequest::get().uri("/store?name=ecomediteque+lyon&fuzzy=true&per_page=50");
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(res).await;
+    let found: Vec<&Value> = body["result"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|store| ids.contains(&store["id"].as_i64().unwrap()))
+        .collect();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0]["id"], ids[0]);
+    assert!(found[0]["similarity"].as_f64().unwrap() > 0.4);
+    let req = test::TestRequest::get().uri("/store?name=ecomediteque&fuzzy=true&cursor=");
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    for id in ids {
+        let req = test::TestRequest::delete()
+            .uri(&format!("/store/{}", id))
+            .insert_header(("if-match", "*"));
+        common::send(&app_data, req).await;
+    }
+}
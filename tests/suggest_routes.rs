@@ -0,0 +1,71 @@
+mod common;
+
+use actix_web::{http::StatusCode, test};
+use serde_json::{json, Value};
+
+#[actix_web::test]
+async fn suggest_mixes_entities_by_name_prefix() {
+    let app_data = common::app_data();
+    let worktimes: Vec<Value> = (1..=7)
+        .map(|day| json!({ "day_id": day, "am_open": "08:00", "am_close": "12:00" }))
+        .collect();
+    let mut created = vec![];
+    for (uri, body) in [
+        ("/category", json!({ "name": "Zéphyr" })),
+        ("/product", json!({ "name": "zephyrine tea", "price": "1" })),
+        ("/product", json!({ "name": "zephyr_gone", "price": "1" })),
+        (
+            "/store",
+            json!({ "name": "ZEPHYR", "is_holiday": false, "worktimes": worktimes }),
+        ),
+    ] {
+        let req = test::TestRequest::post().uri(uri).set_json(body);
+        let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+        created.push(format!("{}/{}", uri, body["rows"]["id"]));
+    }
+    let req = test::TestRequest::delete()
+        .uri(&created[2])
+        .insert_header(("if-match", "*"));
+    assert_eq!(common::send(&app_data, req).await.status(), StatusCode::OK);
+    let suggest = |query: &str| test::TestRequest::get().uri(&format!("/suggest?{}", query));
+    let found = |body: &Value| -> Vec<String> {
+        body["rows"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| format!("/{}/{}", s["type"].as_str().unwrap(), s["id"]))
+            .filter(|uri| created.contains(uri))
+            .collect()
+    };
+
+    let res = common::send(&app_data, suggest("q=zephyr&limit=50")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(res).await;
+    // Exact names first, the deleted product is left out
+    let mut exact = found(&body)[..2].to_vec();
+    exact.sort();
+    assert_eq!(exact, vec![created[0].clone(), created[3].clone()]);
+    assert_eq!(found(&body)[2..], [created[1].clone()]);
+    let body: Value =
+        test::read_body_json(common::send(&app_data, suggest("q=Z%C3%A9ph")).await).await;
+    assert_eq!(found(&body).len(), 3);
+    let body: Value =
+        test::read_body_json(common::send(&app_data, suggest("q=zephyr&limit=1")).await).await;
+    assert_eq!(body["rows"].as_array().unwrap().len(), 1);
+    // LIKE wildcards are taken literally
+    let body: Value =
+        test::read_body_json(common::send(&app_data, suggest("q=zeph%25tea")).await).await;
+    assert!(found(&body).is_empty());
+
+    for query in ["", "q=", "q=zephyr&limit=0", "q=zephyr&limit=51"] {
+        let res = common::send(&app_data, suggest(query)).await;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    for uri in [&created[0], &created[1], &created[3]] {
+        let req = test::TestRequest::delete()
+            .uri(uri)
+            .insert_header(("if-match", "*"));
+        common::send(&app_data, req).await;
+    }
+}
@@ -0,0 +1,223 @@
+mod common;
+
+use actix_web::{http::StatusCode, test};
+use diesel::prelude::*;
+use fs_store::schema::{products, stores, translations};
+use serde_json::{json, Value};
+
+fn translate(entity: &str, id: i32, field: &str, locale: &str, value: &str) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/translation")
+        .set_json(json!({
+            "entity_type": entity,
+            "entity_id": id,
+            "field": field,
+            "locale": locale,
+            "value": value
+        }))
+}
+
+#[actix_web::test]
+async fn reads_follow_accept_language() {
+    let app_data = common::app_data();
+    let mut conn = app_data.pg_pool.get().unwrap();
+    let store_id: i32 = diesel::insert_into(stores::table)
+        .values((stores::name.eq("fruit store"), stores::is_holiday.eq(false)))
+        .returning(stores::id)
+        .get_result(&mut conn)
+        .unwrap();
+    let product_id: i32 = diesel::insert_into(products::table)
+        .values((
+            products::name.eq("apple"),
+            products::description.eq("red fruit"),
+            products::price.eq(bigdecimal::BigDecimal::from(2)),
+            products::store_id.eq(store_id),
+        ))
+        .returning(products::id)
+        .get_result(&mut conn)
+        .unwrap();
+    let uri = format!("/product/{}", product_id);
+    let bare = format!("{}?expand=", uri);
+    let get = |language: &str| {
+        test::TestRequest::get()
+            .uri(&uri)
+            .insert_header(("accept-language", language))
+    };
+
+    let res = common::send(&app_data, test::TestRequest::get().uri(&bare)).await;
+    let untranslated = res.headers().get("etag").cloned().unwrap();
+    for (field, locale, value) in [
+        ("name", "fr", "pomme"),
+        ("description", "fr", "fruit rouge"),
+        ("name", "ES", "manzana"),
+    ] {
+        let res = common::send(
+            &app_data,
+            translate("product", product_id, field, locale, value),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::CREATED);
+    }
+    let res = common::send(
+        &app_data,
+        translate("store", store_id, "name", "fr-ca", "fruiterie"),
+    )
+    .await;
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["rows"]["locale"], "fr-CA");
+
+    let res = common::send(&app_data, get("fr-CA, en;q=0.5")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(res.headers().get("etag").is_none());
+    assert_eq!(res.headers().get("content-language").unwrap(), "fr, fr-CA");
+    assert_eq!(res.headers().get("vary").unwrap(), "Accept-Language");
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["rows"]["name"], "pomme");
+    assert_eq!(body["rows"]["description"], "fruit rouge");
+    assert_eq!(body["rows"]["store"]["name"], "fruiterie");
+
+    // Fields missing in the picked locale keep the default text
+    let body: Value =
+        test::read_body_json(common::send(&app_data, get("de, es;q=0.8")).await).await;
+    assert_eq!(body["rows"]["name"], "manzana");
+    assert_eq!(body["rows"]["description"], "red fruit");
+
+    for language in ["en-US, fr;q=0.9", "*", "de", "fr;q=0"] {
+        let req = test::TestRequest::get()
+            .uri(&bare)
+            .insert_header(("accept-language", language));
+        let res = common::send(&app_data, req).await;
+        assert_eq!(res.headers().get("content-language").unwrap(), "en");
+        assert!(res.headers().get("etag").is_some());
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["rows"]["name"], "apple");
+    }
+    let res = common::send(&app_data, test::TestRequest::get().uri(&bare)).await;
+    assert_ne!(res.headers().get("etag").unwrap(), &untranslated);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/product?store_id={}", store_id))
+        .insert_header(("accept-language", "fr"));
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    assert_eq!(body["result"][0]["name"], "pomme");
+    let req = test::TestRequest::get()
+        .uri(&format!("/store/{}", store_id))
+        .insert_header(("accept-language", "fr"));
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    assert_eq!(body["rows"]["name"], "fruiterie");
+    assert_eq!(body["rows"]["products"][0]["name"], "pomme");
+
+    let req = test::TestRequest::get().uri(&format!(
+        "/translation?entity=product&entity_id={}&locale=fr",
+        product_id
+    ));
+    let body: Value = test::read_body_json(common::send(&app_data, req).await).await;
+    assert_eq!(body["total"], 2);
+    let name = body["result"][0]["id"].as_i64().unwrap();
+    let req = test::TestRequest::put()
+        .uri(&format!("/translation/{}", name))
+        .set_json(json!({ "value": "pomme rouge" }));
+    assert_eq!(
+        common::send(&app_data, req).await.status(),
+        StatusCode::PRECONDITION_REQUIRED
+    );
+    // The translation is part of the product, its version is checked
+    let req = test::TestRequest::put()
+        .uri(&format!("/translation/{}", name))
+        .insert_header(("if-match", "\"1\""))
+        .set_json(json!({ "value": "pomme rouge" }));
+    assert_eq!(
+        common::send(&app_data, req).await.status(),
+        StatusCode::PRECONDITION_FAILED
+    );
+    let req = test::TestRequest::put()
+        .uri(&format!("/translation/{}", name))
+        .insert_header(("if-match", "*"))
+        .set_json(json!({ "value": "pomme rouge" }));
+    assert_eq!(common::send(&app_data, req).await.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(common::send(&app_data, get("fr")).await).await;
+    assert_eq!(body["rows"]["name"], "pomme rouge");
+    let req = test::TestRequest::delete()
+        .uri(&format!("/translation/{}", name))
+        .insert_header(("if-match", "*"));
+    assert_eq!(common::send(&app_data, req).await.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(common::send(&app_data, get("fr")).await).await;
+    assert_eq!(body["rows"]["name"], "apple");
+    assert_eq!(body["rows"]["description"], "fruit rouge");
+
+    diesel::delete(
+        translations::table.filter(
+            translations::entity_type
+                .eq("product")
+                .and(translations::entity_id.eq(product_id))
+                .or(translations::entity_type
+                    .eq("store")
+                    .and(translations::entity_id.eq(store_id))),
+        ),
+    )
+    .execute(&mut conn)
+    .unwrap();
+    diesel::delete(products::table.find(product_id))
+        .execute(&mut conn)
+        .unwrap();
+    diesel::delete(stores::table.find(store_id))
+        .execute(&mut conn)
+        .unwrap();
+}
+
+#[actix_web::test]
+async fn translations_are_checked() {
+    let app_data = common::app_data();
+    let mut conn = app_data.pg_pool.get().unwrap();
+    let store_id: i32 = diesel::insert_into(stores::table)
+        .values((
+            stores::name.eq("checked store"),
+            stores::is_holiday.eq(false),
+        ))
+        .returning(stores::id)
+        .get_result(&mut conn)
+        .unwrap();
+
+    let res = common::send(
+        &app_data,
+        translate("store", store_id, "name", "de", "Laden"),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let res = common::send(
+        &app_data,
+        translate("store", store_id, "name", "DE", "Geschäft"),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+    for (entity, field, locale) in [
+        ("store", "name", "d"),
+        ("store", "name", "en"),
+        ("store", "name", "de-toolongsubtag"),
+        ("store", "timezone", "de"),
+        ("worktime", "name", "de"),
+    ] {
+        let res = common::send(&app_data, translate(entity, store_id, field, locale, "x")).await;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    let res = common::send(
+        &app_data,
+        translate("store", i32::MAX, "name", "de", "Laden"),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    let req = test::TestRequest::get().uri("/translation?locale=not_a_tag");
+    let res = common::send(&app_data, req).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    diesel::delete(
+        translations::table
+            .filter(translations::entity_type.eq("store"))
+            .filter(translations::entity_id.eq(store_id)),
+    )
+    .execute(&mut conn)
+    .unwrap();
+    diesel::delete(stores::table.find(store_id))
+        .execute(&mut conn)
+        .unwrap();
+}